@@ -0,0 +1,146 @@
+//! Backups of the task file. `backup` writes the tasks to a file named after the task file and the time (in UTC), like
+//! `tasks-2024-06-02T15-04.json`, in the backup directory; `backups` lists them, newest first, and `restore --backup`
+//! brings one back.
+use std::{fs, io, path::{Path, PathBuf}};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use super::TaskError;
+
+/// The directory backups go in, next to the task file, unless `--backup-dir` or the config file say otherwise.
+pub(crate) const DIR_NAME: &str = "backups";
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H-%M";
+
+/// The directory the backups of the task file are kept in.
+pub(crate) fn dir(file_path: &str, configured: Option<&str>) -> PathBuf {
+    match configured {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(file_path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).join(DIR_NAME)
+    }
+}
+
+/// The name of the task file split into the part backups start with and their extension, e.g. ("tasks", "json").
+fn parts(file_path: &str) -> (String, String) {
+    let path = Path::new(file_path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+    (stem, extension)
+}
+
+/// Where a backup made at `now` goes: <stem>-<time>.<extension>, or <stem>-<time>-<n>.<extension>, numbered after
+/// the newest, if there already are backups from that minute. Numbers are never reused, so that the new backup is
+/// always listed first even after older ones from the same minute were deleted.
+pub(crate) fn path(dir: &Path, file_path: &str, now: DateTime<Utc>) -> PathBuf {
+    let (stem, extension) = parts(file_path);
+    let time = now.format(TIME_FORMAT).to_string();
+    let newest = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|entry| made(&entry.file_name().to_string_lossy(), &stem, &extension))
+        .filter(|(made, _)| made.format(TIME_FORMAT).to_string() == time)
+        .map(|(_, n)| n)
+        .max();
+    match newest {
+        Some(n) => dir.join(format!("{}-{}-{}.{}", stem, time, n + 1, extension)),
+        None => dir.join(format!("{}-{}.{}", stem, time, extension))
+    }
+}
+
+/// When the file with the given name was backed up, along with its number within that minute, or `None` if it is not
+/// a backup of the task file.
+fn made(name: &str, stem: &str, extension: &str) -> Option<(NaiveDateTime, u32)> {
+    let rest = name.strip_prefix(stem)?.strip_prefix('-')?.strip_suffix(extension)?.strip_suffix('.')?;
+    let time = rest.get(..16)?;
+    let n = match &rest[16..] {
+        "" => 0,
+        suffix => suffix.strip_prefix('-')?.parse().ok()?
+    };
+    Some((NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?, n))
+}
+
+/// The backups of the task file in `dir`, newest first. There are none if the directory doesn't exist.
+pub(crate) fn list(dir: &Path, file_path: &str) -> Result<Vec<PathBuf>, TaskError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(TaskError::io(&dir.to_string_lossy(), error))
+    };
+    let (stem, extension) = parts(file_path);
+    let mut backups = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|error| TaskError::io(&dir.to_string_lossy(), error))?;
+        if let Some(made) = made(&entry.file_name().to_string_lossy(), &stem, &extension) {
+            backups.push((made, entry.path()));
+        }
+    }
+    backups.sort_by_key(|(made, _)| std::cmp::Reverse(*made));
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/// The backup `restore --backup` was given: its number in the list `backups` prints (1 being the newest), or a path.
+pub(crate) fn find(target: &str, dir: &Path, file_path: &str) -> Result<PathBuf, TaskError> {
+    if let Ok(index) = target.parse::<usize>() {
+        let backups = list(dir, file_path)?;
+        return index.checked_sub(1).and_then(|index| backups.get(index)).cloned().ok_or_else(|| {
+            TaskError::Usage(format!("There is no backup number {} ({} backup(s) in {})", target, backups.len(), dir.display()))
+        });
+    }
+    let path = PathBuf::from(target);
+    if !path.is_file() {
+        return Err(TaskError::io(target, io::Error::new(io::ErrorKind::NotFound, "no such backup file")));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("task-tracker-backups-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn backups_are_named_after_the_task_file_and_the_time() {
+        let dir = backup_dir("names");
+        let first = path(&dir, "data/tasks.json", at("2024-06-02T15:04:59Z"));
+        assert_eq!(first, dir.join("tasks-2024-06-02T15-04.json"));
+        fs::write(&first, "").unwrap();
+        // Same minute
+        assert_eq!(path(&dir, "data/tasks.json", at("2024-06-02T15:04:30Z")), dir.join("tasks-2024-06-02T15-04-1.json"));
+        fs::write(dir.join("tasks-2024-06-02T15-04-3.json"), "").unwrap();
+        fs::remove_file(&first).unwrap();
+        assert_eq!(path(&dir, "data/tasks.json", at("2024-06-02T15:04:30Z")), dir.join("tasks-2024-06-02T15-04-4.json"));
+        assert_eq!(path(&dir, "data/tasks.json", at("2024-06-02T15:05:00Z")), dir.join("tasks-2024-06-02T15-05.json"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(made("tasks-2024-06-02T15-04-12.json", "tasks", "json").map(|(_, n)| n), Some(12));
+        assert_eq!(made("tasks-work-2024-06-02T15-04.json", "tasks", "json"), None);
+        assert_eq!(made("tasks-2024-06-02T15-04.yaml", "tasks", "json"), None);
+        assert_eq!(made("tasks-2024-13-02T15-04.json", "tasks", "json"), None);
+        assert_eq!(super::dir("tasks.json", None), Path::new(".").join(DIR_NAME));
+        assert_eq!(super::dir("data/tasks.json", Some("/mnt/backups")), Path::new("/mnt/backups"));
+    }
+
+    #[test]
+    fn backups_are_listed_newest_first() {
+        let dir = backup_dir("list");
+        for name in ["tasks-2024-06-02T15-04-1.json", "tasks-2024-06-02T15-04.json", "tasks-2025-01-01T00-00.json", "tasks-2024-06-02T15-04-10.json", "notes.txt", "other-2030-01-01T00-00.json"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let names: Vec<String> = list(&dir, "tasks.json").unwrap().iter().map(|path| path.file_name().unwrap().to_string_lossy().to_string()).collect();
+        let second = find("2", &dir, "tasks.json").unwrap();
+        let out_of_range = find("5", &dir, "tasks.json");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, ["tasks-2025-01-01T00-00.json", "tasks-2024-06-02T15-04-10.json", "tasks-2024-06-02T15-04-1.json", "tasks-2024-06-02T15-04.json"]);
+        assert_eq!(second, dir.join("tasks-2024-06-02T15-04-10.json"));
+        assert!(matches!(out_of_range, Err(TaskError::Usage(_))));
+        assert!(find("0", &dir, "tasks.json").is_err());
+        assert!(list(&dir, "tasks.json").unwrap().is_empty());
+    }
+}
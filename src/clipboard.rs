@@ -0,0 +1,89 @@
+//! Sending command output to the system clipboard instead of stdout.
+//!
+//! The system clipboard is only available when built with the `clipboard` feature. Everything else goes through the
+//! `Clipboard` trait, so a missing clipboard (headless machines, SSH sessions) degrades to printing.
+use std::io::{self, Write};
+
+pub trait Clipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+#[cfg(feature = "clipboard")]
+struct SystemClipboard(arboard::Clipboard);
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.0.set_text(text.to_string()).map_err(|error| error.to_string())
+    }
+}
+
+/// Opens the system clipboard, if there is one.
+#[cfg(feature = "clipboard")]
+pub fn system() -> Result<Box<dyn Clipboard>, String> {
+    let clipboard = arboard::Clipboard::new().map_err(|error| error.to_string())?;
+    Ok(Box::new(SystemClipboard(clipboard)))
+}
+
+/// Opens the system clipboard, if there is one.
+#[cfg(not(feature = "clipboard"))]
+pub fn system() -> Result<Box<dyn Clipboard>, String> {
+    Err("clipboard support is not enabled in this build".to_string())
+}
+
+/// Copies the rendered output of `count` tasks to the clipboard and confirms it on `out`. If the clipboard is
+/// unavailable or refuses the text, warns on stderr and writes the output itself to `out` instead.
+pub fn copy_or_print(text: &str, count: usize, clipboard: Result<&mut dyn Clipboard, String>, out: &mut dyn Write) -> io::Result<()> {
+    match clipboard.and_then(|clipboard| clipboard.set_text(text)) {
+        Ok(()) => writeln!(out, "copied {} tasks to clipboard", count),
+        Err(reason) => {
+            eprintln!("Warning: could not copy to the clipboard ({}), printing instead.", reason);
+            write!(out, "{}", text)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Records what was copied, or refuses everything when `fail` is set.
+    #[derive(Default)]
+    pub(crate) struct MockClipboard {
+        pub(crate) text: Option<String>,
+        pub(crate) fail: bool
+    }
+
+    impl Clipboard for MockClipboard {
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            if self.fail {
+                return Err("no display".to_string());
+            }
+            self.text = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn copies_to_clipboard() {
+        let mut clipboard = MockClipboard::default();
+        let mut out = vec![];
+        copy_or_print("ID: 1\nID: 2\n", 2, Ok(&mut clipboard), &mut out).unwrap();
+
+        assert_eq!(clipboard.text.as_deref(), Some("ID: 1\nID: 2\n"));
+        assert_eq!(String::from_utf8(out).unwrap(), "copied 2 tasks to clipboard\n");
+    }
+
+    #[test]
+    fn falls_back_to_stdout() {
+        let mut out = vec![];
+        copy_or_print("ID: 1\n", 1, Err("no clipboard".to_string()), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "ID: 1\n");
+
+        let mut clipboard = MockClipboard { fail: true, ..MockClipboard::default() };
+        let mut out = vec![];
+        copy_or_print("ID: 1\n", 1, Ok(&mut clipboard), &mut out).unwrap();
+        assert!(clipboard.text.is_none());
+        assert_eq!(String::from_utf8(out).unwrap(), "ID: 1\n");
+    }
+}
@@ -0,0 +1,287 @@
+//! Shell completion scripts, generated from the command table in [`crate::help`] so that they list the same
+//! commands and options as `help` does.
+//!
+//! The words offered after a command are read from its arguments in the table: flags (`--cascade`), literal words
+//! (`all`, `todotxt`) and the alternatives of a placeholder (`<low|medium|high>`). The bash script also completes task
+//! IDs, by running `list all --json`.
+use std::{fmt::Display, str::FromStr};
+use crate::{help::{self, CommandHelp}, STATUS_NAMES};
+
+const PRIORITIES: &str = "low medium high";
+const BACKENDS: &str = "json sqlite";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish
+}
+
+impl FromStr for Shell {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("Unknown shell: {} (expected bash, zsh or fish)", s))
+        }
+    }
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish")
+        }
+    }
+}
+
+/// The completion script for `shell`, ending with a newline.
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish()
+    }
+}
+
+/// A global option, split out of its help entry (e.g. `-y, --yes` or `--file <path>`).
+struct Flag {
+    short: Option<&'static str>,
+    long: &'static str,
+    takes_value: bool,
+    summary: &'static str
+}
+
+fn flags() -> Vec<Flag> {
+    help::OPTIONS.iter().map(|option| {
+        let (short, rest) = match option.flag.split_once(", ") {
+            Some((short, rest)) => (Some(short.trim_start_matches('-')), rest),
+            None => (None, option.flag)
+        };
+        let end = rest.find([' ', '=']).unwrap_or(rest.len());
+        Flag { short, long: rest[..end].trim_start_matches('-'), takes_value: end < rest.len(), summary: option.summary }
+    }).collect()
+}
+
+/// Every name a command can be typed as, leaving out the ones that look like options (`--help`).
+fn names(command: &CommandHelp) -> impl Iterator<Item = &'static str> {
+    std::iter::once(command.name).chain(command.aliases.iter().copied()).filter(|name| !name.starts_with('-'))
+}
+
+/// The words worth offering after a command: its flags, literal words and the alternatives of its placeholders.
+fn arguments(name: &str) -> Vec<&'static str> {
+    let mut words = vec![];
+    for command in help::COMMANDS.iter().filter(|command| command.name == name) {
+        for word in command.args.split_whitespace() {
+            let word = word.trim_matches(|c| c == '[' || c == ']');
+            let choices = match word.strip_prefix('<').and_then(|word| word.strip_suffix('>')) {
+                Some(placeholder) if placeholder.contains('|') => placeholder,
+                Some(_) => continue,
+                None => word
+            };
+            for choice in choices.split('|') {
+                let plain = choice.trim_start_matches('-');
+                if !plain.is_empty() && plain.chars().all(|c| c.is_ascii_lowercase() || c == '-') && !words.contains(&choice) {
+                    words.push(choice);
+                }
+            }
+        }
+    }
+    words
+}
+
+/// Whether the command's first argument is a task ID.
+fn takes_id(command: &CommandHelp) -> bool {
+    command.args.starts_with("<id") || command.args.starts_with("[<id")
+}
+
+/// The status names offered, as a shell word list: the main name of each status, leaving out the aliases.
+fn statuses() -> String {
+    STATUS_NAMES.iter().map(|(_, names)| names[0]).collect::<Vec<_>>().join(" ")
+}
+
+/// The values an option is completed with, as a shell word list; `None` means file names.
+fn values(flag: &str) -> Option<String> {
+    match flag {
+        "status" => Some(statuses()),
+        "priority" | "min-priority" => Some(PRIORITIES.to_string()),
+        "backend" => Some(BACKENDS.to_string()),
+        _ => None
+    }
+}
+
+fn unique_names() -> Vec<&'static str> {
+    let mut all = vec![];
+    for name in help::COMMANDS.iter().flat_map(names) {
+        if !all.contains(&name) {
+            all.push(name);
+        }
+    }
+    all
+}
+
+fn bash() -> String {
+    let flags = flags();
+    let options: Vec<String> = flags.iter().flat_map(|flag| flag.short.map(|short| format!("-{}", short)).into_iter().chain([format!("--{}", flag.long)])).collect();
+    let mut script = String::from("# bash completion for task-tracker\n_task_tracker() {\n    local cur prev words\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str(&format!("    local options=\"{}\"\n", options.join(" ")));
+    script.push_str("    case \"$prev\" in\n");
+    script.push_str(&format!("        --status) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n", statuses()));
+    script.push_str(&format!("        --priority|--min-priority) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n", PRIORITIES));
+    script.push_str(&format!("        --backend) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n", BACKENDS));
+    let file_flags: Vec<String> = flags.iter().filter(|flag| flag.takes_value && values(flag.long).is_none()).map(|flag| format!("--{}", flag.long)).collect();
+    script.push_str(&format!("        {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;\n    esac\n", file_flags.join("|")));
+    script.push_str(&format!("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{} $options\" -- \"$cur\"))\n        return\n    fi\n", unique_names().join(" ")));
+    let id_commands: Vec<&str> = help::COMMANDS.iter().filter(|command| takes_id(command)).flat_map(names).collect();
+    script.push_str(&format!("    if [ \"$COMP_CWORD\" -eq 2 ]; then\n        case \"${{COMP_WORDS[1]}}\" in\n            {})\n", id_commands.join("|")));
+    script.push_str("                local ids\n");
+    script.push_str("                ids=$(\"${COMP_WORDS[0]}\" list all --json 2>/dev/null | sed -n 's/^    \"id\": \\([0-9]*\\),$/\\1/p')\n");
+    script.push_str("                COMPREPLY=($(compgen -W \"$ids\" -- \"$cur\"))\n                return ;;\n        esac\n    fi\n");
+    script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    let mut done = vec![];
+    for command in help::COMMANDS {
+        if done.contains(&command.name) {
+            continue;
+        }
+        done.push(command.name);
+        let words = arguments(command.name);
+        if !words.is_empty() {
+            script.push_str(&format!("        {}) words=\"{}\" ;;\n", names(command).collect::<Vec<_>>().join("|"), words.join(" ")));
+        }
+    }
+    script.push_str("        *) words=\"\" ;;\n    esac\n    COMPREPLY=($(compgen -W \"$words $options\" -- \"$cur\"))\n}\ncomplete -F _task_tracker task-tracker\n");
+    script
+}
+
+/// Quotes text for a single-quoted shell string.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn zsh() -> String {
+    let mut script = String::from("#compdef task-tracker\n\n_task_tracker() {\n    local -a commands options\n    commands=(\n");
+    let mut seen = vec![];
+    for command in help::COMMANDS {
+        for name in names(command) {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+            script.push_str(&format!("        {}\n", quote(&format!("{}:{}", name, command.summary))));
+        }
+    }
+    script.push_str("    )\n    options=(\n");
+    for flag in flags() {
+        let value = if flag.takes_value { ":value:" } else { "" };
+        for name in flag.short.map(|short| format!("-{}", short)).into_iter().chain([format!("--{}", flag.long)]) {
+            script.push_str(&format!("        {}\n", quote(&format!("{}[{}]{}", name, flag.summary.replace(['[', ']'], ""), value))));
+        }
+    }
+    script.push_str("    )\n    if (( CURRENT == 2 )); then\n        _describe 'command' commands\n        _arguments $options\n        return\n    fi\n");
+    script.push_str("    case $words[CURRENT-1] in\n");
+    script.push_str(&format!("        --status) compadd -- {}; return ;;\n", statuses()));
+    script.push_str(&format!("        --priority|--min-priority) compadd -- {}; return ;;\n", PRIORITIES));
+    script.push_str(&format!("        --backend) compadd -- {}; return ;;\n", BACKENDS));
+    script.push_str("        --file|--archive-file) _files; return ;;\n    esac\n    case $words[2] in\n");
+    let mut done = vec![];
+    for command in help::COMMANDS {
+        if done.contains(&command.name) {
+            continue;
+        }
+        done.push(command.name);
+        let words = arguments(command.name);
+        if !words.is_empty() {
+            script.push_str(&format!("        {}) compadd -- {} ;;\n", names(command).collect::<Vec<_>>().join("|"), words.join(" ")));
+        }
+    }
+    script.push_str("    esac\n    _arguments $options\n}\n\n_task_tracker \"$@\"\n");
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::from("# fish completion for task-tracker\ncomplete -c task-tracker -f\n");
+    let mut done = vec![];
+    for command in help::COMMANDS {
+        for name in names(command) {
+            if done.contains(&name) {
+                continue;
+            }
+            done.push(name);
+            script.push_str(&format!("complete -c task-tracker -n __fish_use_subcommand -a {} -d {}\n", name, quote(command.summary)));
+            let words = arguments(command.name);
+            if !words.is_empty() {
+                script.push_str(&format!("complete -c task-tracker -n {} -a {}\n", quote(&format!("__fish_seen_subcommand_from {}", name)), quote(&words.join(" "))));
+            }
+        }
+    }
+    for flag in flags() {
+        let mut line = String::from("complete -c task-tracker");
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", flag.long));
+        if flag.takes_value {
+            match values(flag.long) {
+                Some(values) => line.push_str(&format!(" -x -a {}", quote(&values))),
+                None => line.push_str(" -r -F")
+            }
+        }
+        script.push_str(&format!("{} -d {}\n", line, quote(flag.summary)));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_command_and_option_is_completed() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            for command in help::COMMANDS {
+                for name in names(command) {
+                    assert!(script.contains(name), "{:?} is missing {}", shell, name);
+                }
+            }
+            for flag in flags() {
+                assert!(script.contains(flag.long), "{:?} is missing --{}", shell, flag.long);
+            }
+            for status in statuses().split(' ') {
+                assert!(script.contains(status), "{:?} is missing {}", shell, status);
+            }
+        }
+    }
+
+    #[test]
+    fn arguments_come_from_the_help_table() {
+        assert_eq!(arguments("list")[..6], ["all", "not-done", "todo", "in-progress", "done", "--min-priority"]);
+        assert_eq!(arguments("priority"), ["low", "medium", "high"]);
+        assert_eq!(arguments("set-repeat"), ["daily", "weekly"]);
+        assert_eq!(arguments("delete"), ["--cascade", "--reparent", "--all-done"]);
+        assert_eq!(arguments("import"), ["todotxt"]);
+        assert!(arguments("show").is_empty());
+        assert!(arguments("add").contains(&"--stdin"));
+    }
+
+    #[test]
+    fn options_are_split_from_their_help() {
+        let flags = flags();
+        let yes = flags.iter().find(|flag| flag.long == "yes").unwrap();
+        assert_eq!((yes.short, yes.takes_value), (Some("y"), false));
+        assert!(flags.iter().find(|flag| flag.long == "file").unwrap().takes_value);
+        assert!(flags.iter().find(|flag| flag.long == "max-description-len").unwrap().takes_value);
+        assert!(!flags.iter().find(|flag| flag.long == "json").unwrap().takes_value);
+    }
+
+    #[test]
+    fn shells_are_parsed() {
+        assert_eq!("zsh".parse::<Shell>(), Ok(Shell::Zsh));
+        assert!("powershell".parse::<Shell>().is_err());
+    }
+}
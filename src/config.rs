@@ -0,0 +1,158 @@
+//! Defaults read from an optional `task-tracker.toml` (or `task-tracker.json`) next to the task file.
+//!
+//! Flags and arguments given on the command line always win over the config file. A config file that can't be read
+//! or parsed is reported with a warning and ignored, so a typo in it never stops a command from running.
+use std::{fs, io, path::Path};
+use serde::{de, Deserialize, Deserializer};
+use super::{JsonStyle, Options, StatusFilter, TaskError};
+
+/// The config files looked for, in order; the first one found is used.
+pub(crate) const FILE_NAMES: [&str; 2] = ["task-tracker.toml", "task-tracker.json"];
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Status `list` shows when none is given; "all" brings back done tasks, which are hidden otherwise.
+    #[serde(default, deserialize_with = "status")]
+    pub default_list_filter: Option<StatusFilter>,
+    #[serde(default)]
+    pub default_format: Option<OutputFormat>,
+    /// "compact" writes JSON task files on one line; "pretty" (the default) indents them.
+    #[serde(default)]
+    pub json_style: Option<JsonStyle>,
+    /// Refuse to mark a task as done while any of its subtasks are not (`--force` still does).
+    #[serde(default)]
+    pub require_subtasks_done: bool,
+    /// Append changes to a journal instead of rewriting the task file, as with `--journal`.
+    #[serde(default)]
+    pub journal: bool,
+    /// Where `backup` writes backups, as with `--backup-dir`.
+    #[serde(default)]
+    pub backup_dir: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    Text,
+    Json
+}
+
+impl Config {
+    /// Fills in the options the command line left unset.
+    pub fn apply(&self, options: &mut Options) {
+        if !options.text && self.default_format == Some(OutputFormat::Json) {
+            options.json = true;
+        }
+        options.default_status = self.default_list_filter;
+        options.require_subtasks_done = self.require_subtasks_done;
+        options.journal |= self.journal;
+        if options.backup_dir.is_none() {
+            options.backup_dir = self.backup_dir.clone();
+        }
+        // There is only a flag for compact output, so it wins over the file by not being the default
+        if options.json_style == JsonStyle::Pretty {
+            options.json_style = self.json_style.unwrap_or_default();
+        }
+    }
+}
+
+/// Status names are written the way `list` takes them: all, not-done, todo, in-progress or done, or several joined by commas.
+fn status<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<StatusFilter>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(de::Error::custom)
+}
+
+/// Reads the config file in `dir`, if there is one.
+pub(crate) fn read(dir: &Path) -> Result<Config, String> {
+    for name in FILE_NAMES {
+        let path = dir.join(name);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(format!("{}: {}", path.display(), error))
+        };
+        let config = if name.ends_with(".toml") {
+            toml::from_str(&data).map_err(|error| error.message().to_string())
+        } else {
+            serde_json::from_str(&data).map_err(|error| error.to_string())
+        };
+        return config.map_err(|message| format!("{}: {}", path.display(), message.trim_end()));
+    }
+    Ok(Config::default())
+}
+
+/// Reads the config file in `dir`, falling back to the built-in defaults with a warning if it is malformed (or, with
+/// `--strict`, failing).
+pub(crate) fn load(dir: &Path, options: &Options) -> Result<Config, TaskError> {
+    read(dir).or_else(|message| {
+        let error = TaskError::Usage(format!("Invalid config file {}", message));
+        super::tolerate(options, error, format!("Warning: ignoring the config file {}", message))?;
+        Ok(Config::default())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    fn config_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("task-tracker-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_file_present_absent_or_malformed() {
+        let dir = config_dir("read");
+        assert_eq!(read(&dir), Ok(Config::default()));
+
+        fs::write(dir.join("task-tracker.json"), r#"{"default_format": "json"}"#).unwrap();
+        assert_eq!(read(&dir), Ok(Config { default_list_filter: None, default_format: Some(OutputFormat::Json), json_style: None, require_subtasks_done: false, journal: false, backup_dir: None }));
+
+        // The TOML file comes first
+        fs::write(dir.join("task-tracker.toml"), "default_list_filter = \"in-progress\"\ndefault_format = \"text\"\n").unwrap();
+        let expected = Config { default_list_filter: Some(StatusFilter::only(Status::InProgress)), default_format: Some(OutputFormat::Text), json_style: None, require_subtasks_done: false, journal: false, backup_dir: None };
+        assert_eq!(read(&dir), Ok(expected));
+        fs::write(dir.join("task-tracker.toml"), "default_list_filter = \"all\"\n").unwrap();
+        assert_eq!(read(&dir).unwrap().default_list_filter, Some(StatusFilter::ALL));
+        fs::write(dir.join("task-tracker.toml"), "json_style = \"compact\"\n").unwrap();
+        assert_eq!(read(&dir).unwrap().json_style, Some(JsonStyle::Compact));
+
+        for malformed in ["default_list_filter = \"someday\"", "json_style = \"tiny\"", "default_format = \"table\"", "colour = true", "default_format = "] {
+            fs::write(dir.join("task-tracker.toml"), malformed).unwrap();
+            let error = read(&dir).unwrap_err();
+            assert!(error.contains("task-tracker.toml"), "{}", error);
+            assert_eq!(load(&dir, &Options::default()).unwrap(), Config::default(), "{}", malformed);
+            assert!(load(&dir, &Options { strict: true, ..Options::default() }).is_err(), "{}", malformed);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flags_win_over_the_config_file() {
+        let config = Config { default_list_filter: Some(StatusFilter::only(Status::Todo)), default_format: Some(OutputFormat::Json), json_style: Some(JsonStyle::Pretty), require_subtasks_done: true, journal: true, backup_dir: Some("backups".to_string()) };
+        let mut options = Options::default();
+        config.apply(&mut options);
+        assert!(options.json);
+        assert_eq!(options.default_status, Some(StatusFilter::only(Status::Todo)));
+        assert!(options.require_subtasks_done);
+        assert!(options.journal);
+        assert_eq!(options.json_style, JsonStyle::Pretty);
+        assert_eq!(options.backup_dir.as_deref(), Some("backups"));
+
+        let mut options = Options { json_style: JsonStyle::Compact, backup_dir: Some("elsewhere".to_string()), ..Options::default() };
+        config.apply(&mut options);
+        assert_eq!(options.json_style, JsonStyle::Compact);
+        assert_eq!(options.backup_dir.as_deref(), Some("elsewhere"));
+        let mut options = Options::default();
+        Config { json_style: Some(JsonStyle::Compact), ..Config::default() }.apply(&mut options);
+        assert_eq!(options.json_style, JsonStyle::Compact);
+
+        let mut options = Options { text: true, ..Options::default() };
+        config.apply(&mut options);
+        assert!(!options.json);
+    }
+}
@@ -0,0 +1,75 @@
+//! Editing text in the user's editor, for descriptions too long to type comfortably on the command line.
+//!
+//! The text is written to a temp file, the editor named in `$EDITOR` is run on it, and the file is read back once the
+//! editor exits. An editor that exits with an error leaves the text unchanged.
+use std::{env, fs, path::Path, process};
+use crate::TaskError;
+
+/// The editor used when `$EDITOR` is unset or empty.
+pub const FALLBACK: &str = if cfg!(windows) { "notepad" } else { "vi" };
+
+/// The editor to run: `$EDITOR`, or the fallback.
+pub fn from_env() -> String {
+    env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()).unwrap_or_else(|| FALLBACK.to_string())
+}
+
+/// Lets the user edit `text` for task `id` in `editor`, and returns what they saved. The editor may carry its own
+/// arguments (e.g. `code --wait`); the temp file is passed after them.
+pub fn edit(editor: &str, text: &str, id: u32) -> Result<String, TaskError> {
+    let path = env::temp_dir().join(format!("task-tracker-{}-{}.txt", id, process::id()));
+    fs::write(&path, format!("{}\n", text)).map_err(|error| TaskError::io(&path.display().to_string(), error))?;
+    let edited = run(editor, &path, id).and_then(|()| fs::read_to_string(&path).map_err(|error| TaskError::io(&path.display().to_string(), error)));
+    // Nothing useful can be done if the temp file can't be removed; the system cleans it up eventually
+    let _ = fs::remove_file(&path);
+    edited
+}
+
+fn run(editor: &str, path: &Path, id: u32) -> Result<(), TaskError> {
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(FALLBACK);
+    let status = process::Command::new(program).args(words).arg(path).status().map_err(|error| TaskError::io(program, error))?;
+    if status.success() {
+        Ok(())
+    } else {
+        let message = format!("The editor ({}) exited with {}, so the description was not changed.", editor, status);
+        Err(TaskError::PreconditionFailed { id, message })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A script standing in for the editor: it saves the text it was given next to itself and replaces it with `content`.
+    fn fake_editor(name: &str, content: &str) -> std::path::PathBuf {
+        let script = env::temp_dir().join(format!("task-tracker-editor-{}-{}", name, process::id()));
+        fs::write(&script, format!("#!/bin/sh\ncp \"$1\" \"$0.seen\"\nprintf '{}' > \"$1\"\n", content)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    #[test]
+    fn reads_back_what_the_editor_saved() {
+        let script = fake_editor("save", "Walk the dog\\nand the cat\\n");
+        let edited = edit(script.to_str().unwrap(), "Walk the dog", 7).unwrap();
+        let seen = fs::read_to_string(script.with_extension("seen")).unwrap();
+        for file in [script.clone(), script.with_extension("seen")] {
+            fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(seen, "Walk the dog\n");
+        assert_eq!(edited, "Walk the dog\nand the cat\n");
+        assert!(!env::temp_dir().join(format!("task-tracker-7-{}.txt", process::id())).exists());
+    }
+
+    #[test]
+    fn failing_editor_changes_nothing() {
+        let error = edit("false", "Walk the dog", 8).unwrap_err();
+        assert_eq!(error.code(), "precondition_failed");
+        assert!(!env::temp_dir().join(format!("task-tracker-8-{}.txt", process::id())).exists());
+
+        let error = edit("task-tracker-no-such-editor", "Walk the dog", 9).unwrap_err();
+        assert_eq!(error.code(), "io");
+    }
+}
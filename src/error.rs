@@ -0,0 +1,157 @@
+use std::{error::Error, fmt::Display, io};
+use serde_json::{json, Value};
+
+/// Everything that can make a command fail.
+///
+/// Each variant maps to a stable, machine-readable error code (see [`TaskError::code`]), which is what `--json` mode
+/// reports alongside the human message and any context fields. Codes are part of the public interface: an existing
+/// code never changes meaning or gets renamed, and new variants only ever add new codes. Messages may change at any
+/// time, so tooling should match on `code` only.
+#[derive(Debug)]
+pub enum TaskError {
+    /// The command line could not be understood.
+    Usage(String),
+    /// A task referenced by id does not exist.
+    NotFound { id: u32 },
+    /// Reading or writing the database failed.
+    Io { path: String, source: io::Error },
+    /// The database exists but is not valid.
+    Parse { path: String, line: usize, column: usize, message: String },
+    /// The task exists but the command cannot be applied to it in its current state.
+    PreconditionFailed { id: u32, message: String },
+    /// Another process is working on the database.
+    Locked { path: String },
+    /// The database was written by a newer task-tracker, in a layout this one doesn't know.
+    UnsupportedVersion { path: String, version: u32 },
+    /// More than one task in the database has the same id.
+    DuplicateIds { path: String, ids: Vec<u32> },
+}
+
+impl TaskError {
+    /// The stable error code. This is the only place codes are assigned, and the match is deliberately exhaustive
+    /// so that a new variant cannot be added without one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TaskError::Usage(_) => "usage",
+            TaskError::NotFound { .. } => "not_found",
+            TaskError::Io { .. } => "io",
+            TaskError::Parse { .. } => "parse",
+            TaskError::PreconditionFailed { .. } => "precondition_failed",
+            TaskError::Locked { .. } => "locked",
+            TaskError::UnsupportedVersion { .. } => "unsupported_version",
+            TaskError::DuplicateIds { .. } => "duplicate_ids",
+        }
+    }
+    /// The error as a JSON object with `code`, `message` and the variant's context fields.
+    pub fn to_json(&self) -> Value {
+        let mut object = json!({ "code": self.code(), "message": self.to_string() });
+        let context = match self {
+            TaskError::Usage(_) => json!({}),
+            TaskError::NotFound { id } | TaskError::PreconditionFailed { id, .. } => json!({ "id": id }),
+            TaskError::Io { path, .. } | TaskError::Locked { path } => json!({ "path": path }),
+            TaskError::Parse { path, line, column, .. } => json!({ "path": path, "line": line, "column": column }),
+            TaskError::UnsupportedVersion { path, version } => json!({ "path": path, "version": version }),
+            TaskError::DuplicateIds { path, ids } => json!({ "path": path, "ids": ids }),
+        };
+        if let (Some(object), Value::Object(context)) = (object.as_object_mut(), context) {
+            object.extend(context);
+        }
+        object
+    }
+    pub(crate) fn io(path: &str, source: io::Error) -> Self {
+        TaskError::Io { path: path.to_string(), source }
+    }
+    /// Classifies a serde_json failure on the database file: I/O problems stay I/O errors, anything else is a parse error.
+    pub(crate) fn json(path: &str, error: serde_json::Error) -> Self {
+        if error.is_io() {
+            TaskError::io(path, error.into())
+        } else {
+            let message = error.to_string();
+            TaskError::Parse { path: path.to_string(), line: error.line(), column: error.column(), message }
+        }
+    }
+    /// A TOML parse error on the database file, with the line and column worked out from where it is in `data`.
+    pub(crate) fn toml(path: &str, data: &str, error: toml::de::Error) -> Self {
+        let (line, column) = error.span().map_or((0, 0), |span| {
+            let before = &data[..span.start.min(data.len())];
+            let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+            (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+        });
+        TaskError::Parse { path: path.to_string(), line, column, message: error.message().to_string() }
+    }
+    pub(crate) fn yaml(path: &str, error: serde_yaml::Error) -> Self {
+        let (line, column) = error.location().map_or((0, 0), |location| (location.line(), location.column()));
+        TaskError::Parse { path: path.to_string(), line, column, message: error.to_string() }
+    }
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskError::Usage(message) => write!(f, "{}", message),
+            TaskError::NotFound { id } => write!(f, "Task (ID: {}) not found.", id),
+            TaskError::Io { path, source } => write!(f, "{}: {}", path, source),
+            TaskError::Parse { path, line, column, message } => {
+                write!(f, "{} is not a valid task database (line {}, column {}): {}. ", path, line, column, message)?;
+                write!(f, "Fix it by hand, or run `task-tracker repair` to move it aside and start a new, empty task list.")
+            },
+            TaskError::PreconditionFailed { message, .. } => write!(f, "{}", message),
+            TaskError::Locked { path } => {
+                write!(f, "The database is locked by another task-tracker ({}); if none is running, delete that file.", path)
+            },
+            TaskError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{} is in version {} of the file layout, but this task-tracker only reads up to version {}. Please upgrade task-tracker.",
+                path, version, crate::FORMAT_VERSION
+            ),
+            TaskError::DuplicateIds { path, ids } => {
+                let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                write!(f, "More than one task in {} has the ID {}; run `task-tracker doctor` to give the later ones new IDs.", path, ids.join(", "))
+            },
+        }
+    }
+}
+
+impl Error for TaskError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TaskError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_has_a_code() {
+        let errors = [
+            TaskError::Usage("Invalid argument".to_string()),
+            TaskError::NotFound { id: 1 },
+            TaskError::io("tasks.json", io::Error::from(io::ErrorKind::PermissionDenied)),
+            TaskError::Parse { path: "tasks.json".to_string(), line: 1, column: 2, message: "EOF".to_string() },
+            TaskError::PreconditionFailed { id: 1, message: "Not allowed".to_string() },
+            TaskError::Locked { path: "tasks.json.lock".to_string() },
+            TaskError::UnsupportedVersion { path: "tasks.json".to_string(), version: 3 },
+            TaskError::DuplicateIds { path: "tasks.json".to_string(), ids: vec![2] },
+        ];
+        for error in errors {
+            // No wildcard arm: a new variant must be listed here (and given a code) before this compiles
+            let expected = match error {
+                TaskError::Usage(_) => "usage",
+                TaskError::NotFound { .. } => "not_found",
+                TaskError::Io { .. } => "io",
+                TaskError::Parse { .. } => "parse",
+                TaskError::PreconditionFailed { .. } => "precondition_failed",
+                TaskError::Locked { .. } => "locked",
+                TaskError::UnsupportedVersion { .. } => "unsupported_version",
+                TaskError::DuplicateIds { .. } => "duplicate_ids",
+            };
+            assert_eq!(error.code(), expected);
+            assert_eq!(error.to_json()["code"], expected);
+            assert_eq!(error.to_json()["message"], error.to_string());
+        }
+    }
+}
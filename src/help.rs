@@ -0,0 +1,137 @@
+//! The list of commands and global options, used for the `help` output and to recognise command names.
+//!
+//! This is the one place a command has to be described: `parse_args` rejects names that are not listed here, so a
+//! new command cannot be added without also showing up in `help`.
+use crate::STATUS_NAMES;
+
+pub struct CommandHelp {
+    pub name: &'static str,
+    /// Other names the command can be run under.
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    pub summary: &'static str
+}
+
+pub struct OptionHelp {
+    pub flag: &'static str,
+    pub summary: &'static str
+}
+
+pub const COMMANDS: &[CommandHelp] = &[
+    CommandHelp { name: "add", aliases: &[], args: "<description> [--parent <id>] [--repeat <interval>] [--status <status>] [--priority <level>] [--due <date>] [--tag <tag>]...", summary: "Adds a new task, optionally as a subtask, repeating or with other fields set" },
+    CommandHelp { name: "add", aliases: &[], args: "--stdin [--status <status>] [--priority <level>] [--due <date>]", summary: "Adds a task for each line of stdin, skipping blank lines and # comments" },
+    CommandHelp { name: "update", aliases: &[], args: "<id> <description> | <id> [--desc <text>] [--status <status>] [--due <date>] [--priority <level>]", summary: "Changes the description, status, due date and/or priority of a task" },
+    CommandHelp { name: "subtask", aliases: &[], args: "<parent id> <description>", summary: "Adds a new task as a subtask of another" },
+    CommandHelp { name: "copy", aliases: &["duplicate"], args: "<id> [<description>]", summary: "Adds a new todo task like an existing one, optionally with another description" },
+    CommandHelp { name: "edit-desc", aliases: &[], args: "<id>", summary: "Changes the description of a task in $EDITOR" },
+    CommandHelp { name: "delete", aliases: &["rm"], args: "<ids> [--cascade|--reparent] | --all-done", summary: "Moves tasks to the trash after asking; --cascade also deletes their subtasks, --reparent keeps them, --all-done deletes every done task" },
+    CommandHelp { name: "mark", aliases: &["advance"], args: "<id> [--wrap]", summary: "Moves a task on from todo to in progress to done; --wrap goes from done back to todo" },
+    CommandHelp { name: "toggle", aliases: &[], args: "<id>", summary: "Marks a task as done, or as todo again if it is done" },
+    CommandHelp { name: "reopen", aliases: &["uncomplete"], args: "<id>", summary: "Marks a done task as todo again" },
+    CommandHelp { name: "block", aliases: &[], args: "<id> --on <id>", summary: "Makes a task depend on another, so that it can't be marked as done before it" },
+    CommandHelp { name: "unblock", aliases: &[], args: "<id> --on <id>", summary: "Removes the dependency of a task on another" },
+    CommandHelp { name: "mark-todo", aliases: &[], args: "<ids>", summary: "Sets the status of tasks to todo" },
+    CommandHelp { name: "mark-in-progress", aliases: &[], args: "<ids>", summary: "Sets the status of tasks to in progress" },
+    CommandHelp { name: "mark-done", aliases: &["done"], args: "<ids>", summary: "Sets the status of tasks to done" },
+    CommandHelp { name: "list", aliases: &["ls"], args: "[all|not-done|todo|in-progress|done] [--min-priority <level>] [--created|--updated|--completed <period>] [completed-today] [--since <date>] [--until <date>] [--stale <days>] [--archived|--trash] [--status <statuses>] [--tag <tag>] [--assignee <name>|--unassigned] [--sort estimate|smart|id] [--regex <pattern> [-i]] [--limit <n> [--page <n>]] [--template <format>|--plain] [--count]", summary: "Lists (or counts) tasks that are not done, or all, archived or deleted tasks, optionally only those with some statuses (e.g. todo,done), priority, date or assignee" },
+    CommandHelp { name: "move", aliases: &[], args: "<id> <position>", summary: "Moves a task to a position in the list, counting from 1" },
+    CommandHelp { name: "search", aliases: &[], args: "[-i] <text>|--regex <pattern>", summary: "Lists the tasks of any status whose description contains the text, or matches the regular expression" },
+    CommandHelp { name: "count", aliases: &[], args: "[<list filters>]", summary: "Prints just the number of tasks list would show with the same filters, for scripts and shell prompts" },
+    CommandHelp { name: "show", aliases: &[], args: "<id>", summary: "Prints every detail of a task" },
+    CommandHelp { name: "progress", aliases: &[], args: "<id> <percent>", summary: "Sets how far along a task is, from 0 to 100; 100 marks it as done" },
+    CommandHelp { name: "assign", aliases: &[], args: "<id> <name>", summary: "Sets who a task is assigned to" },
+    CommandHelp { name: "unassign", aliases: &[], args: "<id>", summary: "Removes the assignee of a task" },
+    CommandHelp { name: "estimate", aliases: &[], args: "<id> <points>", summary: "Sets the estimated effort of a task, in points, hours or any other whole unit" },
+    CommandHelp { name: "priority", aliases: &[], args: "<id> <low|medium|high>", summary: "Sets the priority of a task" },
+    CommandHelp { name: "set-repeat", aliases: &["recur"], args: "<id> <daily|weekly|<n>d>", summary: "Makes a task repeat when it is done" },
+    CommandHelp { name: "due", aliases: &[], args: "<id> <YYYY-MM-DD>", summary: "Sets the due date of a task" },
+    CommandHelp { name: "remind", aliases: &[], args: "[<id> <offset>]", summary: "Adds a reminder (e.g. 2d, 3h, 30m before due), or prints the reminders that are due" },
+    CommandHelp { name: "start", aliases: &[], args: "<id>", summary: "Starts tracking time on a task" },
+    CommandHelp { name: "stop", aliases: &[], args: "<id>", summary: "Stops tracking time on a task" },
+    CommandHelp { name: "note", aliases: &[], args: "<id> <text>", summary: "Adds a note to a task" },
+    CommandHelp { name: "notes", aliases: &[], args: "<id>", summary: "Shows the notes of a task" },
+    CommandHelp { name: "clear-notes", aliases: &[], args: "<id>", summary: "Removes all notes of a task" },
+    CommandHelp { name: "history", aliases: &[], args: "<id>", summary: "Shows the changes made to a task, oldest first" },
+    CommandHelp { name: "archive", aliases: &[], args: "[--before <YYYY-MM-DD>]", summary: "Moves done tasks (completed before a date) to the archive" },
+    CommandHelp { name: "prune", aliases: &[], args: "--older-than <days>", summary: "Deletes done tasks completed more than some days ago, after asking" },
+    CommandHelp { name: "restore", aliases: &[], args: "<id>", summary: "Moves a deleted task back from the trash" },
+    CommandHelp { name: "restore", aliases: &[], args: "--backup <file|number>", summary: "Replaces the tasks with those in a backup, after backing them up" },
+    CommandHelp { name: "empty-trash", aliases: &[], args: "", summary: "Permanently deletes the tasks in the trash" },
+    CommandHelp { name: "backup", aliases: &[], args: "[--keep <count>]", summary: "Writes the tasks to a new, timestamped backup, deleting all but the newest <count> backups" },
+    CommandHelp { name: "backups", aliases: &[], args: "", summary: "Lists the backups of the task file, newest first" },
+    CommandHelp { name: "compact", aliases: &[], args: "", summary: "Writes the changes kept in the journal (see --journal) back into the task file" },
+    CommandHelp { name: "stats", aliases: &[], args: "", summary: "Counts the tasks by status and adds up the estimates of those that are not done" },
+    CommandHelp { name: "report", aliases: &[], args: "[--weeks <n> | --from <date> [--to <date>]]", summary: "Counts the tasks completed and added each week, with a bar chart (the last 4 weeks by default)" },
+    CommandHelp { name: "validate", aliases: &[], args: "", summary: "Lists the tasks that break the description length limit" },
+    CommandHelp { name: "projects", aliases: &[], args: "", summary: "Lists the projects next to the task file" },
+    CommandHelp { name: "path", aliases: &[], args: "", summary: "Prints the path of the task file, and whether it came from --file, TASKS_FILE, the current directory or the data directory" },
+    CommandHelp { name: "merge", aliases: &[], args: "<path>", summary: "Merges a separately changed copy of the task file into the tasks, keeping the newer version of tasks changed in both" },
+    CommandHelp { name: "import", aliases: &[], args: "todotxt <path>", summary: "Adds the tasks in a todo.txt file, skipping lines that can't be read" },
+    CommandHelp { name: "export", aliases: &[], args: "ics [<path>]", summary: "Writes the tasks that have a due date as iCalendar to-dos, to stdout if no path is given" },
+    CommandHelp { name: "watch", aliases: &[], args: "[all|todo|in-progress|done] [<list-options>]", summary: "Lists tasks like list, and again whenever the task file changes, until Ctrl-C" },
+    CommandHelp { name: "interactive", aliases: &["repl"], args: "", summary: "Runs commands typed one per line, saving on save, quit or exit" },
+    CommandHelp { name: "repair", aliases: &[], args: "", summary: "Moves an invalid task file aside and starts a new, empty one" },
+    CommandHelp { name: "migrate", aliases: &[], args: "", summary: "Rewrites the task file in the current file layout" },
+    CommandHelp { name: "reset-ids", aliases: &[], args: "", summary: "Numbers the tasks from 1 in the order they are in (needs --yes)" },
+    CommandHelp { name: "doctor", aliases: &["fsck"], args: "", summary: "Checks the task file for tasks sharing an ID and offers to renumber them" },
+    CommandHelp { name: "completions", aliases: &[], args: "<bash|zsh|fish>", summary: "Prints a shell completion script for the commands, options and task IDs" },
+    CommandHelp { name: "help", aliases: &["--help", "-h"], args: "", summary: "Prints this help" },
+];
+
+pub const OPTIONS: &[OptionHelp] = &[
+    OptionHelp { flag: "--json", summary: "Print tasks and errors as JSON" },
+    OptionHelp { flag: "--ndjson", summary: "Print the tasks in list as one JSON object per line" },
+    OptionHelp { flag: "--explain", summary: "Describe what the command will do first" },
+    OptionHelp { flag: "--dry-run", summary: "Don't write anything to the task file" },
+    OptionHelp { flag: "--clipboard", summary: "Copy the output of list to the clipboard" },
+    OptionHelp { flag: "-y, --yes", summary: "Don't ask before deleting or pruning" },
+    OptionHelp { flag: "--force", summary: "Mark tasks as done even if tasks they depend on are not" },
+    OptionHelp { flag: "--strict", summary: "Fail on problems that are otherwise only warned about" },
+    OptionHelp { flag: "-q, --quiet", summary: "Don't print success messages; add prints just the new ID" },
+    OptionHelp { flag: "--porcelain", summary: "Print what commands did, and list, as stable lines for scripts" },
+    OptionHelp { flag: "--project <name>", summary: "Use tasks.<name>.json" },
+    OptionHelp { flag: "--file <path>", summary: "Use the given task file (also read from TASKS_FILE)" },
+    OptionHelp { flag: "--archive-file <path>", summary: "Use the given archive file" },
+    OptionHelp { flag: "--backup-dir <dir>", summary: "Keep backups in the given directory" },
+    OptionHelp { flag: "--backend <json|sqlite>", summary: "Store tasks as JSON or in SQLite (default: by file extension)" },
+    OptionHelp { flag: "--max-description-len=<n>", summary: "Longest description accepted, in characters" },
+    OptionHelp { flag: "--text", summary: "Print text even if the config file sets default_format = \"json\"" },
+    OptionHelp { flag: "--absolute", summary: "Show exact times in list instead of e.g. 3 days ago" },
+    OptionHelp { flag: "--iso", summary: "Show times as RFC 3339 in UTC, e.g. 2025-01-12T14:02:28Z" },
+    OptionHelp { flag: "--verbose", summary: "Print the task file, task counts and parsed command to stderr" },
+    OptionHelp { flag: "--compact", summary: "Write the task file as compact JSON, on one line" },
+    OptionHelp { flag: "--journal", summary: "Append changes to a journal next to the task file instead of rewriting it" },
+    OptionHelp { flag: "--truncate", summary: "Shorten descriptions over the limit instead of refusing them" },
+];
+
+/// Finds a command by its name or one of its aliases, ignoring case.
+pub fn find(name: &str) -> Option<&'static CommandHelp> {
+    let name = name.to_lowercase();
+    COMMANDS.iter().find(|command| command.name == name || command.aliases.contains(&name.as_str()))
+}
+
+/// The full help text, ending with a newline.
+pub fn usage() -> String {
+    let mut text = String::from("Usage: task-tracker <command> [arguments] [options]\n\nCommands:\n");
+    let width = COMMANDS.iter().map(|command| command.name.len() + command.args.len() + 1).max().unwrap_or(0);
+    for command in COMMANDS {
+        let synopsis = format!("{} {}", command.name, command.args);
+        text.push_str(&format!("  {:<width$}  {}", synopsis.trim_end(), command.summary));
+        if !command.aliases.is_empty() {
+            text.push_str(&format!(" (also: {})", command.aliases.join(", ")));
+        }
+        text.push('\n');
+    }
+    text.push_str("\n<ids> is one or more ids, separated by commas and/or spaces. For update, delete, show and\nmark-*, `last` (the newest task) or text from a task's description can be given instead of an id.\n");
+    let statuses: Vec<String> = STATUS_NAMES.iter().map(|(_, names)| match names {
+        [name] => name.to_string(),
+        [name, aliases @ ..] => format!("{} (also: {})", name, aliases.join(", ")),
+        [] => String::new()
+    }).collect();
+    text.push_str(&format!("Statuses are {}.\nCommands and statuses can be typed in any case.\n\nOptions can go anywhere; anything after `--` is not read as one.\n\nOptions:\n", statuses.join(", ")));
+    let width = OPTIONS.iter().map(|option| option.flag.len()).max().unwrap_or(0);
+    for option in OPTIONS {
+        text.push_str(&format!("  {:<width$}  {}\n", option.flag, option.summary));
+    }
+    text
+}
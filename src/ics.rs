@@ -0,0 +1,136 @@
+//! Writing tasks with a due date as an iCalendar file (RFC 5545), one VTODO per task, for calendar apps.
+use chrono::{DateTime, Utc};
+use super::{Priority, Status, Task};
+
+/// The calendar holding the tasks that have a due date, along with the number of tasks left out for having none.
+pub(crate) fn calendar(tasks: &[Task]) -> (String, usize) {
+    let mut lines = vec!["BEGIN:VCALENDAR".to_string(), "VERSION:2.0".to_string(), "PRODID:-//task-tracker//EN".to_string()];
+    let mut skipped = 0;
+    for task in tasks {
+        let Some(due_at) = task.due_at else {
+            skipped += 1;
+            continue;
+        };
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:task-{}@task-tracker", task.id));
+        lines.push(format!("DTSTAMP:{}", timestamp(&task.created_at)));
+        lines.push(format!("CREATED:{}", timestamp(&task.created_at)));
+        lines.push(format!("SUMMARY:{}", escape(&task.description)));
+        lines.push(format!("DUE:{}", timestamp(&due_at)));
+        lines.push(format!("STATUS:{}", status(task.status)));
+        if let Some(completed_at) = task.completed_at {
+            lines.push(format!("COMPLETED:{}", timestamp(&completed_at)));
+        }
+        if let Some(priority) = task.priority {
+            lines.push(format!("PRIORITY:{}", self::priority(priority)));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    let calendar = lines.iter().map(|line| fold(line) + "\r\n").collect();
+    (calendar, skipped)
+}
+
+fn status(status: Status) -> &'static str {
+    match status {
+        Status::Todo => "NEEDS-ACTION",
+        Status::InProgress => "IN-PROCESS",
+        Status::Done => "COMPLETED"
+    }
+}
+
+/// iCalendar priorities go from 1 (highest) to 9 (lowest).
+fn priority(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9
+    }
+}
+
+fn timestamp(value: &DateTime<Utc>) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters that have a meaning in a text value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Splits a line into lines of at most 75 octets, each continuation starting with a space. Characters are never
+/// split, so a line can end a few octets short.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 37);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_are_folded_at_75_octets() {
+        assert_eq!(fold("SUMMARY:Buy milk"), "SUMMARY:Buy milk");
+        let line = format!("SUMMARY:{}", "x".repeat(200));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.iter().map(|line| line.len()).collect::<Vec<_>>(), [75, 75, 60]);
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+
+        // Three-octet characters don't fit evenly, and must not be cut in half
+        let line = format!("SUMMARY:{}", "日本語のテキスト".repeat(10));
+        let folded = fold(&line);
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= 75, "{} octets", part.len());
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape("Buy milk, eggs; and \\ butter\nthen cook"), "Buy milk\\, eggs\\; and \\\\ butter\\nthen cook");
+        assert_eq!(escape("Café — naïve"), "Café — naïve");
+    }
+
+    #[test]
+    fn only_tasks_with_a_due_date_are_exported() {
+        let due = DateTime::parse_from_rfc3339("2024-08-01T09:30:00Z").unwrap().with_timezone(&Utc);
+        let mut tasks = vec![Task::new(1, "No due date".to_string()), Task::new(2, format!("Écrire le rapport, enfin; {}", "très long ".repeat(10)))];
+        tasks[1].due_at = Some(due);
+        tasks[1].status = Status::InProgress;
+        tasks[1].priority = Some(Priority::High);
+        let (calendar, skipped) = calendar(&tasks);
+
+        assert_eq!(skipped, 1);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+        assert!(calendar.contains("\r\nUID:task-2@task-tracker\r\n"));
+        assert!(calendar.contains("\r\nDUE:20240801T093000Z\r\n"));
+        assert!(calendar.contains("\r\nSTATUS:IN-PROCESS\r\nPRIORITY:1\r\n"));
+        assert!(!calendar.contains("No due date"));
+        assert!(calendar.lines().all(|line| line.len() <= 76), "{}", calendar);
+        assert!(calendar.replace("\r\n ", "").contains("SUMMARY:Écrire le rapport\\, enfin\\; très long "));
+    }
+}
@@ -1,341 +1,6741 @@
-use std::{env, fmt::Display, fs::{File, OpenOptions}, io::{Read, Write}, path::Path, error::Error
+use std::{borrow::Borrow, collections::{HashMap, HashSet}, ffi::OsString, fmt::{Display, Write as _}, fs::{File, OpenOptions}, io::{self, BufRead, BufWriter, IsTerminal, Read, Write}, path::{Path, PathBuf}, str::FromStr, time::SystemTime
 };
-use chrono::{Local, NaiveDateTime}; 
-use serde::{Serialize, Deserialize}; 
+use regex::{Regex, RegexBuilder};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc}; 
+use serde::{Serialize, Deserialize, Deserializer, de}; 
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+mod backup;
+mod clipboard;
+mod completions;
+mod config;
+mod editor;
+mod error;
+mod help;
+mod ics;
+mod lock;
+mod merge;
+mod output;
+pub mod paging;
+mod report;
+mod storage;
+mod template;
+mod todotxt;
+
+pub use error::TaskError;
+pub use output::OutputMode;
+pub use storage::Backend;
+use storage::Storage;
+use output::{report, Outcome};
+use template::Template;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum Status {
     Todo, 
     InProgress,
     Done
 }
 
-#[derive(Debug)]
-enum Command {
-    Add(String), // Adding a new task with the given description 
-    Update(String, u32), // Updating the description of the task with the given id  
-    Delete(u32), // Delete task with given id  
-    Mark(Status, u32), // Marking task with the id with the given status 
-    List(Option<Status>), // For listing tasks with the given status
-}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum Priority {
+    Low, 
+    Medium, 
+    High
+}
+
+/// How often a recurring task comes back. Stored as "daily", "weekly" or "<n>d" (every n days). 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+enum Recurrence {
+    Daily, 
+    Weekly, 
+    EveryNDays(u32)
+}
+
+/// A reminder that fires some time before the task's due date. 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Reminder {
+    minutes_before: u32, 
+    delivered: bool
+}
+
+/// A free-form note attached to a task; the text may span several lines. 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Note {
+    text: String, 
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    created_at: DateTime<Utc>
+}
+
+/// How many changes each task keeps in its history; the oldest are dropped to make room for new ones. 
+const HISTORY_LIMIT: usize = 50;
+
+/// A change made to a task, kept in its history. 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HistoryEntry {
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    at: DateTime<Utc>, 
+    #[serde(flatten)]
+    change: Change
+}
+
+/// What a history entry records, stored as `"change": "status"` along with the values before and after. 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+enum Change {
+    Created { description: String }, 
+    Status { from: Status, to: Status }, 
+    Description { from: String, to: String }
+}
+
+/// Optional settings given to `add` as flags before or after the description. 
+#[derive(Debug, Default)]
+struct AddOptions {
+    parent: Option<u32>, 
+    recurrence: Option<Recurrence>, 
+    status: Option<Status>, 
+    priority: Option<Priority>, 
+    due_at: Option<DateTime<Utc>>, 
+    tags: Vec<String>
+}
+
+impl AddOptions {
+    /// Creates the task with `id`, with these settings applied. The description is expected to be checked already. 
+    fn new_task(&self, id: u32, description: &str) -> Result<Task, TaskError> {
+        let mut task = Task::try_new(id, description)?;
+        task.parent = self.parent;
+        task.recurrence = self.recurrence;
+        task.priority = self.priority;
+        task.due_at = self.due_at;
+        task.tags = self.tags.clone();
+        if let Some(status) = self.status {
+            task.status = status;
+            if status == Status::Done {
+                task.completed_at = Some(task.created_at);
+            }
+        }
+        Ok(task)
+    }
+}
+
+/// The changes `update` makes to a task, all in one go; fields left unset stay as they are. 
+#[derive(Debug, Default, PartialEq)]
+struct TaskChanges {
+    description: Option<String>, 
+    status: Option<Status>, 
+    due_at: Option<DateTime<Utc>>, 
+    priority: Option<Priority>
+}
+
+impl TaskChanges {
+    /// Applies the changes to `task`. The status is set last, so that a change to a recurring task's due date is
+    /// carried over if this completes it. 
+    fn apply(self, task: &mut Task, now: DateTime<Utc>) -> Result<(), TaskError> {
+        if let Some(description) = self.description {
+            task.update_description(&description)?;
+        }
+        if let Some(due_at) = self.due_at {
+            task.update_due(due_at, now);
+        }
+        if let Some(priority) = self.priority {
+            task.update_priority(priority);
+        }
+        if let Some(status) = self.status {
+            task.update_status(status);
+        }
+        Ok(())
+    }
+    /// The changes in plain English, e.g. "mark it as done, set its due date to ...". 
+    fn describe(&self) -> String {
+        let mut changes = vec![];
+        if let Some(description) = &self.description {
+            changes.push(format!("change its description to '{}'", description));
+        }
+        if let Some(status) = self.status {
+            changes.push(format!("mark it as {}", status));
+        }
+        if let Some(due_at) = &self.due_at {
+            changes.push(format!("set its due date to {}", timestamp::format(due_at, &Local)));
+        }
+        if let Some(priority) = self.priority {
+            changes.push(format!("set its priority to {}", priority));
+        }
+        changes.join(", ")
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Add(String, AddOptions), // Adding a new task with the given description and options 
+    AddFromStdin(AddOptions), // Adding a task for each line read from stdin, all with the given options
+    Update(TaskChanges, TaskRef), // Changing the description, status, due date and/or priority of the given task  
+    Delete(Vec<TaskRef>, Subtasks), // Delete the given tasks, and what to do with their subtasks  
+    DeleteDone, // Delete every done task whose subtasks are all done too
+    Mark(Status, Vec<TaskRef>), // Marking the given tasks with the given status 
+    List(ListFilter, ListSource, ListOutput), // For listing (or counting) the tasks, or archived or deleted tasks, that match the filter
+    SetRepeat(Recurrence, u32), // Setting the recurrence of the task with the given id
+    Prioritize(Priority, u32), // Setting the priority of the task with the given id
+    Estimate(u32, u32), // Setting the estimated effort of the task with the given id
+    Assign(Option<String>, u32), // Setting (or with None, clearing) who the task with the given id is assigned to
+    Progress(u8, u32), // Setting how far along the task with the given id is, in percent
+    Archive(Option<DateTime<Utc>>), // Moving all done tasks (completed before the given time, if any) to the archive
+    Prune(u32), // Deleting the done tasks completed more than the given number of days ago
+    Restore(u32), // Moving the task with the given id back from the trash
+    Backup(Option<usize>), // Writing the tasks to a new backup, then deleting all but the given number of the newest backups
+    Backups, // Listing the backups of the task file, newest first
+    RestoreBackup(String), // Replacing the tasks with those in the given backup (a file, or its number in the list), after backing them up
+    EmptyTrash, // Deleting the tasks in the trash for good
+    Compact, // Folding the journals of the task file, archive and trash back into the files
+    ImportTodoTxt(String), // Adding the tasks in the todo.txt file at the given path
+    Merge(String), // Merging the tasks in the task file at the given path, a separately changed copy, into the tasks
+    ExportIcs(Option<String>), // Writing the tasks that have a due date as iCalendar to-dos to the given path, or stdout
+    Interactive, // Reading commands from stdin and running them on tasks kept in memory
+    Watch(ListFilter), // Listing the tasks again whenever the task file changes, until interrupted
+    Due(DateTime<Utc>, u32), // Setting the due date of the task with the given id
+    Remind(u32, u32), // Adding a reminder the given number of minutes before the due date of the task with the given id
+    SendReminders, // Delivering all reminders that are due
+    Start(u32), // Starting the timer of the task with the given id
+    Stop(u32), // Stopping the timer of the task with the given id
+    Validate, // Checking the existing tasks against the limits
+    ResetIds, // Numbering the tasks from 1 in the order they are in
+    Completions(completions::Shell), // Printing the completion script for the given shell
+    Stats, // Counting the tasks by status and adding up the estimates of those not done
+    Report(ReportRange), // Counting the tasks completed and added in each week of the range
+    Projects, // Listing the projects in the data directory
+    Path, // Printing the path of the task file and where it came from
+    Note(String, u32), // Appending a note to the task with the given id
+    Advance(u32, bool), // Moving the task with the given id on to its next status, from done back to todo if asked to wrap
+    Toggle(u32), // Marking the task with the given id as done, or as todo if it is done already
+    Block(u32, u32), // Making the first task depend on the second, so that it can't be done before it
+    Unblock(u32, u32), // Removing the dependency of the first task on the second
+    Reopen(u32), // Marking the done task with the given id as todo again
+    Copy(u32, Option<String>), // Adding a new task like the one with the given id, optionally with another description
+    EditDescription(u32), // Changing the description of the task with the given id in the user's editor
+    Notes(u32), // Showing the notes of the task with the given id
+    History(u32), // Showing the changes made to the task with the given id, oldest first
+    ClearNotes(u32), // Removing all notes of the task with the given id
+    Show(TaskRef), // Showing every detail of the given task
+    Move(u32, usize), // Moving the task with the given id to the given position in the list
+    Repair, // Moving an invalid task file aside and starting a new one
+    Migrate, // Rewriting the task file in the current layout
+    Doctor, // Checking the task file for duplicate ids and fixing them
+    Help, // Printing the list of commands
+}
+
+/// A task as named on the command line: by id, as `last` (the most recently created task), or by some of the text of
+/// its description. Only ids can be checked before the tasks are loaded, so the others are resolved when the command
+/// runs. 
+#[derive(Debug, Clone, PartialEq)]
+enum TaskRef {
+    Id(u32), 
+    Last, 
+    /// Text found anywhere in the description, ignoring case. 
+    Text(String)
+}
+
+impl TaskRef {
+    /// Finds the id of the task. An id is returned as it is, even if there is no such task, so that each command can
+    /// report missing ids its own way. 
+    fn resolve(&self, tasks: &[Task]) -> Result<u32, TaskError> {
+        match self {
+            TaskRef::Id(id) => Ok(*id),
+            TaskRef::Last => tasks
+                .iter()
+                .map(|task| task.id)
+                .max()
+                .ok_or_else(|| TaskError::Usage("There are no tasks yet, so `last` doesn't name one.".to_string())),
+            TaskRef::Text(text) => {
+                let lowercase = text.to_lowercase();
+                let matches: Vec<&Task> = tasks.iter().filter(|task| task.description.to_lowercase().contains(&lowercase)).collect();
+                match matches[..] {
+                    [task] => Ok(task.id),
+                    [] => Err(TaskError::Usage(format!("No match: no task's description contains '{}'.", text))),
+                    _ => {
+                        let names: Vec<String> = matches.iter().map(|task| format!("{} ('{}')", task.id, task.description)).collect();
+                        Err(TaskError::Usage(format!("'{}' matches more than one task: {}. Use an ID instead.", text, names.join(", "))))
+                    }
+                }
+            }
+        }
+    }
+    fn resolve_all(refs: &[TaskRef], tasks: &[Task]) -> Result<Vec<u32>, TaskError> {
+        refs.iter().map(|task| task.resolve(tasks)).collect()
+    }
+}
+
+/// What `delete` does with the subtasks of a task it deletes. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Subtasks {
+    /// Refuses to delete a task that has subtasks. 
+    Refuse, 
+    /// Deletes them too, with their own subtasks. 
+    Cascade, 
+    /// Keeps them, moving them up to the deleted task's parent (or to the top level). 
+    Reparent
+}
+
+/// Where `list` takes its tasks from. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListSource {
+    Tasks, 
+    Archive, 
+    Trash
+}
+
+/// What `list` prints: the tasks themselves, or with `--count` just how many there are. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListOutput {
+    Tasks, 
+    Count
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Task {
+    id: u32, 
+    description: String,
+    status: Status, 
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    created_at: DateTime<Utc>, 
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    updated_at: Option<DateTime<Utc>>, 
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    due_at: Option<DateTime<Utc>>, 
+    #[serde(default)]
+    recurrence: Option<Recurrence>, 
+    #[serde(default)]
+    parent: Option<u32>, 
+    #[serde(default)]
+    priority: Option<Priority>, 
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    completed_at: Option<DateTime<Utc>>, 
+    /// Set by older versions, which archived tasks in place; such tasks are hidden and moved out on the next `archive`. 
+    #[serde(default)]
+    archived: bool, 
+    #[serde(default)]
+    reminders: Vec<Reminder>, 
+    #[serde(default)]
+    time_spent: u64, 
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    started_at: Option<DateTime<Utc>>, 
+    #[serde(default)]
+    notes: Vec<Note>, 
+    /// Position in the list, set by `move`. Tasks without one come after the others, in the order they were added. 
+    #[serde(default)]
+    order: Option<u32>, 
+    /// How far along the task is, as a percentage from 0 to 100. 
+    #[serde(default)]
+    progress: u8, 
+    /// Expected effort, in whatever unit the user plans in (story points, hours...). 
+    #[serde(default)]
+    estimate: Option<u32>, 
+    /// Tasks that have to be done before this one can be. 
+    #[serde(default)]
+    depends_on: Vec<u32>, 
+    /// Who is working on the task, for task files shared by a team. 
+    #[serde(default)]
+    assignee: Option<String>, 
+    /// Labels given with `add --tag`, e.g. "work"; without spaces, and never two that differ only in case. 
+    #[serde(default)]
+    tags: Vec<String>, 
+    /// The last `HISTORY_LIMIT` changes, oldest first. Files written before history was kept load with none. 
+    #[serde(default)]
+    history: Vec<HistoryEntry>
+}
+
+impl Task {
+    /// Creates a task from a description typed by the user, cleaned up with [`Task::clean_description`]. 
+    fn try_new(id: u32, description: &str) -> Result<Self, TaskError> {
+        Ok(Self::new(id, Self::clean_description(description)?))
+    }
+    fn new(id: u32, description: String) -> Self {
+        let created_at = Utc::now();
+        let history = vec![HistoryEntry { at: created_at, change: Change::Created { description: description.clone() } }];
+        Self { id, description, status: Status::Todo, created_at, updated_at: None, due_at: None, recurrence: None, parent: None, priority: None, completed_at: None, archived: false, reminders: vec![], time_spent: 0, started_at: None, notes: vec![], order: None, progress: 0, estimate: None, depends_on: vec![], assignee: None, tags: vec![], history }
+    }
+    /// A new task like this one, with the given id and optionally another description: it starts out as todo, with
+    /// no history (notes, tracked time, completion) and its reminders not yet delivered. 
+    fn copy(&self, id: u32, description: Option<&str>) -> Result<Self, TaskError> {
+        let description = match description {
+            Some(description) => Self::clean_description(description)?,
+            None => self.description.clone()
+        };
+        let reminders = self.reminders.iter().map(|reminder| Reminder { delivered: false, ..reminder.clone() }).collect();
+        Ok(Self { due_at: self.due_at, recurrence: self.recurrence, parent: self.parent, priority: self.priority, estimate: self.estimate, depends_on: self.depends_on.clone(), assignee: self.assignee.clone(), tags: self.tags.clone(), reminders, ..Self::new(id, description) })
+    }
+    fn update_status(&mut self, status: Status) {
+        let now = Utc::now();
+        if status != Status::Done {
+            self.completed_at = None;
+        } else if self.status != Status::Done {
+            self.completed_at = Some(now);
+        }
+        if status == Status::Done && self.started_at.is_some() {
+            let _ = self.stop_timer(now);
+        }
+        if status != self.status {
+            self.record(now, Change::Status { from: self.status, to: status });
+        }
+        self.status = status; 
+        self.updated_at = Some(now);
+    }
+    /// Adds a change to the history, dropping the oldest entries past `HISTORY_LIMIT`. 
+    fn record(&mut self, at: DateTime<Utc>, change: Change) {
+        self.history.push(HistoryEntry { at, change });
+        let excess = self.history.len().saturating_sub(HISTORY_LIMIT);
+        self.history.drain(..excess);
+    }
+    fn start_timer(&mut self, now: DateTime<Utc>) -> Result<(), TaskError> {
+        if self.started_at.is_some() {
+            let message = format!("Task (ID: {}) is already running.", self.id);
+            return Err(TaskError::PreconditionFailed { id: self.id, message });
+        }
+        self.started_at = Some(now);
+        self.updated_at = Some(now);
+        Ok(())
+    }
+    /// Stops the timer and adds the elapsed time to the total, returning the seconds elapsed. 
+    fn stop_timer(&mut self, now: DateTime<Utc>) -> Result<u64, TaskError> {
+        let Some(started_at) = self.started_at.take() else {
+            let message = format!("Task (ID: {}) is not running.", self.id);
+            return Err(TaskError::PreconditionFailed { id: self.id, message });
+        };
+        let elapsed = elapsed_seconds(started_at, now);
+        self.time_spent += elapsed;
+        self.updated_at = Some(now);
+        Ok(elapsed)
+    }
+    /// When the task last changed: its last update, or when it was created if it never was updated. 
+    fn last_touched(&self) -> DateTime<Utc> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+    /// When a done task was completed. Tasks completed before completion times were recorded fall back to their last
+    /// update, or to when they were created if they never were updated (e.g. when written by hand). 
+    fn completion_time(&self) -> Option<DateTime<Utc>> {
+        if self.status != Status::Done {
+            return None;
+        }
+        Some(self.completed_at.or(self.updated_at).unwrap_or(self.created_at))
+    }
+    fn update_description(&mut self, description: &str) -> Result<(), TaskError> {
+        let description = Self::clean_description(description)?;
+        let now = Utc::now();
+        if description != self.description {
+            let from = std::mem::replace(&mut self.description, description.clone());
+            self.record(now, Change::Description { from, to: description });
+        }
+        self.updated_at = Some(now);
+        Ok(())
+    }
+    /// Trims the description and turns each run of control characters (tabs, newlines) into a single space, so it
+    /// fits on one line of the list output. Fails if nothing is left. 
+    fn clean_description(description: &str) -> Result<String, TaskError> {
+        let mut cleaned = String::with_capacity(description.len());
+        for c in description.trim().chars() {
+            if !c.is_control() {
+                cleaned.push(c);
+            } else if !cleaned.ends_with(' ') {
+                cleaned.push(' ');
+            }
+        }
+        let cleaned = cleaned.trim_end();
+        if cleaned.is_empty() {
+            Err(TaskError::Usage("Description can't be empty.".to_string()))
+        } else {
+            Ok(cleaned.to_string())
+        }
+    }
+    fn update_progress(&mut self, progress: u8) {
+        self.progress = progress;
+        self.updated_at = Some(Utc::now());
+    }
+    fn update_priority(&mut self, priority: Priority) {
+        self.priority = Some(priority);
+        self.updated_at = Some(Utc::now());
+    }
+    fn update_assignee(&mut self, assignee: Option<String>) {
+        self.assignee = assignee;
+        self.updated_at = Some(Utc::now());
+    }
+    fn update_estimate(&mut self, estimate: u32) {
+        self.estimate = Some(estimate);
+        self.updated_at = Some(Utc::now());
+    }
+    /// Tasks without a priority count as medium. 
+    fn has_priority_at_least(&self, priority: Priority) -> bool {
+        self.priority.unwrap_or(Priority::Medium) >= priority
+    }
+    fn update_recurrence(&mut self, recurrence: Recurrence) {
+        self.recurrence = Some(recurrence);
+        self.updated_at = Some(Utc::now());
+    }
+    /// Sets the due date. Reminders that were already delivered but would now fire in the future are re-armed. 
+    fn update_due(&mut self, due_at: DateTime<Utc>, now: DateTime<Utc>) {
+        self.due_at = Some(due_at);
+        for reminder in self.reminders.iter_mut() {
+            if reminder.fires_at(due_at) > now {
+                reminder.delivered = false;
+            }
+        }
+        self.updated_at = Some(now);
+    }
+    fn add_reminder(&mut self, minutes_before: u32) -> Result<(), TaskError> {
+        if self.due_at.is_none() {
+            let message = format!("Task (ID: {}) has no due date; set one first with `due {} <date>`.", self.id, self.id);
+            return Err(TaskError::PreconditionFailed { id: self.id, message });
+        }
+        if !self.reminders.iter().any(|reminder| reminder.minutes_before == minutes_before) {
+            self.reminders.push(Reminder { minutes_before, delivered: false });
+        }
+        self.updated_at = Some(Utc::now());
+        Ok(())
+    }
+    fn add_note(&mut self, text: String, now: DateTime<Utc>) {
+        self.notes.push(Note { text, created_at: now });
+        self.updated_at = Some(now);
+    }
+    fn clear_notes(&mut self, now: DateTime<Utc>) {
+        self.notes.clear();
+        self.updated_at = Some(now);
+    }
+    fn next_id(tasks: &[Task]) -> u32 {
+        // Tasks can be moved, so the last one doesn't necessarily have the highest id 
+        tasks.iter().map(|task| task.id).max().unwrap_or(0) + 1
+    }
+    /// Puts the tasks in the order they are shown in. 
+    fn sort(tasks: &mut [Task]) {
+        tasks.sort_by_key(|task| task.order.unwrap_or(u32::MAX));
+    }
+    /// Moves a task to a position in the list (counting from 1, and at most the end of the list), numbering every task
+    /// by its new position. Returns the position it ended up at, or None if there is no such task. 
+    fn move_to(tasks: &mut Vec<Task>, id: u32, position: usize) -> Option<usize> {
+        Self::sort(tasks);
+        let index = tasks.iter().position(|task| task.id == id)?;
+        let task = tasks.remove(index);
+        let position = position.clamp(1, tasks.len() + 1);
+        tasks.insert(position - 1, task);
+        for (index, task) in tasks.iter_mut().enumerate() {
+            task.order = Some(index as u32 + 1);
+        }
+        Some(position)
+    }
+    /// Renders tasks with subtasks indented under their parent. Subtasks whose parent is not among the given tasks are rendered flat. 
+    fn render(tasks: &[Task], all: &[Task], times: TimeFormat) -> String {
+        let mut out = String::new();
+        let mut rendered = vec![false; tasks.len()];
+        for (index, task) in tasks.iter().enumerate() {
+            let has_parent = task.parent.is_some_and(|parent| tasks.iter().any(|other| other.id == parent));
+            if !has_parent {
+                Task::render_subtree(tasks, all, index, 0, times, &mut rendered, &mut out);
+            }
+        }
+        // Anything left over is part of a parent cycle (only possible in a hand-edited file) 
+        for (index, task) in tasks.iter().enumerate() {
+            if !rendered[index] {
+                let _ = writeln!(out, "{}", TaskDisplay(task, times));
+            }
+        }
+        out
+    }
+    fn render_subtree(tasks: &[Task], all: &[Task], index: usize, depth: usize, times: TimeFormat, rendered: &mut [bool], out: &mut String) {
+        if rendered[index] {
+            return;
+        }
+        rendered[index] = true;
+        let indent = "    ".repeat(depth);
+        let text = TaskDisplay(&tasks[index], times).to_string();
+        for line in text.lines() {
+            let _ = writeln!(out, "{}{}", indent, line);
+        }
+        let blocked_by = blockers(all, tasks[index].id);
+        if !blocked_by.is_empty() {
+            let _ = writeln!(out, "{}Blocked by: {}", indent, join_ids(&blocked_by));
+        }
+        for (child, task) in tasks.iter().enumerate() {
+            if task.parent == Some(tasks[index].id) {
+                Task::render_subtree(tasks, all, child, depth + 1, times, rendered, out);
+            }
+        }
+    }
+    /// The task as `key=value` lines for `list --plain`, ending with a newline. Every key is always there, with an
+    /// empty value if the task has none; values are escaped with [`output::escape`], so that each key stays on one
+    /// line. 
+    fn plain(&self, times: TimeFormat) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let fields = [
+            ("id", self.id.to_string()), 
+            ("status", self.status.name().to_string()), 
+            ("desc", self.description.clone()), 
+            ("created", times.exact(&self.created_at)), 
+            ("updated", optional(self.updated_at.map(|updated_at| times.exact(&updated_at)))), 
+            ("completed", optional(self.completed_at.map(|completed_at| times.exact(&completed_at)))), 
+            ("due", optional(self.due_at.map(|due_at| times.exact(&due_at)))), 
+            ("priority", optional(self.priority.map(|priority| priority.to_string()))), 
+            ("parent", optional(self.parent.map(|parent| parent.to_string()))), 
+            ("assignee", optional(self.assignee.clone())), 
+            ("tags", self.tags.join(","))
+        ];
+        let mut out = String::new();
+        for (key, value) in fields {
+            let _ = writeln!(out, "{}={}", key, output::escape(&value));
+        }
+        out
+    }
+    /// Returns the ids of all subtasks of the given task, recursively. 
+    fn descendants(tasks: &[Task], id: u32) -> Vec<u32> {
+        let mut found = vec![];
+        let mut pending = vec![id];
+        while let Some(parent) = pending.pop() {
+            for task in tasks.iter().filter(|task| task.parent == Some(parent)) {
+                if task.id != id && !found.contains(&task.id) {
+                    found.push(task.id);
+                    pending.push(task.id);
+                }
+            }
+        }
+        found
+    }
+}
+
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Todo => write!(f, "todo"),
+            Status::Done => write!(f, "done"),
+            Status::InProgress => write!(f, "in progress")
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::Low => write!(f, "low"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::High => write!(f, "high")
+        }
+    }
+}
+
+impl Status {
+    /// The status after this one, in the order todo, in progress, done. Done is the last, unless `wrap` starts the
+    /// cycle over at todo. 
+    fn next(self, wrap: bool) -> Option<Status> {
+        match self {
+            Status::Todo => Some(Status::InProgress),
+            Status::InProgress => Some(Status::Done),
+            Status::Done => wrap.then_some(Status::Todo)
+        }
+    }
+    /// The name the status is given as on the command line and in machine-readable output, e.g. "in-progress". 
+    fn name(self) -> &'static str {
+        STATUS_NAMES.iter().find(|(status, _)| *status == self).map_or("", |(_, names)| names[0])
+    }
+    /// The status `toggle` gives: done tasks become todo, and all others done. 
+    fn toggled(self) -> Status {
+        match self {
+            Status::Done => Status::Todo,
+            Status::Todo | Status::InProgress => Status::Done
+        }
+    }
+}
+
+/// The names each status can be given as, ignoring case. The first one is the name shown in help and completions. 
+pub(crate) const STATUS_NAMES: [(Status, &[&str]); 3] = [
+    (Status::Todo, &["todo"]), 
+    (Status::InProgress, &["in-progress", "in_progress", "inprogress", "wip"]), 
+    (Status::Done, &["done", "complete", "finished"])
+];
+
+/// Parses a status the way `list` takes it. 
+impl FromStr for Status {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        STATUS_NAMES.iter()
+            .find(|(_, names)| names.contains(&s.as_str()))
+            .map(|(status, _)| *status)
+            .ok_or("Invalid status, expected one of: todo, in-progress, done".to_string())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "medium" => Ok(Priority::Medium),
+            "high" => Ok(Priority::High),
+            _ => Err("Invalid priority, expected one of: low, medium, high".to_string())
+        }
+    }
+}
+
+/// The alternate form (`{:#}`) shows the creation and update times relative to now, e.g. "3 days ago". 
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let times = if f.alternate() { TimeFormat::Relative } else { TimeFormat::Local };
+        TaskDisplay(self, times).fmt(f)
+    }
+}
+
+/// How `list`, `show` and `notes` write times. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeFormat {
+    Local, 
+    /// Creation and update times relative to now, e.g. "3 days ago", and the others in local time 
+    Relative, 
+    /// RFC 3339 in UTC, for other tools to read 
+    Iso
+}
+
+impl TimeFormat {
+    /// The format for a listing: relative unless `--absolute` or `--iso` is given. 
+    fn for_list(options: &Options) -> Self {
+        match (options.iso, options.absolute) {
+            (true, _) => TimeFormat::Iso, 
+            (false, true) => TimeFormat::Local, 
+            (false, false) => TimeFormat::Relative
+        }
+    }
+    /// The format for a single task, which is never relative. 
+    fn for_details(options: &Options) -> Self {
+        if options.iso { TimeFormat::Iso } else { TimeFormat::Local }
+    }
+    fn exact(self, value: &DateTime<Utc>) -> String {
+        match self {
+            TimeFormat::Iso => timestamp::iso(value), 
+            TimeFormat::Local | TimeFormat::Relative => timestamp::format(value, &Local)
+        }
+    }
+    /// Creation and update times, which are the ones shown relative to now. 
+    fn recent(self, value: &DateTime<Utc>) -> String {
+        match self {
+            TimeFormat::Relative => timestamp::relative(value, &Utc::now()), 
+            _ => self.exact(value)
+        }
+    }
+}
+
+/// A task written out with its times in the given format. 
+struct TaskDisplay<'a>(&'a Task, TimeFormat);
+
+impl Display for TaskDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let TaskDisplay(task, times) = *self;
+        let created_at = times.recent(&task.created_at);
+        let updated_at = match task.updated_at {
+            Some(value) => times.recent(&value),
+            None => "-".to_string()
+        };
+        write!(f, "------------\nID: {} [{}]\nTask: {}\nCreated at: {}\nLast Update: {}", task.id, task.status, task.description, created_at, updated_at)?;
+        if task.status == Status::InProgress {
+            write!(f, "\nProgress: {}", progress_bar(task.progress))?;
+        }
+        if let Some(priority) = task.priority {
+            write!(f, "\nPriority: {}", priority)?;
+        }
+        if let Some(estimate) = task.estimate {
+            write!(f, "\nEstimate: {}", estimate)?;
+        }
+        if let Some(assignee) = &task.assignee {
+            write!(f, "\nAssigned to: {}", assignee)?;
+        }
+        if !task.tags.is_empty() {
+            write!(f, "\nTags: {}", task.tags.join(", "))?;
+        }
+        if let Some(due_at) = task.due_at {
+            write!(f, "\nDue: {}", times.exact(&due_at))?;
+        }
+        if let Some(recurrence) = &task.recurrence {
+            write!(f, "\nRepeats: {}", recurrence)?;
+        }
+        if let Some(parent) = task.parent {
+            write!(f, "\nSubtask of: {}", parent)?;
+        }
+        if !task.depends_on.is_empty() {
+            write!(f, "\nDepends on: {}", join_ids(&task.depends_on))?;
+        }
+        if let Some(completed_at) = task.completed_at {
+            write!(f, "\nCompleted at: {}", times.exact(&completed_at))?;
+        }
+        if task.time_spent > 0 || task.started_at.is_some() {
+            write!(f, "\nTime spent: {}", format_duration(task.time_spent))?;
+            if let Some(started_at) = task.started_at {
+                write!(f, " (running since {})", times.exact(&started_at))?;
+            }
+        }
+        if !task.reminders.is_empty() {
+            write!(f, "\nReminders: {}", format_reminders(&task.reminders))?;
+        }
+        if !task.notes.is_empty() {
+            write!(f, "\nNotes:\n{}", format_notes(&task.notes, times))?;
+        }
+        Ok(())
+    }
+}
+
+/// Timestamps are stored in UTC (RFC 3339) and displayed in local time. Databases written by older versions stored naive local
+/// times, which are still accepted on load and converted to UTC using the local timezone. 
+mod timestamp {
+    use super::*;
+
+    pub fn format<Tz: TimeZone>(value: &DateTime<Utc>, tz: &Tz) -> String where Tz::Offset: Display {
+        value.with_timezone(tz).format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// RFC 3339 in UTC to the second, e.g. "2025-01-12T14:02:28Z", the way the task file stores it. 
+    pub fn iso(value: &DateTime<Utc>) -> String {
+        value.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    /// How long ago (or from now) a time is, in its largest whole unit, e.g. "just now", "5 minutes ago", "in 2 days". 
+    pub fn relative(value: &DateTime<Utc>, now: &DateTime<Utc>) -> String {
+        let seconds = (*now - *value).num_seconds();
+        let elapsed = seconds.unsigned_abs();
+        let (count, unit) = match elapsed {
+            0..60 => return "just now".to_string(),
+            60..3_600 => (elapsed / 60, "minute"),
+            3_600..86_400 => (elapsed / 3_600, "hour"),
+            86_400..2_592_000 => (elapsed / 86_400, "day"),
+            2_592_000..31_536_000 => (elapsed / 2_592_000, "month"),
+            _ => (elapsed / 31_536_000, "year")
+        };
+        let plural = if count == 1 { "" } else { "s" };
+        if seconds < 0 {
+            format!("in {} {}{}", count, unit, plural)
+        } else {
+            format!("{} {}{} ago", count, unit, plural)
+        }
+    }
+    pub fn parse(value: &str) -> Result<DateTime<Utc>, String> {
+        if let Ok(value) = DateTime::parse_from_rfc3339(value) {
+            return Ok(value.with_timezone(&Utc));
+        }
+        let naive = value.parse::<NaiveDateTime>().map_err(|error| format!("Invalid timestamp {:?}: {}", value, error))?;
+        Ok(from_local(naive))
+    }
+    /// Parses a YYYY-MM-DD date given on the command line into the start of that day in local time. 
+    pub fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+        Ok(from_local(parse_day(value)?.and_time(chrono::NaiveTime::MIN)))
+    }
+    /// Parses a YYYY-MM-DD date given on the command line. 
+    pub fn parse_day(value: &str) -> Result<NaiveDate, String> {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Invalid date {:?}, expected YYYY-MM-DD", value))
+    }
+    /// The start of a day in the given time zone (or the earliest time it has, if a DST change skips midnight). 
+    pub fn midnight<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+        let naive = date.and_time(chrono::NaiveTime::MIN);
+        tz.from_local_datetime(&naive).earliest().map_or_else(|| naive.and_utc(), |local| local.with_timezone(&Utc))
+    }
+    fn from_local(naive: NaiveDateTime) -> DateTime<Utc> {
+        // A local time skipped by a DST change has no UTC equivalent, so it is taken as UTC as-is 
+        Local.from_local_datetime(&naive).earliest().map_or_else(|| naive.and_utc(), |local| local.with_timezone(&Utc))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse(&value).map_err(de::Error::custom)
+    }
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        let value = Option::<String>::deserialize(deserializer)?;
+        value.map(|value| parse(&value).map_err(de::Error::custom)).transpose()
+    }
+}
+
+impl Recurrence {
+    /// Moves a date forward by one recurrence interval. 
+    fn advance(&self, date: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Recurrence::Daily => date + Duration::days(1),
+            Recurrence::Weekly => date + Duration::weeks(1),
+            Recurrence::EveryNDays(days) => date + Duration::days(i64::from(*days))
+        }
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly => write!(f, "weekly"),
+            Recurrence::EveryNDays(days) => write!(f, "{}d", days)
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || "Invalid repeat interval, expected one of: daily, weekly, <n>d (e.g. 3d)".to_string();
+        match s {
+            "daily" => Ok(Recurrence::Daily),
+            "weekly" => Ok(Recurrence::Weekly),
+            _ => {
+                let days = s.strip_suffix('d').ok_or_else(error)?.parse::<u32>().map_err(|_| error())?;
+                if days == 0 {
+                    return Err(error());
+                }
+                Ok(Recurrence::EveryNDays(days))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+/// Adds a task for each line of a todo.txt file, created at the line's creation date (the start of that day) or
+/// `now`. Lines that can't be read are reported on stderr and skipped. Returns the new ids and the number skipped. 
+fn import_todotxt(tasks: &mut Vec<Task>, data: &str, options: &Options, now: DateTime<Utc>) -> Result<(Vec<u32>, usize), TaskError> {
+    let mut imported = vec![];
+    let mut skipped = 0;
+    for (index, line) in data.lines().enumerate() {
+        let task = todotxt::parse_line(line).and_then(|entry| match entry {
+            Some(entry) => {
+                let description = check_description(entry.description, options.max_description_len, options.truncate).map_err(|error| error.to_string())?;
+                let mut task = Task::try_new(Task::next_id(tasks), &description).map_err(|error| error.to_string())?;
+                task.created_at = entry.created_on.map_or(now, |day| timestamp::midnight(day, &Local));
+                task.priority = entry.priority;
+                task.due_at = entry.due_on.map(|day| timestamp::midnight(day, &Local));
+                if entry.done {
+                    task.status = Status::Done;
+                    task.completed_at = Some(entry.completed_on.map_or(now, |day| timestamp::midnight(day, &Local)));
+                }
+                Ok(Some(task))
+            },
+            None => Ok(None)
+        });
+        match task {
+            Ok(Some(task)) => {
+                imported.push(task.id);
+                tasks.push(task);
+            },
+            Ok(None) => {},
+            Err(message) => {
+                tolerate(options, TaskError::Usage(format!("Line {}: {}", index + 1, message)), format!("Warning: skipped line {}: {}.", index + 1, message))?;
+                skipped += 1;
+            }
+        }
+    }
+    Ok((imported, skipped))
+}
+
+/// Reads the descriptions for `add --stdin`: one per line, with their line numbers, leaving out blank lines and
+/// comments starting with #. 
+fn read_task_lines(input: &mut dyn BufRead) -> io::Result<Vec<(usize, String)>> {
+    let mut descriptions = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            descriptions.push((index + 1, line.to_string()));
+        }
+    }
+    Ok(descriptions)
+}
+
+/// Splits a line typed in interactive mode into arguments the way a shell would: on whitespace, except inside single
+/// or double quotes, with a backslash keeping the next character as it is (other than within single quotes). 
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or("Nothing after the backslash at the end of the line")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    if let Some(open) = quote {
+        return Err(format!("Missing closing {}", open));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// How often `watch` looks at the task file. 
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The modification time and size of a file, or None if it doesn't exist. The size catches a second write within the
+/// resolution of the modification time. 
+fn file_stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Whether a file was written, created or removed between two looks at it. 
+fn has_changed(before: Option<(SystemTime, u64)>, after: Option<(SystemTime, u64)>) -> bool {
+    before != after
+}
+
+/// Lists the tasks, then lists them again, after clearing the screen, whenever the task file or its journal changes. It only ever
+/// reads the file, taking the lock just while it does so; being stopped with Ctrl-C in that moment leaves the lock file
+/// behind, as it would for any other command. A file that can't be read (e.g. halfway through being saved by an
+/// editor) is reported in place of the list until it changes again. 
+fn watch(mut filter: ListFilter, file_path: &str, storage: &mut dyn Storage, options: &Options) -> Result<(), TaskError> {
+    filter.status = filter.status.or(options.default_status).or(Some(StatusFilter::OPEN));
+    let journal = storage::journal_path(file_path);
+    let mut seen = (None, None);
+    let mut first = true;
+    loop {
+        let stamp = (file_stamp(file_path), file_stamp(&journal));
+        if first || has_changed(seen.0, stamp.0) || has_changed(seen.1, stamp.1) {
+            let rendered = (|| {
+                // Waits for a command that is writing the file to finish 
+                let _lock = lock::Lock::acquire(file_path, lock::ATTEMPTS, lock::RETRY_DELAY)?;
+                let mut tasks = if storage.exists() { storage.load()? } else { vec![] };
+                Task::sort(&mut tasks);
+                render_list(&filter, tasks, options).map(|(output, _)| output)
+            })();
+            let output = rendered.unwrap_or_else(|error| format!("Can't read the tasks: {}\n", error));
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "\x1b[2J\x1b[H{}", output).and_then(|_| stdout.flush()).map_err(|error| TaskError::io("<stdout>", error))?;
+            seen = stamp;
+            first = false;
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Runs commands read from `input`, one per line, on tasks that are loaded once and kept in memory. Nothing is
+/// written until `save`, or the end of the session (`quit`, `exit` or the end of the input). A command that fails
+/// is reported and the session goes on. 
+fn interactive(input: &mut Input, file_path: &str, stores: Stores, options: &Options) -> Result<(), TaskError> {
+    let mut stores = Stores {
+        tasks: Box::new(storage::Buffered::new(stores.tasks)), 
+        archive: Box::new(storage::Buffered::new(stores.archive)), 
+        trash: Box::new(storage::Buffered::new(stores.trash))
+    };
+    loop {
+        if input.ask {
+            print!("> ");
+            let _ = io::stdout().flush();
+        }
+        let mut line = String::new();
+        if input.reader.read_line(&mut line).map_err(|error| TaskError::io("<stdin>", error))? == 0 {
+            break;
+        }
+        let words = match tokenize(&line) {
+            Ok(words) => words,
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                continue;
+            }
+        };
+        match words.first().map(String::as_str) {
+            None => {},
+            Some("quit" | "exit") => break,
+            Some("save") => {
+                stores.flush()?;
+                report(options, "Saved.".to_string());
+            },
+            Some(_) => {
+                if let Err(error) = run_line(words, file_path, &mut stores, input, options) {
+                    eprintln!("Error: {}", error);
+                }
+            }
+        }
+    }
+    stores.flush()
+}
+
+/// Runs one command of an interactive session. 
+fn run_line(words: Vec<String>, file_path: &str, stores: &mut Stores, input: &mut Input, options: &Options) -> Result<(), TaskError> {
+    let args = std::iter::once("task-tracker".to_string()).chain(words).collect();
+    let command = parse_args(args).map_err(TaskError::Usage)?;
+    if run_without_tasks(&command, file_path, options)? {
+        return Ok(());
+    }
+    if let Command::Repair | Command::Migrate | Command::Doctor | Command::Interactive | Command::Watch(_) = command {
+        return Err(TaskError::Usage("That command works on the task file, so it can't be run in interactive mode.".to_string()));
+    }
+    if let Command::AddFromStdin(_) = command {
+        return Err(TaskError::Usage("add --stdin reads the rest of the input, which is the session's, so it can't be run in interactive mode.".to_string()));
+    }
+    execute(command, file_path, stores, input, options)
+}
+
+/// Moves the given tasks (and, with `cascade`, their subtasks) to the trash, after asking if stdin is a terminal.
+/// Returns the ids that were deleted, or none if the user said no. Ids that don't exist are reported (see `--strict`). 
+fn delete_tasks(mut tasks: Vec<Task>, ids: Vec<u32>, subtasks: Subtasks, storage: &mut dyn Storage, trash: &mut dyn Storage, input: &mut Input, options: &Options) -> Result<Vec<u32>, TaskError> {
+    let mut deleted = vec![];
+    let mut removed = vec![];
+    let mut not_found = vec![];
+    let mut trashed = vec![];
+    let mut summary = String::new();
+    for id in ids {
+        if removed.contains(&id) {
+            continue;
+        }
+        if !tasks.iter().any(|task| task.id == id) {
+            not_found.push(id);
+            continue;
+        }
+        let descendants = Task::descendants(&tasks, id);
+        if !descendants.is_empty() && subtasks == Subtasks::Refuse {
+            // Nothing has been written yet, so a refusal leaves the database untouched 
+            let message = format!("Task (ID: {}) has {} subtask(s); use --cascade to delete them too, or --reparent to keep them.", id, descendants.len());
+            return Err(TaskError::PreconditionFailed { id, message });
+        }
+        let mut parent = None;
+        if let Some(task) = tasks.iter().find(|task| task.id == id) {
+            let _ = write!(summary, "Task (ID: {}): {}", id, task.description);
+            match subtasks {
+                _ if descendants.is_empty() => {},
+                Subtasks::Reparent => { let _ = write!(summary, " (keeping its subtasks)"); },
+                _ => { let _ = write!(summary, " (and {} subtask(s))", descendants.len()); }
+            }
+            summary.push('\n');
+            parent = task.parent;
+        }
+        let descendants = if subtasks == Subtasks::Reparent {
+            // Only the children are moved; theirs stay under them. Nothing is saved unless the delete goes ahead 
+            for child in tasks.iter_mut().filter(|task| task.parent == Some(id)) {
+                child.parent = parent;
+                child.updated_at = Some(Utc::now());
+            }
+            vec![]
+        } else {
+            descendants
+        };
+        let (gone, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|task| task.id == id || descendants.contains(&task.id));
+        tasks = kept;
+        trashed.extend(gone);
+        deleted.push(id);
+        removed.push(id);
+        removed.extend(descendants);
+    }
+    // Nothing has been written yet, so with --strict a missing id leaves everything as it was
+    report_not_found(&not_found, options)?;
+    if !deleted.is_empty() {
+        let question = if deleted.len() == 1 { "Delete this task?".to_string() } else { format!("Delete these {} tasks?", deleted.len()) };
+        if !input.confirm(&format!("{}{}", summary, question), options)? {
+            report(options, "Delete cancelled.".to_string());
+            return Ok(vec![]);
+        }
+    }
+    if !deleted.is_empty() {
+        for task in tasks.iter_mut() {
+            let dangling: Vec<u32> = task.depends_on.iter().copied().filter(|other| removed.contains(other)).collect();
+            if !dangling.is_empty() {
+                let message = format!("Task (ID: {}) depends on task(s) {}, which would be deleted.", task.id, join_ids(&dangling));
+                let warning = format!("Warning: task (ID: {}) depended on deleted task(s) {}; the dependency was removed.", task.id, join_ids(&dangling));
+                tolerate(options, TaskError::PreconditionFailed { id: task.id, message }, warning)?;
+                task.depends_on.retain(|other| !dangling.contains(other));
+            }
+        }
+        let mut in_trash = read_optional(trash)?;
+        in_trash.extend(trashed);
+        // The trash is written first: if saving the tasks fails, they end up in both files rather than neither
+        save(trash, &in_trash, options)?;
+        save(storage, &tasks, options)?;
+    }
+    Ok(deleted)
+}
+
+/// The done tasks that can be deleted, and the done tasks kept because some of their subtasks are not done. 
+fn done_tasks(tasks: &[Task]) -> (Vec<u32>, Vec<u32>) {
+    tasks
+    .iter()
+    .filter(|task| task.status == Status::Done)
+    .map(|task| task.id)
+    .partition(|id| Task::descendants(tasks, *id).iter().all(|subtask| tasks.iter().any(|task| task.id == *subtask && task.status == Status::Done)))
+}
+
+/// The tasks that the given one depends on and that are not done yet. Dependencies that no longer exist don't block. 
+fn blockers(tasks: &[Task], id: u32) -> Vec<u32> {
+    let Some(task) = tasks.iter().find(|task| task.id == id) else {
+        return vec![];
+    };
+    task.depends_on.iter().copied().filter(|other| tasks.iter().any(|task| task.id == *other && task.status != Status::Done)).collect()
+}
+
+/// Refuses to mark tasks as done while tasks they depend on are not, unless forced. With `require_subtasks_done` in
+/// the config file (or `--strict`), the same goes for their subtasks, other than those being marked as done along
+/// with them. 
+fn check_unblocked(tasks: &[Task], ids: &[u32], status: Status, options: &Options) -> Result<(), TaskError> {
+    if status != Status::Done || options.force {
+        return Ok(());
+    }
+    for &id in ids {
+        let blocked_by = blockers(tasks, id);
+        if !blocked_by.is_empty() {
+            let message = format!("Task (ID: {}) is blocked by task(s) {}, which are not done; add --force to mark it as done anyway.", id, join_ids(&blocked_by));
+            return Err(TaskError::PreconditionFailed { id, message });
+        }
+        let open: Vec<u32> = open_subtasks(tasks, id).into_iter().filter(|subtask| !ids.contains(subtask)).collect();
+        if (options.require_subtasks_done || options.strict) && !open.is_empty() {
+            let message = format!("Task (ID: {}) has subtask(s) {} that are not done; finish them first, or add --force to mark it as done anyway.", id, join_ids(&open));
+            return Err(TaskError::PreconditionFailed { id, message });
+        }
+    }
+    Ok(())
+}
+
+/// The subtasks of a task, at any depth, that are not done. 
+fn open_subtasks(tasks: &[Task], id: u32) -> Vec<u32> {
+    Task::descendants(tasks, id)
+        .into_iter()
+        .filter(|child| tasks.iter().any(|task| task.id == *child && task.status != Status::Done))
+        .collect()
+}
+
+/// Whether `from` depends on `to`, directly or through other tasks. 
+fn depends_on_transitively(tasks: &[Task], from: u32, to: u32) -> bool {
+    let mut seen = vec![];
+    let mut pending = vec![from];
+    while let Some(id) = pending.pop() {
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id);
+        for &next in tasks.iter().find(|task| task.id == id).map(|task| task.depends_on.as_slice()).unwrap_or_default() {
+            if next == to {
+                return true;
+            }
+            pending.push(next);
+        }
+    }
+    false
+}
+
+/// Makes task `id` depend on task `on`, refusing dependencies that don't exist or would go round in a circle. 
+fn add_dependency(tasks: &mut [Task], id: u32, on: u32) -> Result<(), TaskError> {
+    if !tasks.iter().any(|task| task.id == on) {
+        return Err(TaskError::NotFound { id: on });
+    }
+    if id == on || depends_on_transitively(tasks, on, id) {
+        let message = format!("Task (ID: {}) can't depend on task (ID: {}), since that would make a cycle.", id, on);
+        return Err(TaskError::PreconditionFailed { id, message });
+    }
+    let task = tasks.iter_mut().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+    if task.depends_on.contains(&on) {
+        let message = format!("Task (ID: {}) already depends on task (ID: {}).", id, on);
+        return Err(TaskError::PreconditionFailed { id, message });
+    }
+    task.depends_on.push(on);
+    task.updated_at = Some(Utc::now());
+    Ok(())
+}
+
+fn join_ids(ids: &[u32]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Sets the status of an existing task, warning if it is done before its subtasks. A recurring task that this
+/// completes is respawned, and the id of the new task returned. 
+fn set_status(tasks: &mut Vec<Task>, id: u32, status: Status) -> Option<u32> {
+    let open_subtasks = open_subtasks(tasks, id).len();
+    let task = tasks.iter_mut().find(|task| task.id == id)?;
+    if status == Status::Done && open_subtasks > 0 {
+        eprintln!("Warning: task (ID: {}) still has {} subtask(s) that are not done.", id, open_subtasks);
+    }
+    let completes = status == Status::Done && task.status != Status::Done;
+    task.update_status(status);
+    if completes { respawn(tasks, id, Utc::now()) } else { None }
+}
+
+/// Respawns the given task if it is recurring: inserts a fresh todo copy with a new id, created at `now`, with the same 
+/// recurrence and due one interval after the original's due date (or after `now`, if it had none). Returns the new id. 
+fn respawn(tasks: &mut Vec<Task>, id: u32, now: DateTime<Utc>) -> Option<u32> {
+    let next_id = Task::next_id(tasks);
+    let task = tasks.iter().find(|task| task.id == id)?;
+    let recurrence = task.recurrence?;
+    let mut next = Task::new(next_id, task.description.clone());
+    next.created_at = now;
+    next.due_at = Some(recurrence.advance(task.due_at.unwrap_or(now)));
+    next.recurrence = Some(recurrence);
+    next.priority = task.priority;
+    next.parent = task.parent;
+    tasks.push(next);
+    Some(next_id)
+}
+
+impl Reminder {
+    fn fires_at(&self, due_at: DateTime<Utc>) -> DateTime<Utc> {
+        due_at - Duration::minutes(i64::from(self.minutes_before))
+    }
+}
+
+/// Parses a reminder offset such as `2d`, `3h` or `30m` into minutes. 
+fn parse_offset(value: &str) -> Result<u32, String> {
+    let error = || format!("Invalid offset {:?}, expected a number followed by d, h or m (e.g. 2d)", value);
+    let unit = match value.chars().last() {
+        Some('d') => 24 * 60,
+        Some('h') => 60,
+        Some('m') => 1,
+        _ => return Err(error())
+    };
+    let amount = value[..value.len() - 1].parse::<u32>().map_err(|_| error())?;
+    amount.checked_mul(unit).ok_or_else(error)
+}
+
+fn format_offset(minutes: u32) -> String {
+    if minutes > 0 && minutes.is_multiple_of(24 * 60) {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes > 0 && minutes.is_multiple_of(60) {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Whole seconds between two times; a start in the future (e.g. after the clock was changed) counts as no time. 
+fn elapsed_seconds(from: DateTime<Utc>, to: DateTime<Utc>) -> u64 {
+    u64::try_from((to - from).num_seconds()).unwrap_or(0)
+}
+
+/// Draws a percentage as a bar of ten characters, one # for each full 10%, e.g. "[####------] 40%". 
+fn progress_bar(percent: u8) -> String {
+    let filled = usize::from(percent.min(100) / 10);
+    format!("[{}{}] {}%", "#".repeat(filled), "-".repeat(10 - filled), percent)
+}
+
+/// Formats seconds as hours and minutes, e.g. "2h 15m" or "0h 45m", rounding down to the minute. 
+fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+/// Limit on the length of a description, in characters, unless overridden with `--max-description-len`. 
+const DEFAULT_MAX_DESCRIPTION_LEN: usize = 10_000;
+
+/// What is appended to a description cut short by `--truncate`. 
+const ELLIPSIS: char = '…';
+
+/// Checks a new description against the limit, cutting it down to `limit` characters (ellipsis included) if
+/// `truncate` is set. Length is counted in characters rather than bytes, so non-ASCII text gets the same allowance. 
+fn check_description(description: String, limit: usize, truncate: bool) -> Result<String, TaskError> {
+    let length = description.chars().count();
+    if length <= limit {
+        Ok(description)
+    } else if truncate {
+        let mut truncated: String = description.chars().take(limit.saturating_sub(1)).collect();
+        truncated.push(ELLIPSIS);
+        Ok(truncated)
+    } else {
+        Err(TaskError::Usage(format!("Description is {} characters long, the limit is {} (use --truncate to shorten it).", length, limit)))
+    }
+}
+
+/// Ids and description lengths of the tasks whose description is over the limit. 
+fn long_descriptions(tasks: &[Task], limit: usize) -> Vec<(u32, usize)> {
+    tasks
+        .iter()
+        .map(|task| (task.id, task.description.chars().count()))
+        .filter(|(_, length)| *length > limit)
+        .collect()
+}
+
+/// Reminders as a comma-separated list, e.g. "2d before (sent), 30m before". 
+fn format_reminders(reminders: &[Reminder]) -> String {
+    let reminders: Vec<String> = reminders
+        .iter()
+        .map(|reminder| {
+            let sent = if reminder.delivered { " (sent)" } else { "" };
+            format!("{} before{}", format_offset(reminder.minutes_before), sent)
+        })
+        .collect();
+    reminders.join(", ")
+}
+
+/// Every field of a task, one per line and with "-" for those that are not set, as printed by `show`. Unlike the
+/// list layout this also names the task's subtasks, so it needs the other tasks as well. 
+fn format_details(task: &Task, tasks: &[Task], times: TimeFormat) -> String {
+    let time = |value: Option<DateTime<Utc>>| value.map_or("-".to_string(), |value| times.exact(&value));
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let subtasks: Vec<String> = tasks.iter().filter(|other| other.parent == Some(task.id)).map(|other| other.id.to_string()).collect();
+    let mut time_spent = format_duration(task.time_spent);
+    if let Some(started_at) = task.started_at {
+        time_spent = format!("{} (running since {})", time_spent, times.exact(&started_at));
+    }
+    let fields = [
+        ("Status", task.status.to_string()),
+        ("Priority", or_dash(task.priority.map(|priority| priority.to_string()))),
+        ("Tags", or_dash(Some(task.tags.join(", ")).filter(|tags| !tags.is_empty()))),
+        ("Created at", time(Some(task.created_at))),
+        ("Last update", time(task.updated_at)),
+        ("Due", time(task.due_at)),
+        ("Repeats", or_dash(task.recurrence.as_ref().map(Recurrence::to_string))),
+        ("Subtask of", or_dash(task.parent.map(|parent| parent.to_string()))),
+        ("Subtasks", or_dash(Some(subtasks.join(", ")).filter(|subtasks| !subtasks.is_empty()))),
+        ("Completed at", time(task.completed_at)),
+        ("Time spent", time_spent),
+        ("Reminders", or_dash(Some(format_reminders(&task.reminders)).filter(|reminders| !reminders.is_empty()))),
+        ("Archived", if task.archived { "yes" } else { "no" }.to_string()),
+    ];
+    let mut output = format!("Task {}: {}\n", task.id, task.description);
+    for (label, value) in fields {
+        let _ = writeln!(output, "{:<14}{}", format!("{}:", label), value);
+    }
+    if task.notes.is_empty() {
+        output.push_str("Notes:        -\n");
+    } else {
+        let _ = writeln!(output, "Notes:\n{}", format_notes(&task.notes, times));
+    }
+    output
+}
+
+/// Notes as a bulleted list, each starting with its timestamp, without a trailing newline. Further lines of a
+/// note are indented to line up with the first. 
+fn format_notes(notes: &[Note], times: TimeFormat) -> String {
+    let items: Vec<String> = notes
+        .iter()
+        .map(|note| {
+            let text = note.text.lines().collect::<Vec<_>>().join("\n      ");
+            format!("    - [{}] {}", times.exact(&note.created_at), text)
+        })
+        .collect();
+    items.join("\n")
+}
+
+/// The history as a bulleted list, oldest first, each entry starting with its timestamp, without a trailing newline. 
+fn format_history(history: &[HistoryEntry], times: TimeFormat) -> String {
+    let items: Vec<String> = history
+        .iter()
+        .map(|entry| {
+            let change = match &entry.change {
+                Change::Created { description } => format!("created as '{}'", description),
+                Change::Status { from, to } => format!("status: {} → {}", from, to),
+                Change::Description { from, to } => format!("description: '{}' → '{}'", from, to)
+            };
+            format!("    - [{}] {}", times.exact(&entry.at), change)
+        })
+        .collect();
+    items.join("\n")
+}
+
+/// Marks every reminder that is due at `now` as delivered and returns the ids of their tasks, so each reminder fires once. 
+fn sweep_reminders(tasks: &mut [Task], now: DateTime<Utc>) -> Vec<u32> {
+    let mut fired = vec![];
+    for task in tasks.iter_mut().filter(|task| task.status != Status::Done) {
+        let Some(due_at) = task.due_at else { continue };
+        for reminder in task.reminders.iter_mut().filter(|reminder| !reminder.delivered) {
+            if now >= reminder.fires_at(due_at) {
+                reminder.delivered = true;
+                fired.push(task.id);
+            }
+        }
+    }
+    fired.dedup();
+    fired
+}
+
+/// Whether `archive` moves the task out: it is done (and completed before the cutoff, if there is one), or it was
+/// archived in place by an older version. 
+fn is_archivable(task: &Task, cutoff: Option<DateTime<Utc>>) -> bool {
+    task.archived || task.completion_time().is_some_and(|completed_at| cutoff.is_none_or(|cutoff| completed_at < cutoff))
+}
+
+/// Removes the tasks `archive` moves out and returns them, in their original order. 
+fn take_archivable(tasks: &mut Vec<Task>, cutoff: Option<DateTime<Utc>>) -> Vec<Task> {
+    let (mut archived, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|task| is_archivable(task, cutoff));
+    *tasks = kept;
+    for task in archived.iter_mut() {
+        task.archived = false;
+    }
+    archived
+}
+
+/// The time `days` days before `now`: `prune --older-than <days>` removes done tasks completed before it, and
+/// `list --stale <days>` shows tasks last updated before it. Days from before the earliest time that can be
+/// represented are a usage error. 
+fn days_before(now: DateTime<Utc>, days: u32) -> Result<DateTime<Utc>, TaskError> {
+    Duration::try_days(days.into())
+        .and_then(|days| now.checked_sub_signed(days))
+        .ok_or_else(|| TaskError::Usage(format!("Invalid number of days: {} (too far back)", days)))
+}
+
+/// Whether a task is done and was completed before the cutoff. 
+fn is_prunable(task: &Task, cutoff: DateTime<Utc>) -> bool {
+    task.completion_time().is_some_and(|completed_at| completed_at < cutoff)
+}
+
+/// Removes the done tasks completed (or, for older tasks without a completion time, last updated) before the cutoff
+/// and returns them. 
+fn take_prunable(tasks: &mut Vec<Task>, cutoff: DateTime<Utc>) -> Vec<Task> {
+    let (pruned, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|task| is_prunable(task, cutoff));
+    *tasks = kept;
+    pruned
+}
+
+/// The archive that goes with a task file: archive.json for tasks.json, archive.<project>.json for a project, and
+/// <name>.archive.json for any other file (keeping the extension, so that the archive uses the same backend). 
+fn archive_path(file_path: &str) -> String {
+    companion_path(file_path, "archive")
+}
+
+/// The trash that goes with a task file, named like the archive: trash.json for tasks.json and so on. 
+fn trash_path(file_path: &str) -> String {
+    companion_path(file_path, "trash")
+}
+
+fn companion_path(file_path: &str, name: &str) -> String {
+    let path = Path::new(file_path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map_or("json".to_string(), |extension| extension.to_string_lossy().to_string());
+    let file_name = match stem.strip_prefix("tasks") {
+        Some(rest) if rest.is_empty() || rest.starts_with('.') => format!("{}{}.{}", name, rest, extension),
+        _ => format!("{}.{}.{}", stem, name, extension)
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+/// Reads the archive or the trash, which are empty until something has been moved there. 
+fn read_optional(store: &mut dyn Storage) -> Result<Vec<Task>, TaskError> {
+    if store.exists() {
+        store.load()
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// The format of a task file, picked from its extension: .toml and .yaml/.yml files are TOML and YAML, anything else
+/// is JSON. All of them hold the same document, a [`FORMAT_VERSION`] and the list of tasks. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml
+}
+
+impl Format {
+    fn for_path(file_path: &str) -> Self {
+        match Path::new(file_path).extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json
+        }
+    }
+}
+
+/// How JSON task files are laid out when written. Both are read back the same way. 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JsonStyle {
+    /// Indented, one field per line, for reading and editing by hand. 
+    #[default]
+    Pretty, 
+    /// All on one line: about a third of the size, and quicker to write for long lists. 
+    Compact
+}
+
+/// The version of the task file layout written by this build. Version 1 was a bare list of tasks (in TOML, a
+/// `tasks = [...]` table); version 2 wraps it as `{ "version": 2, "tasks": [...] }`. 
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    tasks: Vec<Task>
+}
+
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    version: u32,
+    tasks: &'a [Task]
+}
+
+/// Just enough of a task file to tell which version it is in, before parsing the tasks. Version 1 files have none. 
+#[derive(Deserialize)]
+struct Header {
+    version: Option<u32>
+}
+
+/// Creates a new file as a database with an empty list, if such a file does not already exist. 
+fn create_db(file_path: &str) -> Result<(), TaskError> {
+    if !Path::new(file_path).exists() {
+        write_db(file_path, &[], JsonStyle::default())?;
+    }
+    Ok(())
+}
+
+/// Opens the file and parses the string into a vector of Tasks, in the format given by its extension (serde_json's from_reader can also be used here, but docs say it is usually slower). 
+fn read_db(file_path: &str) -> Result<Vec<Task>, TaskError> {
+    read_document(file_path).map(|(_, tasks)| tasks)
+}
+
+/// Refuses tasks sharing an id, since commands would only ever find the first of them. Only the task list is checked:
+/// the archive and the trash can hold several tasks that had the same id at different times. 
+fn check_unique_ids(file_path: &str, tasks: &[Task]) -> Result<(), TaskError> {
+    let ids = duplicate_ids(tasks);
+    if ids.is_empty() {
+        Ok(())
+    } else {
+        Err(TaskError::DuplicateIds { path: file_path.to_string(), ids })
+    }
+}
+
+/// The ids used by more than one task, in ascending order. 
+fn duplicate_ids(tasks: &[Task]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<u32> = tasks.iter().map(|task| task.id).filter(|id| !seen.insert(*id)).collect();
+    duplicates.sort();
+    duplicates.dedup();
+    duplicates
+}
+
+/// Gives every task whose id was already used by an earlier task a new id, returning the (old, new) ids. 
+fn reassign_duplicate_ids(tasks: &mut [Task]) -> Vec<(u32, u32)> {
+    let mut next_id = Task::next_id(tasks);
+    let mut seen = HashSet::new();
+    let mut reassigned = vec![];
+    for task in tasks.iter_mut() {
+        if !seen.insert(task.id) {
+            reassigned.push((task.id, next_id));
+            task.id = next_id;
+            next_id += 1;
+        }
+    }
+    reassigned
+}
+
+/// Numbers the tasks 1, 2, 3... in the order they are in, pointing subtasks and dependencies at the new IDs. Returns
+/// the old and new ID of each task whose ID changed. 
+fn reset_ids(tasks: &mut [Task]) -> Vec<(u32, u32)> {
+    let new_ids: HashMap<u32, u32> = tasks.iter().zip(1..).map(|(task, new_id)| (task.id, new_id)).collect();
+    let new_id = |id: u32| new_ids.get(&id).copied().unwrap_or(id);
+    let mut renumbered = vec![];
+    for task in tasks.iter_mut() {
+        if new_id(task.id) != task.id {
+            renumbered.push((task.id, new_id(task.id)));
+        }
+        task.id = new_id(task.id);
+        task.parent = task.parent.map(new_id);
+        for dependency in &mut task.depends_on {
+            *dependency = new_id(*dependency);
+        }
+    }
+    renumbered
+}
+
+/// Reads a task file along with the version of the layout it was written in. Older versions are upgraded in memory;
+/// newer ones are refused, since their tasks could have fields this build would silently drop. 
+fn read_document(file_path: &str) -> Result<(u32, Vec<Task>), TaskError> {
+    let mut file = File::open(file_path).map_err(|error| TaskError::io(file_path, error))?;
+    // Sized from the file up front, so that a large file is read without growing the buffer over and over
+    let size = file.metadata().map(|metadata| metadata.len() as usize).unwrap_or(0);
+    let mut data = String::with_capacity(size); 
+    file.read_to_string(&mut data).map_err(|error| TaskError::io(file_path, error))?;
+    let format = Format::for_path(file_path);
+    let header: Option<Header> = match format {
+        Format::Json => serde_json::from_str(&data).ok(),
+        Format::Toml => toml::from_str(&data).ok(),
+        Format::Yaml => serde_yaml::from_str(&data).ok()
+    };
+    // Invalid files are parsed as version 1 as well, which reports where the problem is
+    let version = header.and_then(|header| header.version).unwrap_or(1);
+    if version > FORMAT_VERSION {
+        return Err(TaskError::UnsupportedVersion { path: file_path.to_string(), version });
+    }
+    let tasks = match (format, version) {
+        (Format::Json, 1) => serde_json::from_str(&data).map_err(|error| TaskError::json(file_path, error))?,
+        (Format::Json, _) => serde_json::from_str::<Envelope>(&data).map_err(|error| TaskError::json(file_path, error))?.tasks,
+        (Format::Toml, _) => toml::from_str::<Envelope>(&data).map_err(|error| TaskError::toml(file_path, &data, error))?.tasks,
+        (Format::Yaml, 1) => serde_yaml::from_str(&data).map_err(|error| TaskError::yaml(file_path, error))?,
+        (Format::Yaml, _) => serde_yaml::from_str::<Envelope>(&data).map_err(|error| TaskError::yaml(file_path, error))?.tasks
+    };
+    Ok((version, tasks))
+}
+
+/// Overwrites the contents of the database file, using the current version of the tasks and of the file layout. The
+/// style only applies to JSON files, which are serialized straight into a buffered writer rather than into a string
+/// first. 
+fn write_db(file_path: &str, tasks: &[Task], style: JsonStyle) -> Result<(), TaskError> {
+    let unsupported = |error: String| TaskError::io(file_path, io::Error::other(error));
+    // The file is written while serializing, so an error can be a failed write rather than a problem with the tasks
+    let json = |error: serde_json::Error| if error.is_io() { TaskError::io(file_path, error.into()) } else { TaskError::json(file_path, error) };
+    let document = EnvelopeRef { version: FORMAT_VERSION, tasks };
+    let file = OpenOptions::new().write(true).create(true).truncate(true).open(file_path).map_err(|error| TaskError::io(file_path, error))?;
+    let mut writer = BufWriter::new(file);
+    match Format::for_path(file_path) {
+        Format::Json if style == JsonStyle::Compact => serde_json::to_writer(&mut writer, &document).map_err(json)?,
+        Format::Json => serde_json::to_writer_pretty(&mut writer, &document).map_err(json)?,
+        Format::Toml => {
+            let data = toml::to_string_pretty(&document).map_err(|error| unsupported(error.to_string()))?;
+            writer.write_all(data.as_bytes()).map_err(|error| TaskError::io(file_path, error))?
+        },
+        Format::Yaml => serde_yaml::to_writer(&mut writer, &document).map_err(|error| unsupported(error.to_string()))?
+    }
+    writer.flush().map_err(|error| TaskError::io(file_path, error))
+}
+
+/// Rewrites a task file in the current layout, reporting what changed. 
+fn migrate(file_path: &str, backend: Backend, storage: &mut dyn Storage, options: &Options) -> Result<(), TaskError> {
+    if backend == Backend::Sqlite {
+        report(options, format!("{} is a SQLite database, which has no versions to migrate.", file_path));
+        return Ok(());
+    }
+    if !Path::new(file_path).exists() {
+        report(options, format!("{} doesn't exist yet, nothing to migrate.", file_path));
+        return Ok(());
+    }
+    let (version, mut tasks) = read_document(file_path)?;
+    if version == FORMAT_VERSION {
+        report(options, format!("{} is already at version {}, nothing to migrate.", file_path, FORMAT_VERSION));
+        return Ok(());
+    }
+    if !options.dry_run {
+        // A journal would be replayed on top of the rewritten file; folding it in writes the current version anyway
+        if storage.compact()? {
+            tasks = read_db(file_path)?;
+        } else {
+            write_db(file_path, &tasks, options.json_style)?;
+        }
+    }
+    let mut message = format!("Migrated {} from version {} to version {} ({} tasks):", file_path, version, FORMAT_VERSION, tasks.len());
+    if version < 2 {
+        message.push_str("\n- the tasks are now stored under \"tasks\", next to the \"version\" of the file");
+    }
+    report(options, message);
+    Ok(())
+}
+
+/// Checks a task file for tasks sharing an id and, once confirmed, gives the later ones new ids. 
+fn doctor(file_path: &str, backend: Backend, storage: &mut dyn Storage, input: &mut Input, options: &Options) -> Result<(), TaskError> {
+    if backend == Backend::Sqlite || !Path::new(file_path).exists() {
+        report(options, format!("No problems found in {}.", file_path));
+        return Ok(());
+    }
+    // The journal would be replayed on top of the new IDs, so it is folded into the file first
+    if !options.dry_run {
+        storage.compact()?;
+    }
+    let (_, mut tasks) = read_document(file_path)?;
+    let duplicates = duplicate_ids(&tasks);
+    if duplicates.is_empty() {
+        report(options, format!("No problems found in {}.", file_path));
+        return Ok(());
+    }
+    let listed: Vec<String> = duplicates.iter().map(u32::to_string).collect();
+    report(options, format!("More than one task has the ID {}.", listed.join(", ")));
+    if !input.confirm("Give the later ones new IDs?", options)? {
+        report(options, "Nothing was changed.".to_string());
+        return Ok(());
+    }
+    let reassigned = reassign_duplicate_ids(&mut tasks);
+    if !options.dry_run {
+        write_db(file_path, &tasks, options.json_style)?;
+    }
+    for (old, new) in reassigned {
+        report(options, Outcome::new(format!("Task (ID: {}) is now task (ID: {}).", old, new), format!("renumbered {} {}", old, new)));
+    }
+    Ok(())
+}
+
+/// Writes the tasks to a new backup of the task file, unless this is a dry run, and returns where it is (or would be). 
+fn back_up(file_path: &str, tasks: &[Task], options: &Options, now: DateTime<Utc>) -> Result<PathBuf, TaskError> {
+    let dir = backup::dir(file_path, options.backup_dir.as_deref());
+    let path = backup::path(&dir, file_path, now);
+    if !options.dry_run {
+        std::fs::create_dir_all(&dir).map_err(|error| TaskError::io(&dir.to_string_lossy(), error))?;
+        let backend = options.backend.unwrap_or_else(|| Backend::for_path(file_path));
+        storage::open(backend, &path.to_string_lossy(), options.json_style, false).save(tasks)?;
+    }
+    Ok(path)
+}
+
+/// Moves an invalid task file aside as <file>.corrupt-<timestamp> (and its journal, if any, as
+/// <file>.corrupt-<timestamp>.journal) and starts a new, empty one. This is only ever done on request: a valid file is
+/// left alone, and so is one that can't be read for another reason. 
+fn repair(file_path: &str, backend: Backend, options: &Options, now: DateTime<Utc>) -> Result<(), TaskError> {
+    if backend == Backend::Sqlite {
+        return Err(TaskError::Usage("repair only works on JSON, TOML and YAML task files".to_string()));
+    }
+    if !Path::new(file_path).exists() {
+        report(options, format!("{} doesn't exist yet, nothing to repair.", file_path));
+        return Ok(());
+    }
+    match read_db(file_path) {
+        Ok(_) => report(options, format!("{} is valid, nothing to repair.", file_path)),
+        Err(TaskError::Parse { .. }) => {
+            let backup = corrupt_path(file_path, now);
+            if !options.dry_run {
+                std::fs::rename(file_path, &backup).map_err(|error| TaskError::io(file_path, error))?;
+                // Its journal holds changes to the corrupt file, which mustn't be replayed onto the new one
+                let journal = storage::journal_path(file_path);
+                if Path::new(&journal).exists() {
+                    std::fs::rename(&journal, format!("{}.journal", backup)).map_err(|error| TaskError::io(&journal, error))?;
+                }
+                create_db(file_path)?;
+            }
+            report(options, format!("Moved {} to {} and started a new, empty task list.", file_path, backup));
+        },
+        Err(error) => return Err(error)
+    }
+    Ok(())
+}
+
+/// Where a corrupt database is moved: <file>.corrupt-<timestamp>, or <file>.corrupt-<timestamp>.<n> if that is taken. 
+fn corrupt_path(file_path: &str, now: DateTime<Utc>) -> String {
+    let stamped = format!("{}.corrupt-{}", file_path, now.format("%Y%m%dT%H%M%SZ"));
+    let mut backup = stamped.clone();
+    let mut n = 1;
+    while Path::new(&backup).exists() {
+        backup = format!("{}.{}", stamped, n);
+        n += 1;
+    }
+    backup
+}
+
+/// Parses one or more tasks, given as separate arguments and/or comma-separated ids (e.g. `1,2 5`, `last`). An
+/// argument that is not made of ids is taken as text from a task's description. 
+fn parse_ids(values: &[String]) -> Result<Vec<TaskRef>, String> {
+    let mut ids = vec![];
+    for value in values {
+        if value.starts_with("--") {
+            return Err(format!("Unknown option: {}", value));
+        }
+        for part in value.split(',').filter(|part| !part.is_empty()) {
+            match part.parse::<u32>() {
+                Ok(id) => ids.push(TaskRef::Id(id)),
+                Err(_) if part == "last" => ids.push(TaskRef::Last),
+                Err(_) if !value.contains(',') => ids.push(TaskRef::Text(value.clone())),
+                Err(error) => return Err(error.to_string())
+            }
+        }
+    }
+    if ids.is_empty() {
+        return Err("Not enough arguments".to_string());
+    }
+    Ok(ids)
+}
+
+/// Commands that accept any number of ids. 
+const BULK_COMMANDS: [&str; 4] = ["delete", "mark-todo", "mark-done", "mark-in-progress"];
+
+/// Checks a tag given with `--tag`: one word, with any `#` in front of it dropped. 
+fn parse_tag(value: &str) -> Result<String, String> {
+    let tag = value.trim().trim_start_matches('#');
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!("Invalid tag: {:?} (a tag is a single word, e.g. --tag work)", value));
+    }
+    Ok(tag.to_string())
+}
+
+/// Parses a percentage from 0 to 100, with or without a % sign. 
+fn parse_percent(value: &str) -> Result<u8, String> {
+    match value.strip_suffix('%').unwrap_or(value).parse::<u8>() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(format!("Invalid progress: {} (expected a percentage from 0 to 100)", value))
+    }
+}
+
+/// Parses args into the desired command (min number of args: 1 + 1, max number of args: 6 + 1, except for commands
+/// taking a list of ids, `add` and `update`). Arguments after a `--` are never taken as flags, so `add -- --verbose`
+/// adds a task called "--verbose". 
+fn parse_args(mut args: Vec<String>) -> Result<Command, String> {
+    // Commands are matched by their name in the help table from here on, whichever alias or case they were typed in
+    if let Some(command) = args.get(1).and_then(|name| help::find(name)) {
+        args[1] = command.name.to_string();
+    }
+    let literal_from = match args.iter().skip(2).position(|arg| arg == "--") {
+        Some(index) => {
+            args.remove(index + 2);
+            index + 2
+        },
+        None => args.len()
+    };
+    if args.len() < 2 {
+        Err("Not enough arguments".to_string())
+    } else if args.len() > 7 && !(BULK_COMMANDS.contains(&args[1].as_str()) || ["add", "update", "list", "count", "watch"].contains(&args[1].as_str())) {
+        Err("Too many arguments".to_string())
+    } else {
+        let cmd = args[1].as_str(); 
+        let requires_id = ["set-repeat", "priority", "due", "remind", "start", "stop", "note", "notes", "clear-notes", "history", "move", "restore", "edit-desc", "copy", "mark", "progress", "toggle", "reopen", "estimate", "block", "unblock", "assign", "unassign", "subtask"]; 
+        if help::find(cmd).is_none() {
+            Err(format!("Unknown command: {} (run `task-tracker help` to see the commands)", cmd))
+        } else if cmd == "help" {
+            Ok(Command::Help)
+        } else if cmd == "remind" && args.len() == 2 {
+            Ok(Command::SendReminders)
+        } else if cmd == "delete" && args.iter().any(|arg| arg == "--all-done") {
+            if args.len() == 3 { Ok(Command::DeleteDone) } else { Err("--all-done can't be combined with ids, --cascade or --reparent".to_string()) }
+        } else if BULK_COMMANDS.contains(&cmd) {
+            let flag = |name: &str| cmd == "delete" && args[2..].iter().any(|arg| arg == name);
+            let subtasks = match (flag("--cascade"), flag("--reparent")) {
+                (true, true) => return Err("--cascade and --reparent can't be used together".to_string()),
+                (true, false) => Subtasks::Cascade,
+                (false, true) => Subtasks::Reparent,
+                (false, false) => Subtasks::Refuse
+            };
+            let values: Vec<String> = args[2..].iter().filter(|arg| !(cmd == "delete" && ["--cascade", "--reparent"].contains(&arg.as_str()))).cloned().collect();
+            let ids = parse_ids(&values)?;
+            match cmd {
+                "delete" => Ok(Command::Delete(ids, subtasks)), 
+                "mark-todo" => Ok(Command::Mark(Status::Todo, ids)),
+                "mark-done" => Ok(Command::Mark(Status::Done, ids)),
+                "mark-in-progress" => Ok(Command::Mark(Status::InProgress, ids)),
+                _ => Err("Invalid argument".to_string())
+            }
+        } else if cmd == "show" {
+            match parse_ids(&args[2..])?[..] {
+                [ref task] if args.len() == 3 => Ok(Command::Show(task.clone())),
+                _ => Err("show takes a single task".to_string())
+            }
+        } else if cmd == "update" {
+            let task = parse_ids(&args[2..3.min(args.len())])?;
+            let [task] = &task[..] else { return Err("update takes a single task".to_string()) };
+            let mut changes = TaskChanges::default();
+            match &args[3.min(args.len())..] {
+                [] => return Err(
+                    "Nothing to update: give a new description, or any of --desc <text>, --status <todo|in-progress|done>, --due <YYYY-MM-DD> and --priority <low|medium|high>"
+                    .to_string()
+                ),
+                // The form from before there were flags: just the new description
+                [description] if !description.starts_with("--") || literal_from <= 3 => changes.description = Some(description.to_string()),
+                flags => {
+                    let mut flags = flags.iter();
+                    while let Some(flag) = flags.next() {
+                        let value = flags.next().ok_or(format!("{} needs a value", flag))?;
+                        let set = match flag.as_str() {
+                            "--desc" => changes.description.replace(value.to_string()).is_none(),
+                            "--status" => changes.status.replace(value.parse::<Status>()?).is_none(),
+                            "--due" => changes.due_at.replace(timestamp::parse_date(value)?).is_none(),
+                            "--priority" => changes.priority.replace(value.parse::<Priority>()?).is_none(),
+                            _ => return Err(format!("Invalid option: {} (expected --desc, --status, --due or --priority)", flag))
+                        };
+                        if !set {
+                            return Err(format!("{} is given more than once", flag));
+                        }
+                    }
+                }
+            }
+            Ok(Command::Update(changes, task.clone()))
+        } else if cmd == "restore" && args.get(2).is_some_and(|arg| arg == "--backup") {
+            match &args[3..] {
+                [backup] => Ok(Command::RestoreBackup(backup.to_string())),
+                [] => Err("--backup needs a backup file, or its number in the list `backups` prints".to_string()),
+                _ => Err("Too many arguments".to_string())
+            }
+        } else if requires_id.contains(&cmd) {
+            let id = args
+            .get(2)
+            .ok_or("Not enough arguments".to_string())?
+            .parse::<u32>()
+            .map_err(|error| error.to_string())?;
+            
+            match cmd {
+                "set-repeat" => {
+                    let recurrence = args.get(3).ok_or("Not enough arguments".to_string())?.parse::<Recurrence>()?; 
+                    Ok(Command::SetRepeat(recurrence, id))
+                },
+                "progress" => {
+                    let percent = args.get(3).ok_or("Not enough arguments".to_string())?;
+                    Ok(Command::Progress(parse_percent(percent)?, id))
+                },
+                "priority" => {
+                    let priority = args.get(3).ok_or("Not enough arguments".to_string())?.parse::<Priority>()?; 
+                    Ok(Command::Prioritize(priority, id))
+                },
+                "assign" => {
+                    let name = args.get(3).ok_or("Not enough arguments".to_string())?.trim();
+                    if name.is_empty() {
+                        return Err("The assignee can't be empty".to_string());
+                    }
+                    Ok(Command::Assign(Some(name.to_string()), id))
+                },
+                "unassign" => Ok(Command::Assign(None, id)),
+                "estimate" => {
+                    let estimate = args.get(3).ok_or("Not enough arguments".to_string())?;
+                    Ok(Command::Estimate(estimate.parse::<u32>().map_err(|_| format!("Invalid estimate: {} (expected a whole number)", estimate))?, id))
+                },
+                "due" => {
+                    let date = args.get(3).ok_or("Not enough arguments".to_string())?;
+                    Ok(Command::Due(timestamp::parse_date(date)?, id))
+                },
+                "remind" => {
+                    let offset = args.get(3).ok_or("Not enough arguments".to_string())?;
+                    Ok(Command::Remind(parse_offset(offset)?, id))
+                },
+                "note" => {
+                    let text = args.get(3).ok_or("Not enough arguments".to_string())?; 
+                    Ok(Command::Note(text.to_string(), id))
+                },
+                "notes" => Ok(Command::Notes(id)),
+                "history" => Ok(Command::History(id)),
+                "clear-notes" => Ok(Command::ClearNotes(id)),
+                "restore" => Ok(Command::Restore(id)),
+                "edit-desc" => Ok(Command::EditDescription(id)),
+                "copy" => Ok(Command::Copy(id, args.get(3).cloned())),
+                "subtask" => match &args[3..] {
+                    [description] if !description.starts_with("--") || literal_from <= 3 => {
+                        Ok(Command::Add(description.to_string(), AddOptions { parent: Some(id), ..AddOptions::default() }))
+                    },
+                    [option] => Err(format!("Unknown option: {}", option)),
+                    [] => Err("Not enough arguments".to_string()),
+                    _ => Err("Too many arguments (a description with spaces needs quotes)".to_string())
+                },
+                "mark" => match &args[3..] {
+                    [] => Ok(Command::Advance(id, false)),
+                    [flag] if flag == "--wrap" => Ok(Command::Advance(id, true)),
+                    _ => Err("Invalid argument".to_string())
+                },
+                "toggle" => Ok(Command::Toggle(id)),
+                "block" | "unblock" => {
+                    let on = match &args[3..] {
+                        [flag, on] if flag == "--on" => on.parse::<u32>().map_err(|_| format!("Invalid ID: {}", on))?,
+                        _ => return Err(format!("Expected {} <id> --on <other id>", cmd))
+                    };
+                    Ok(if cmd == "block" { Command::Block(id, on) } else { Command::Unblock(id, on) })
+                },
+                "reopen" => Ok(Command::Reopen(id)),
+                "move" => {
+                    let position = args.get(3).ok_or("Not enough arguments".to_string())?.parse::<usize>().map_err(|error| error.to_string())?;
+                    if position == 0 {
+                        return Err("Positions start at 1".to_string());
+                    }
+                    Ok(Command::Move(id, position))
+                },
+                "start" => Ok(Command::Start(id)),
+                "stop" => Ok(Command::Stop(id)),
+                _ => Err("Invalid argument".to_string())
+            }
+        } else if cmd == "add" {
+            let mut stdin = false;
+            let mut description = None;
+            let mut options = AddOptions::default();
+            let mut flags = args[2..literal_from].iter();
+            while let Some(flag) = flags.next() {
+                match flag.as_str() {
+                    "--parent" => {
+                        let parent = flags
+                        .next()
+                        .ok_or("Not enough arguments".to_string())?
+                        .parse::<u32>()
+                        .map_err(|error| error.to_string())?;
+                        options.parent = Some(parent);
+                    },
+                    "--repeat" => {
+                        let recurrence = flags.next().ok_or("Not enough arguments".to_string())?.parse::<Recurrence>()?;
+                        options.recurrence = Some(recurrence);
+                    },
+                    "--status" => options.status = Some(flags.next().ok_or("Not enough arguments".to_string())?.parse::<Status>()?),
+                    "--priority" => options.priority = Some(flags.next().ok_or("Not enough arguments".to_string())?.parse::<Priority>()?),
+                    "--due" => options.due_at = Some(timestamp::parse_date(flags.next().ok_or("Not enough arguments".to_string())?)?),
+                    "--stdin" => stdin = true,
+                    "--tag" => {
+                        let tag = parse_tag(flags.next().ok_or("--tag needs a value".to_string())?)?;
+                        if !options.tags.iter().any(|other| other.to_lowercase() == tag.to_lowercase()) {
+                            options.tags.push(tag);
+                        }
+                    },
+                    value if !value.starts_with("--") && description.is_none() => description = Some(value),
+                    value if value.starts_with("--") => return Err(format!("Unknown option: {}", value)),
+                    value => return Err(format!("Unexpected argument: {} (a description with spaces needs quotes)", value))
+                }
+            }
+            for value in &args[literal_from..] {
+                match description {
+                    None => description = Some(value),
+                    Some(_) => return Err(format!("Unexpected argument: {} (a description with spaces needs quotes)", value))
+                }
+            }
+            match (description, stdin) {
+                (Some(description), false) => Ok(Command::Add(description.to_string(), options)),
+                (None, true) => Ok(Command::AddFromStdin(options)),
+                (Some(_), true) => Err("--stdin reads the descriptions from stdin, so none can be given".to_string()),
+                (None, false) => Err("Not enough arguments".to_string())
+            }
+        } else if cmd == "archive" {
+            match args.get(2).map(String::as_str) {
+                Some("--before") => {
+                    let date = args.get(3).ok_or("Not enough arguments".to_string())?;
+                    Ok(Command::Archive(Some(timestamp::parse_date(date)?)))
+                },
+                Some(option) => Err(format!("Unknown option: {} (expected --before)", option)),
+                None => Ok(Command::Archive(None))
+            }
+        } else if cmd == "prune" {
+            match (args.get(2).map(String::as_str), args.get(3)) {
+                (Some("--older-than"), Some(days)) if args.len() == 4 => {
+                    let days = days.parse::<u32>().map_err(|_| format!("Invalid number of days: {}", days))?;
+                    days_before(Utc::now(), days).map_err(|error| error.to_string())?;
+                    Ok(Command::Prune(days))
+                },
+                (Some("--older-than"), None) => Err("Not enough arguments".to_string()),
+                (Some(option), _) if option != "--older-than" => Err(format!("Unknown option: {} (expected --older-than)", option)),
+                (Some(_), _) => Err("Too many arguments".to_string()),
+                (None, _) => Err("Expected --older-than <days>".to_string())
+            }
+        } else if cmd == "watch" {
+            // The same filters as list, which does the parsing 
+            let mut list = args.clone();
+            list[1] = "list".to_string();
+            match parse_args(list)? {
+                Command::List(filter, ListSource::Tasks, ListOutput::Tasks) => Ok(Command::Watch(filter)),
+                _ => Err("watch only shows the task list, so --archived, --trash and --count can't be used".to_string())
+            }
+        } else if cmd == "count" {
+            // `list --count` under a shorter name, for shell prompts 
+            let mut list = args.clone();
+            list[1] = "list".to_string();
+            match parse_args(list)? {
+                Command::List(filter, source, _) => Ok(Command::List(filter, source, ListOutput::Count)),
+                _ => Err("Invalid argument".to_string())
+            }
+        } else if cmd == "interactive" {
+            if args.len() == 2 { Ok(Command::Interactive) } else { Err("Too many arguments".to_string()) }
+        } else if cmd == "merge" {
+            match &args[2..] {
+                [path] => Ok(Command::Merge(path.to_string())),
+                [] => Err("Expected the task file to merge, e.g. merge tasks.sync-conflict.json".to_string()),
+                _ => Err("Too many arguments".to_string())
+            }
+        } else if cmd == "import" {
+            match &args[2..] {
+                [format, path] if format == "todotxt" => Ok(Command::ImportTodoTxt(path.to_string())),
+                [format, _] => Err(format!("Unknown import format: {} (expected todotxt)", format)),
+                _ => Err("Expected a format and a file, e.g. import todotxt todo.txt".to_string())
+            }
+        } else if cmd == "export" {
+            match &args[2..] {
+                [format] if format == "ics" => Ok(Command::ExportIcs(None)),
+                [format, path] if format == "ics" => Ok(Command::ExportIcs(Some(path.to_string()))),
+                [format, ..] if format != "ics" => Err(format!("Unknown export format: {} (expected ics)", format)),
+                [] => Err("Expected a format, e.g. export ics tasks.ics".to_string()),
+                _ => Err("Too many arguments".to_string())
+            }
+        } else if cmd == "empty-trash" && args.len() == 2 {
+            Ok(Command::EmptyTrash)
+        } else if cmd == "compact" && args.len() == 2 {
+            Ok(Command::Compact)
+        } else if cmd == "backup" {
+            match &args[2..] {
+                [] => Ok(Command::Backup(None)),
+                [option, count] if option == "--keep" => {
+                    let keep = count.parse::<usize>().ok().filter(|keep| *keep > 0).ok_or(format!("Invalid number of backups to keep: {} (expected 1 or more)", count))?;
+                    Ok(Command::Backup(Some(keep)))
+                },
+                [option] if option == "--keep" => Err("--keep needs a value".to_string()),
+                [option, ..] => Err(format!("Unknown option: {} (expected --keep)", option))
+            }
+        } else if cmd == "backups" && args.len() == 2 {
+            Ok(Command::Backups)
+        } else if cmd == "reset-ids" && args.len() == 2 {
+            Ok(Command::ResetIds)
+        } else if cmd == "validate" && args.len() == 2 {
+            Ok(Command::Validate)
+        } else if cmd == "completions" {
+            match &args[2..] {
+                [shell] => Ok(Command::Completions(shell.parse()?)),
+                [] => Err("Expected a shell: bash, zsh or fish".to_string()),
+                _ => Err("Too many arguments".to_string())
+            }
+        } else if cmd == "report" {
+            let (mut weeks, mut from, mut to) = (None, None, None);
+            let mut options = args[2..].iter();
+            while let Some(option) = options.next() {
+                let mut value = || options.next().ok_or(format!("{} needs a value", option));
+                match option.as_str() {
+                    "--weeks" => {
+                        let count = value()?;
+                        weeks = Some(count.parse::<u32>().ok().filter(|weeks| (1..=520).contains(weeks)).ok_or(format!("Invalid number of weeks: {} (expected 1 to 520)", count))?);
+                    },
+                    "--from" => from = Some(timestamp::parse_day(value()?)?),
+                    "--to" => to = Some(timestamp::parse_day(value()?)?),
+                    option if option.starts_with("--") => return Err(format!("Unknown option: {} (expected --weeks, --from or --to)", option)),
+                    other => return Err(format!("Unexpected argument: {}", other))
+                }
+            }
+            match (weeks, from, to) {
+                (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err("--weeks can't be combined with --from and --to".to_string()),
+                (_, None, Some(_)) => Err("--to needs --from".to_string()),
+                (_, Some(from), Some(to)) if to < from => Err(format!("--to ({}) is before --from ({})", to, from)),
+                (_, Some(from), to) => Ok(Command::Report(ReportRange::Between(from, to))),
+                (weeks, None, None) => Ok(Command::Report(ReportRange::Weeks(weeks.unwrap_or(4))))
+            }
+        } else if cmd == "stats" && args.len() == 2 {
+            Ok(Command::Stats)
+        } else if cmd == "projects" && args.len() == 2 {
+            Ok(Command::Projects)
+        } else if cmd == "path" && args.len() == 2 {
+            Ok(Command::Path)
+        } else if cmd == "repair" && args.len() == 2 {
+            Ok(Command::Repair)
+        } else if cmd == "migrate" && args.len() == 2 {
+            Ok(Command::Migrate)
+        } else if (cmd == "doctor" || cmd == "fsck") && args.len() == 2 {
+            Ok(Command::Doctor)
+        } else if cmd == "search" {
+            let (mut text, mut regex, mut ignore_case) = (None, false, false);
+            let mut values = args[2..].iter().enumerate();
+            while let Some((index, value)) = values.next() {
+                let found = match value.as_str() {
+                    _ if index + 2 >= literal_from => value,
+                    "--regex" => {
+                        regex = true;
+                        values.next().ok_or("Not enough arguments".to_string())?.1
+                    },
+                    "-i" => {
+                        ignore_case = true;
+                        continue;
+                    },
+                    option if option.starts_with('-') => return Err(format!("Unknown option: {}", option)),
+                    _ => value
+                };
+                if text.replace(found).is_some() {
+                    return Err("Too many arguments (quote text that contains spaces)".to_string());
+                }
+            }
+            let text = text.ok_or("Not enough arguments".to_string())?;
+            let filter = ListFilter { status: Some(StatusFilter::ALL), description: Some(DescriptionPattern::new(text, regex, ignore_case)?), ..ListFilter::default() };
+            Ok(Command::List(filter, ListSource::Tasks, ListOutput::Tasks))
+        } else if cmd == "list" {
+            let mut filter = ListFilter::default();
+            let mut source = ListSource::Tasks;
+            let mut output = ListOutput::Tasks;
+            let (mut since, mut until) = (None, None);
+            let (mut pattern, mut ignore_case) = (None, false);
+            let mut options = args[2..].iter();
+            while let Some(option) = options.next() {
+                let mut value = || options.next().ok_or("Not enough arguments".to_string());
+                match option.as_str() {
+                    // For stand-ups: what was finished today 
+                    "completed-today" => filter.completed = Some(Period::Today),
+                    status if !status.starts_with('-') => filter.status = Some(status.parse()?),
+                    "--archived" => source = ListSource::Archive,
+                    "--trash" => source = ListSource::Trash,
+                    "--count" => output = ListOutput::Count,
+                    "--sort" => filter.sort = Some(value()?.parse::<SortKey>()?),
+                    "--status" => filter.status = Some(value()?.parse()?),
+                    "--tag" => filter.tag = Some(parse_tag(value()?)?),
+                    "--stale" => {
+                        let days = value()?;
+                        let days = days.parse::<u32>().map_err(|_| format!("Invalid number of days: {}", days))?;
+                        days_before(Utc::now(), days).map_err(|error| error.to_string())?;
+                        filter.stale = Some(days);
+                    },
+                    "--assignee" => filter.assignee = Some(AssigneeFilter::Named(value()?.trim().to_string())),
+                    "--unassigned" => filter.assignee = Some(AssigneeFilter::Unassigned),
+                    "--min-priority" => filter.min_priority = Some(value()?.parse::<Priority>()?),
+                    "--created" => filter.created = Some(value()?.parse::<Period>()?),
+                    "--updated" => filter.updated = Some(value()?.parse::<Period>()?),
+                    "--completed" => filter.completed = Some(value()?.parse::<Period>()?),
+                    "--since" => since = Some(timestamp::parse_day(value()?)?),
+                    "--until" => until = Some(timestamp::parse_day(value()?)?),
+                    "--limit" => {
+                        let limit = value()?;
+                        filter.limit = Some(limit.parse::<usize>().map_err(|_| format!("Invalid limit: {} (expected a number of tasks)", limit))?).filter(|limit| *limit > 0);
+                    },
+                    "--regex" => pattern = Some(value()?),
+                    "-i" => ignore_case = true,
+                    "--template" => filter.template = Some(Template::parse(value()?)?),
+                    "--plain" => filter.plain = true,
+                    "--page" => {
+                        let page = value()?;
+                        filter.page = page.parse::<usize>().ok().filter(|page| *page > 0).ok_or(format!("Invalid page: {} (pages are numbered from 1)", page))?;
+                    },
+                    option => return Err(format!("Unknown option: {}", option))
+                }
+            }
+            if since.is_some() || until.is_some() {
+                if filter.created.is_some() {
+                    return Err("--created can't be combined with --since and --until".to_string());
+                }
+                filter.created = Some(Period::Between(since, until));
+            }
+            // Only done tasks have been completed, and `list` leaves them out unless told otherwise
+            if filter.completed.is_some() && filter.status.is_none() {
+                filter.status = Some(StatusFilter::ALL);
+            }
+            if filter.plain && filter.template.is_some() {
+                return Err("--plain can't be combined with --template".to_string());
+            }
+            match pattern {
+                Some(pattern) => filter.description = Some(DescriptionPattern::new(pattern, true, ignore_case)?),
+                None if ignore_case => return Err("-i goes with --regex, to match regardless of case".to_string()),
+                None => {}
+            }
+            if filter.page > 1 && filter.limit.is_none() {
+                return Err("--page needs --limit, which sets the number of tasks on a page".to_string());
+            }
+            Ok(Command::List(filter, source, output))
+        } else {
+            Err("Invalid argument".to_string())
+        }
+    }
+}
+
+/// Days a date filter covers, in local time: from midnight at the start of the first day up to (but not including)
+/// midnight at the end of the last. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Period {
+    Today, 
+    /// Monday to Sunday. 
+    ThisWeek, 
+    /// From and until the given days; either can be left open. 
+    Between(Option<NaiveDate>, Option<NaiveDate>)
+}
+
+impl Period {
+    /// The first moment in the period and the first one after it, if bounded. 
+    fn bounds<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let tz = now.timezone();
+        let today = now.date_naive();
+        let (first, last) = match self {
+            Period::Today => (Some(today), Some(today)),
+            Period::ThisWeek => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                (Some(monday), Some(monday + Duration::days(6)))
+            },
+            Period::Between(since, until) => (*since, *until)
+        };
+        let start = first.map(|day| timestamp::midnight(day, &tz));
+        let end = last.map(|day| timestamp::midnight(day + Duration::days(1), &tz));
+        (start, end)
+    }
+    fn contains<Tz: TimeZone>(&self, time: &DateTime<Utc>, now: &DateTime<Tz>) -> bool {
+        let (start, end) = self.bounds(now);
+        start.is_none_or(|start| *time >= start) && end.is_none_or(|end| *time < end)
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "today" => Ok(Period::Today),
+            "this-week" => Ok(Period::ThisWeek),
+            _ => Err("Invalid period, expected one of: today, this-week".to_string())
+        }
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Period::Today => write!(f, "today"),
+            Period::ThisWeek => write!(f, "this week"),
+            Period::Between(Some(since), Some(until)) => write!(f, "between {} and {}", since, until),
+            Period::Between(Some(since), None) => write!(f, "since {}", since),
+            Period::Between(None, Some(until)) => write!(f, "until {}", until),
+            Period::Between(None, None) => write!(f, "at any time")
+        }
+    }
+}
+
+/// The statuses `list` shows: any set of them, e.g. `todo,in-progress`. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StatusFilter {
+    todo: bool, 
+    in_progress: bool, 
+    done: bool
+}
+
+impl StatusFilter {
+    /// Everything but done tasks, which is what `list` shows unless told otherwise. 
+    const OPEN: StatusFilter = StatusFilter { todo: true, in_progress: true, done: false };
+    const ALL: StatusFilter = StatusFilter { todo: true, in_progress: true, done: true };
+    const NONE: StatusFilter = StatusFilter { todo: false, in_progress: false, done: false };
+
+    fn only(status: Status) -> Self {
+        Self::NONE.with(status)
+    }
+    fn with(mut self, status: Status) -> Self {
+        match status {
+            Status::Todo => self.todo = true,
+            Status::InProgress => self.in_progress = true,
+            Status::Done => self.done = true
+        }
+        self
+    }
+    fn matches(self, status: Status) -> bool {
+        match status {
+            Status::Todo => self.todo,
+            Status::InProgress => self.in_progress,
+            Status::Done => self.done
+        }
+    }
+    /// The statuses in the set, in the order a task goes through them. 
+    fn statuses(self) -> Vec<Status> {
+        [Status::Todo, Status::InProgress, Status::Done].into_iter().filter(|status| self.matches(*status)).collect()
+    }
+}
+
+/// The statuses joined with "or", e.g. "todo or in progress". 
+impl Display for StatusFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<String> = self.statuses().iter().map(|status| status.to_string()).collect();
+        write!(f, "{}", names.join(" or "))
+    }
+}
+
+/// A comma-separated list of statuses, any of which can also be `all` or `not-done`. 
+impl FromStr for StatusFilter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = StatusFilter::NONE;
+        for token in s.split(',') {
+            let statuses = match token.trim().to_lowercase().as_str() {
+                "all" => StatusFilter::ALL,
+                "not-done" => StatusFilter::OPEN,
+                name => name.parse().map(StatusFilter::only).map_err(|_| format!("Invalid status: '{}', expected one of: all, not-done, todo, in-progress, done", token))?
+            };
+            filter = statuses.statuses().into_iter().fold(filter, StatusFilter::with);
+        }
+        Ok(filter)
+    }
+}
+
+/// An order `list` can show tasks in, other than their own. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    /// Smallest estimate first; tasks without one come last. 
+    Estimate, 
+    /// In progress, then todo, then done; within each, highest priority and then oldest first. 
+    Smart, 
+    /// The order the tasks were added in, ignoring `move`. 
+    Id
+}
+
+impl SortKey {
+    /// Sorts tasks, or references to them. The sort is stable, so tasks that tie keep their order. 
+    fn sort<T: Borrow<Task>>(self, tasks: &mut [T]) {
+        match self {
+            SortKey::Estimate => tasks.sort_by_key(|task| (task.borrow().estimate.is_none(), task.borrow().estimate)), 
+            SortKey::Smart => tasks.sort_by(|a, b| smart_order(a.borrow(), b.borrow())), 
+            SortKey::Id => tasks.sort_by_key(|task| task.borrow().id)
+        }
+    }
+}
+
+/// The order of `--sort smart`. Tasks without a priority rank as medium, as they do for `--min-priority`; tasks that
+/// tie on everything else keep the order of their ids. 
+fn smart_order(a: &Task, b: &Task) -> std::cmp::Ordering {
+    let group = |task: &Task| match task.status {
+        Status::InProgress => 0, 
+        Status::Todo => 1, 
+        Status::Done => 2
+    };
+    group(a).cmp(&group(b))
+        .then_with(|| b.priority.unwrap_or(Priority::Medium).cmp(&a.priority.unwrap_or(Priority::Medium)))
+        .then_with(|| a.created_at.cmp(&b.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "estimate" => Ok(SortKey::Estimate),
+            "smart" => Ok(SortKey::Smart),
+            "id" => Ok(SortKey::Id),
+            _ => Err("Invalid sort order, expected one of: estimate, smart, id".to_string())
+        }
+    }
+}
+
+/// Whom the tasks `list` shows are assigned to. 
+#[derive(Debug, Clone, PartialEq)]
+enum AssigneeFilter {
+    /// Assigned to this person, ignoring case. 
+    Named(String), 
+    Unassigned
+}
+
+impl AssigneeFilter {
+    fn matches(&self, assignee: Option<&str>) -> bool {
+        match (self, assignee) {
+            (AssigneeFilter::Named(name), Some(assignee)) => name.to_lowercase() == assignee.to_lowercase(),
+            (AssigneeFilter::Unassigned, None) => true,
+            _ => false
+        }
+    }
+}
+
+/// What the descriptions of the tasks `list` shows must contain: a plain text (`search <text>`) or a regular
+/// expression (`--regex`), matched anywhere in the description. 
+#[derive(Debug, Clone)]
+struct DescriptionPattern {
+    /// As the user typed it, for messages. 
+    text: String, 
+    ignore_case: bool, 
+    regex: Regex
+}
+
+impl DescriptionPattern {
+    /// Fails with the regex crate's explanation, pointing at the mistake, if `regex` is set and the pattern is invalid. 
+    fn new(text: &str, regex: bool, ignore_case: bool) -> Result<Self, String> {
+        let source = if regex { text.to_string() } else { regex::escape(text) };
+        let regex = RegexBuilder::new(&source).case_insensitive(ignore_case).build().map_err(|error| format!("Invalid pattern: {}", error))?;
+        Ok(Self { text: text.to_string(), ignore_case, regex })
+    }
+    fn matches(&self, description: &str) -> bool {
+        self.regex.is_match(description)
+    }
+}
+
+impl PartialEq for DescriptionPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str() && self.ignore_case == other.ignore_case
+    }
+}
+
+/// The days `report` covers. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportRange {
+    /// This week and the ones before it, up to today. 
+    Weeks(u32), 
+    /// From and until the given days, both included; until today if no end is given. 
+    Between(NaiveDate, Option<NaiveDate>)
+}
+
+impl ReportRange {
+    fn days(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            ReportRange::Weeks(weeks) => (report::monday(today) - Duration::weeks(i64::from(weeks.saturating_sub(1))), today), 
+            ReportRange::Between(from, to) => (from, to.unwrap_or(today))
+        }
+    }
+}
+
+/// Which tasks `list` shows: those meeting every condition that is set, the order they are shown in and how. 
+#[derive(Debug, Clone, PartialEq)]
+struct ListFilter {
+    /// Unset shows every status; `list` fills it in from the config file, or else leaves out done tasks. 
+    status: Option<StatusFilter>, 
+    min_priority: Option<Priority>, 
+    /// When the task was created, from `--created` or `--since`/`--until`. 
+    created: Option<Period>, 
+    /// When the task was last updated; tasks that never were don't match. 
+    updated: Option<Period>, 
+    /// When the task was completed; tasks that are not done don't match. 
+    completed: Option<Period>, 
+    /// Unset keeps the tasks in their own order. 
+    sort: Option<SortKey>, 
+    assignee: Option<AssigneeFilter>, 
+    /// A tag the task has, ignoring case. 
+    tag: Option<String>, 
+    /// Shows only tasks not updated (or, if never updated, created) in this many days. 
+    stale: Option<u32>, 
+    description: Option<DescriptionPattern>, 
+    /// How many of the matching tasks to show at most, a page at a time; unset shows them all. 
+    limit: Option<usize>, 
+    /// Which page of `limit` tasks to show, counting from 1. 
+    page: usize, 
+    /// Prints a line per task from this instead of the usual list, without the summary. 
+    template: Option<Template>, 
+    /// Prints each task as `key=value` lines, with a blank line between tasks, instead of the usual list. 
+    plain: bool
+}
+
+impl Default for ListFilter {
+    fn default() -> Self {
+        ListFilter { status: None, min_priority: None, created: None, updated: None, completed: None, sort: None, assignee: None, tag: None, stale: None, description: None, limit: None, page: 1, template: None, plain: false }
+    }
+}
+
+impl ListFilter {
+    fn matches<Tz: TimeZone>(&self, task: &Task, now: &DateTime<Tz>) -> bool {
+        self.status.is_none_or(|status| status.matches(task.status))
+            && self.min_priority.is_none_or(|priority| task.has_priority_at_least(priority))
+            && self.created.is_none_or(|period| period.contains(&task.created_at, now))
+            && self.updated.is_none_or(|period| task.updated_at.is_some_and(|updated_at| period.contains(&updated_at, now)))
+            && self.completed.is_none_or(|period| task.completed_at.is_some_and(|completed_at| period.contains(&completed_at, now)))
+            && self.assignee.as_ref().is_none_or(|assignee| assignee.matches(task.assignee.as_deref()))
+            // Checked when the command line is read, so a number of days that is too large can't get here
+            && self.stale.is_none_or(|days| days_before(now.with_timezone(&Utc), days).is_ok_and(|cutoff| task.last_touched() < cutoff))
+            && self.tag.as_ref().is_none_or(|tag| task.tags.iter().any(|other| other.to_lowercase() == tag.to_lowercase()))
+            && self.description.as_ref().is_none_or(|pattern| pattern.matches(&task.description))
+    }
+}
+
+/// The tasks that match the filter, in the order `list` shows them. Paging is left to the caller, which also needs
+/// to know how many tasks there are in all. 
+fn filter_tasks<'a, Tz: TimeZone>(filter: &ListFilter, tasks: &'a [Task], now: &DateTime<Tz>) -> Vec<&'a Task> {
+    let mut filtered: Vec<&Task> = tasks.iter().filter(|task| filter.matches(task, now)).collect();
+    if let Some(sort) = filter.sort {
+        sort.sort(&mut filtered);
+    }
+    filtered
+}
+
+/// Renders the output of `list` in the selected format, along with the number of tasks it contains. 
+fn render_list(filter: &ListFilter, tasks: Vec<Task>, options: &Options) -> Result<(String, usize), TaskError> {
+    let tasks: Vec<Task> = tasks.into_iter().filter(|task| !task.archived).collect();
+    let total = tasks.len();
+    let filtered_tasks: Vec<Task> = filter_tasks(filter, &tasks, &Local::now()).into_iter().cloned().collect();
+    let matching = filtered_tasks.len();
+    let shown = filter.limit.map(|limit| paging::page(matching, limit, filter.page));
+    let page_tasks = match &shown {
+        Some(rows) => &filtered_tasks[rows.clone()], 
+        None => &filtered_tasks[..]
+    };
+    let output = if let Some(template) = &filter.template {
+        let times = TimeFormat::for_details(options);
+        page_tasks.iter().map(|task| template.render(task, times) + "\n").collect()
+    } else if filter.plain {
+        let times = TimeFormat::for_details(options);
+        page_tasks.iter().map(|task| task.plain(times)).collect::<Vec<_>>().join("\n")
+    } else if options.ndjson {
+        let mut lines = String::new();
+        for task in page_tasks {
+            lines.push_str(&serde_json::to_string(task).map_err(|error| TaskError::json("<stdout>", error))?);
+            lines.push('\n');
+        }
+        lines
+    } else if options.json {
+        let json = serde_json::to_string_pretty(page_tasks).map_err(|error| TaskError::json("<stdout>", error))?;
+        format!("{}\n", json)
+    } else if options.output == OutputMode::Porcelain {
+        page_tasks.iter().map(|task| format!("{}\t{}\t{}\n", task.id, task.status.name(), output::escape(&task.description))).collect()
+    } else if filtered_tasks.is_empty() {
+        if let Some(s) = filter.status.filter(|status| ![StatusFilter::OPEN, StatusFilter::ALL].contains(status)) {
+            format!("No tasks found with the status {}\n", s)
+        } else if let Some(p) = filter.min_priority {
+            format!("No tasks found with priority {} or higher\n", p)
+        } else if let Some(period) = filter.created {
+            format!("No tasks found created {}\n", period)
+        } else if let Some(period) = filter.updated {
+            format!("No tasks found updated {}\n", period)
+        } else if let Some(period) = filter.completed {
+            format!("No tasks found completed {}\n", period)
+        } else if let Some(AssigneeFilter::Named(name)) = &filter.assignee {
+            format!("No tasks found assigned to {}\n", name)
+        } else if let Some(AssigneeFilter::Unassigned) = filter.assignee {
+            "No unassigned tasks found\n".to_string()
+        } else if let Some(tag) = &filter.tag {
+            format!("No tasks found tagged {}\n", tag)
+        } else if let Some(days) = filter.stale {
+            format!("No tasks found that weren't updated in {} day(s)\n", days)
+        } else if let Some(pattern) = &filter.description {
+            format!("No tasks found matching '{}'\n", pattern.text)
+        } else if filter.status == Some(StatusFilter::OPEN) && total > 0 {
+            "All tasks are done; run `list all` to see them\n".to_string()
+        } else {
+            String::new()
+        }
+    } else {
+        let mut output = String::new();
+        if !page_tasks.is_empty() {
+            output = format!("{}------------\n", Task::render(page_tasks, &tasks, TimeFormat::for_list(options)));
+        }
+        let _ = writeln!(output, "{}", summarize(&filtered_tasks, total));
+        if let Some(rows) = &shown {
+            let _ = writeln!(output, "{}", paging::showing(rows, matching));
+        }
+        output
+    };
+    Ok((output, matching))
+}
+
+/// The total estimate of the tasks that are not done, along with how many of them have no estimate. 
+fn outstanding_estimate(tasks: &[Task]) -> (u64, usize) {
+    let open: Vec<&Task> = tasks.iter().filter(|task| task.status != Status::Done).collect();
+    let total = open.iter().filter_map(|task| task.estimate).map(u64::from).sum();
+    (total, open.iter().filter(|task| task.estimate.is_none()).count())
+}
+
+/// The footer of `list`, counting the listed tasks by status, e.g. "3 todo, 2 in progress, 5 done (10 total)". When
+/// some tasks were filtered out, it says how many were listed out of the total. 
+fn summarize(tasks: &[Task], total: usize) -> String {
+    let count = |status: Status| tasks.iter().filter(|task| task.status == status).count();
+    let counts = format!("{} todo, {} in progress, {} done", count(Status::Todo), count(Status::InProgress), count(Status::Done));
+    if tasks.len() == total {
+        format!("{} ({} total)", counts, total)
+    } else {
+        format!("{} ({} listed, {} total)", counts, tasks.len(), total)
+    }
+}
+
+fn list_tasks(filter: &ListFilter, tasks: Vec<Task>, options: &Options) -> Result<(), TaskError> {
+    let (output, count) = render_list(filter, tasks, options)?;
+    let mut stdout = io::stdout();
+    let written = if options.clipboard {
+        let mut system = clipboard::system();
+        let clipboard: Result<&mut dyn clipboard::Clipboard, String> = match &mut system {
+            Ok(clipboard) => Ok(clipboard.as_mut()),
+            Err(reason) => Err(reason.clone())
+        };
+        clipboard::copy_or_print(&output, count, clipboard, &mut stdout)
+    } else {
+        stdout.write_all(output.as_bytes())
+    };
+    written.map_err(|error| TaskError::io("<stdout>", error))
+}
+
+/// Renders the output of `show`: the task's details, or the task as a JSON object. 
+fn render_show(id: u32, tasks: &[Task], options: &Options) -> Result<String, TaskError> {
+    let task = tasks.iter().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+    if options.json {
+        let json = serde_json::to_string_pretty(task).map_err(|error| TaskError::json("<stdout>", error))?;
+        Ok(format!("{}\n", json))
+    } else {
+        Ok(format_details(task, tasks, TimeFormat::for_details(options)))
+    }
+}
+
+/// Where commands read their input from: the answers to their questions, and the lines of `add --stdin`. It holds
+/// the lock on stdin, so that in interactive mode a question reads from the session's input instead of waiting for
+/// a lock the session never releases. 
+struct Input<'a> {
+    reader: &'a mut dyn BufRead, 
+    /// Whether there is someone to answer questions, i.e. stdin is a terminal. 
+    ask: bool
+}
+
+impl Input<'_> {
+    /// Asks a question before doing something that can't be undone, unless `--yes` or `--dry-run` is given or there is
+    /// no one to ask. Returns whether to go ahead. 
+    fn confirm(&mut self, question: &str, options: &Options) -> Result<bool, TaskError> {
+        if options.yes || options.dry_run || !self.ask {
+            return Ok(true);
+        }
+        confirm(question, self.reader, &mut io::stdout()).map_err(|error| TaskError::io("<stdin>", error))
+    }
+}
+
+/// Asks a yes/no question and reads the answer from `input`; only an explicit "y" or "yes" counts as yes. 
+fn confirm(question: &str, input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<bool> {
+    write!(out, "{} [y/N] ", question)?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Handles a problem the command can carry on from: prints `message` to stderr and goes on, or with `--strict` fails
+/// with `error` instead. 
+pub(crate) fn tolerate(options: &Options, error: TaskError, message: String) -> Result<(), TaskError> {
+    if options.strict {
+        Err(error)
+    } else {
+        eprintln!("{}", message);
+        Ok(())
+    }
+}
+
+/// Reports ids that a command could not find, without failing the command unless `--strict` is given. 
+fn report_not_found(ids: &[u32], options: &Options) -> Result<(), TaskError> {
+    match ids.first() {
+        Some(&id) => tolerate(options, TaskError::NotFound { id }, format!("Error: ID not found: {}.", join_ids(ids))), 
+        None => Ok(())
+    }
+}
+
+/// Reports a task that a command could not find, like [`report_not_found`]. 
+fn not_found(id: u32, options: &Options) -> Result<(), TaskError> {
+    tolerate(options, TaskError::NotFound { id }, "Error: ID not found.".to_string())
+}
+
+const FILE_PATH: &str = "tasks.json";
+/// The directory task files go in by default, under the platform data directory. 
+const DATA_DIR_NAME: &str = "task-tracker";
+/// Environment variable naming the task file, for when `--file` would have to be given every time. 
+const FILE_ENV_VAR: &str = "TASKS_FILE";
+
+/// Where the path of the task file came from, as printed by `path`. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PathSource {
+    Flag, 
+    Env, 
+    /// A task file that was already in the working directory, where they used to go by default. 
+    WorkingDir, 
+    /// The platform data directory, e.g. `~/.local/share/task-tracker` on Linux. 
+    DataDir
+}
+
+impl Display for PathSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSource::Flag => write!(f, "given with --file"), 
+            PathSource::Env => write!(f, "given with {}", FILE_ENV_VAR), 
+            PathSource::WorkingDir => write!(f, "found in the working directory"), 
+            PathSource::DataDir => write!(f, "the default, in the data directory")
+        }
+    }
+}
+
+/// Picks the task file: the one given with `--file`, else with `TASKS_FILE`, else the project's file in `cwd` if it
+/// or the default task file exists there (so that projects stay next to it), else the one in `data_dir`. Without a
+/// data directory (on a platform that has none) it falls back to `cwd`. 
+fn resolve_path(file: Option<&str>, env_file: Option<&str>, cwd: &Path, data_dir: Option<&Path>, project: Option<&str>, backend: Backend) -> (PathBuf, PathSource) {
+    if let Some(file) = file {
+        return (PathBuf::from(file), PathSource::Flag);
+    }
+    if let Some(file) = env_file.filter(|file| !file.is_empty()) {
+        return (PathBuf::from(file), PathSource::Env);
+    }
+    let here = db_path(cwd, project, backend);
+    match data_dir {
+        Some(dir) if !here.exists() && !db_path(cwd, None, backend).exists() => (db_path(dir, project, backend), PathSource::DataDir), 
+        _ => (here, PathSource::WorkingDir)
+    }
+}
+
+/// Checks that a project name can be used as part of a file name without leaving the data directory. 
+fn validate_project_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['.', '/', '\\']) || name.contains(std::path::is_separator) {
+        Err(format!("Invalid project name: {:?} (it can't be empty or contain dots or path separators)", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// The task file in `dir` for the given project, or the default tasks.json (tasks.db for SQLite). 
+fn db_path(dir: &Path, project: Option<&str>, backend: Backend) -> PathBuf {
+    match (project, backend) {
+        (Some(project), _) => dir.join(format!("tasks.{}.{}", project, backend.extension())),
+        (None, Backend::Json) => dir.join(FILE_PATH),
+        (None, _) => dir.join(format!("tasks.{}", backend.extension()))
+    }
+}
+
+/// Names of the projects that have a task file in `dir`, sorted. 
+fn list_projects(dir: &Path) -> Result<Vec<String>, TaskError> {
+    let path = dir.to_string_lossy();
+    let entries = std::fs::read_dir(dir).map_err(|error| TaskError::io(&path, error))?;
+    let mut projects = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|error| TaskError::io(&path, error))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let project = file_name.strip_prefix("tasks.").and_then(|name| name.strip_suffix(".json").or_else(|| name.strip_suffix(".db")));
+        if let Some(project) = project.filter(|project| validate_project_name(project).is_ok()) {
+            projects.push(project.to_string());
+        }
+    }
+    projects.sort();
+    projects.dedup();
+    Ok(projects)
+} 
+
+/// Names a task for explanations, e.g. `task 3 ('Buy milk')`. 
+fn describe_task(tasks: &[Task], id: u32) -> Option<String> {
+    tasks.iter().find(|task| task.id == id).map(|task| format!("task {} ('{}')", task.id, task.description))
+}
+
+/// Describes in plain English what the command will do to the given tasks, one sentence per affected task. 
+fn explain(command: &Command, tasks: &[Task]) -> String {
+    let missing = |id: u32| format!("Task {} doesn't exist, so nothing will happen to it.", id);
+    let for_task = |id: u32, action: &dyn Fn(String) -> String| describe_task(tasks, id).map_or_else(|| missing(id), action);
+    let sentences: Vec<String> = match command {
+        Command::Add(description, options) => {
+            let mut sentence = format!("This will add a new task '{}' (ID: {})", description, Task::next_id(tasks));
+            if let Some(parent) = options.parent {
+                match describe_task(tasks, parent) {
+                    Some(parent) => sentence.push_str(&format!(" as a subtask of {}", parent)),
+                    None => return format!("Task {} doesn't exist, so the subtask can't be added.", parent)
+                }
+            }
+            if let Some(recurrence) = options.recurrence {
+                sentence.push_str(&format!(", repeating {}", recurrence));
+            }
+            if let Some(status) = options.status {
+                sentence.push_str(&format!(", with the status {}", status));
+            }
+            if let Some(priority) = options.priority {
+                sentence.push_str(&format!(", with priority {}", priority));
+            }
+            if let Some(due_at) = &options.due_at {
+                sentence.push_str(&format!(", due {}", timestamp::format(due_at, &Local)));
+            }
+            if !options.tags.is_empty() {
+                sentence.push_str(&format!(", tagged {}", options.tags.join(", ")));
+            }
+            vec![sentence + "."]
+        },
+        Command::AddFromStdin(_) => vec![format!("This will add a task for each line read from stdin, starting at ID {}.", Task::next_id(tasks))],
+        Command::Update(changes, task) => match task.resolve(tasks) {
+            Ok(id) => vec![for_task(id, &|task| format!("This will update {}: {}.", task, changes.describe()))],
+            Err(error) => vec![error.to_string()]
+        },
+        Command::Delete(ids, subtasks) => match TaskRef::resolve_all(ids, tasks) {
+            Ok(ids) => ids
+            .iter()
+            .map(|id| for_task(*id, &|task| {
+                match (Task::descendants(tasks, *id).len(), subtasks) {
+                    (0, _) => format!("This will move {} to the trash.", task),
+                    (n, Subtasks::Cascade) => format!("This will move {} and its {} subtask(s) to the trash.", task, n),
+                    (_, Subtasks::Reparent) => format!("This will move {} to the trash, and its subtasks up a level.", task),
+                    (n, Subtasks::Refuse) => format!("This will refuse to delete {}, because it has {} subtask(s).", task, n)
+                }
+            }))
+            .collect(),
+            Err(error) => vec![error.to_string()]
+        },
+        Command::DeleteDone => {
+            let (ids, kept) = done_tasks(tasks);
+            let mut sentence = format!("This will move the {} done task(s) to the trash", ids.len());
+            if !kept.is_empty() {
+                sentence.push_str(&format!(", keeping {} because of subtasks that are not done", join_ids(&kept)));
+            }
+            vec![sentence + "."]
+        },
+        Command::Mark(status, ids) => match TaskRef::resolve_all(ids, tasks) {
+            Ok(ids) => ids
+            .iter()
+            .map(|id| for_task(*id, &|task| format!("This will mark {} as {}.", task, status)))
+            .collect(),
+            Err(error) => vec![error.to_string()]
+        },
+        Command::List(filter, source, output) => {
+            let verb = match output {
+                ListOutput::Tasks => "list",
+                ListOutput::Count => "count"
+            };
+            let mut sentence = match source {
+                ListSource::Tasks => format!("This will {} all tasks", verb),
+                ListSource::Archive => format!("This will {} all archived tasks", verb),
+                ListSource::Trash => format!("This will {} all tasks in the trash", verb)
+            };
+            match filter.status {
+                Some(StatusFilter::OPEN) => sentence.push_str(" that are not done"),
+                Some(StatusFilter::ALL) | None => {}, 
+                Some(status) => sentence.push_str(&format!(" with the status {}", status))
+            }
+            if let Some(priority) = filter.min_priority {
+                sentence.push_str(&format!(" with priority {} or higher", priority));
+            }
+            if let Some(period) = filter.created {
+                sentence.push_str(&format!(" created {}", period));
+            }
+            if let Some(period) = filter.updated {
+                sentence.push_str(&format!(" updated {}", period));
+            }
+            if let Some(period) = filter.completed {
+                sentence.push_str(&format!(" completed {}", period));
+            }
+            if let Some(tag) = &filter.tag {
+                sentence.push_str(&format!(" tagged {}", tag));
+            }
+            if let Some(days) = filter.stale {
+                sentence.push_str(&format!(" not updated in the last {} day(s)", days));
+            }
+            if let Some(pattern) = &filter.description {
+                sentence.push_str(&format!(" whose description matches '{}'", pattern.text));
+            }
+            if let (Some(limit), ListOutput::Tasks) = (filter.limit, output) {
+                sentence.push_str(&format!(", {} to a page, showing page {}", limit, filter.page));
+            }
+            if let (Some(_), ListOutput::Tasks) = (&filter.template, output) {
+                sentence.push_str(", a line each from the template");
+            }
+            if let (true, ListOutput::Tasks) = (filter.plain, output) {
+                sentence.push_str(", as key=value lines");
+            }
+            vec![sentence + "."]
+        },
+        Command::SetRepeat(recurrence, id) => {
+            vec![for_task(*id, &|task| format!("This will make {} repeat {}.", task, recurrence))]
+        },
+        Command::Progress(100, id) => vec![for_task(*id, &|task| format!("This will set the progress of {} to 100% and mark it as done.", task))],
+        Command::Progress(percent, id) => vec![for_task(*id, &|task| format!("This will set the progress of {} to {}%.", task, percent))],
+        Command::Prioritize(priority, id) => {
+            vec![for_task(*id, &|task| format!("This will set the priority of {} to {}.", task, priority))]
+        },
+        Command::Assign(Some(name), id) => vec![for_task(*id, &|task| format!("This will assign {} to {}.", task, name))],
+        Command::Assign(None, id) => vec![for_task(*id, &|task| format!("This will remove the assignee of {}.", task))],
+        Command::Estimate(estimate, id) => vec![for_task(*id, &|task| format!("This will set the estimate of {} to {}.", task, estimate))],
+        Command::Archive(cutoff) => {
+            let count = tasks.iter().filter(|task| is_archivable(task, *cutoff)).count();
+            match cutoff {
+                Some(cutoff) => vec![format!("This will move {} done task(s) completed before {} to the archive.", count, timestamp::format(cutoff, &Local))],
+                None => vec![format!("This will move {} done task(s) to the archive.", count)]
+            }
+        },
+        Command::Due(due_at, id) => {
+            vec![for_task(*id, &|task| format!("This will set the due date of {} to {}.", task, timestamp::format(due_at, &Local)))]
+        },
+        Command::Remind(minutes_before, id) => {
+            vec![for_task(*id, &|task| format!("This will remind you about {} {} before it is due.", task, format_offset(*minutes_before)))]
+        },
+        Command::Prune(days) => {
+            let Ok(cutoff) = days_before(Utc::now(), *days) else {
+                return format!("{} day(s) is too far back, so nothing will be pruned.", days);
+            };
+            let count = tasks.iter().filter(|task| is_prunable(task, cutoff)).count();
+            vec![format!("This will delete {} done task(s) completed more than {} day(s) ago.", count, days)]
+        },
+        Command::Restore(id) => vec![format!("This will move task {} back from the trash.", id)],
+        Command::Backup(keep) => {
+            let mut sentence = format!("This will write the {} task(s) to a new backup", tasks.len());
+            if let Some(keep) = keep {
+                sentence.push_str(&format!(" and delete all but the newest {} backup(s)", keep));
+            }
+            vec![sentence + "."]
+        },
+        Command::Backups => vec!["This will list the backups of the task file, newest first.".to_string()],
+        Command::RestoreBackup(backup) => vec![format!("This will back up the {} task(s), then replace them with the tasks in backup {}.", tasks.len(), backup)],
+        Command::Merge(path) => vec![format!("This will merge the tasks in {} into the {} task(s) here, keeping the newer version of tasks changed in both.", path, tasks.len())],
+        Command::ImportTodoTxt(path) => vec![format!("This will add the tasks in {} (in todo.txt format), starting at ID {}.", path, Task::next_id(tasks))],
+        Command::ExportIcs(path) => {
+            let count = tasks.iter().filter(|task| task.due_at.is_some()).count();
+            vec![format!("This will write the {} task(s) with a due date as iCalendar to-dos to {}.", count, path.as_deref().unwrap_or("stdout"))]
+        },
+        Command::Interactive => vec!["This will read commands from stdin and save the tasks at the end.".to_string()],
+        Command::Watch(filter) => {
+            let list = explain(&Command::List(filter.clone(), ListSource::Tasks, ListOutput::Tasks), tasks);
+            vec![format!("{} It will list them again whenever the task file changes, until interrupted.", list)]
+        },
+        Command::EmptyTrash => vec!["This will permanently delete the tasks in the trash.".to_string()],
+        Command::Compact => vec!["This will write the changes kept in the journals back into the task, archive and trash files.".to_string()],
+        Command::SendReminders => vec!["This will print the reminders that are due.".to_string()],
+        Command::Start(id) => vec![for_task(*id, &|task| format!("This will start the timer of {}.", task))],
+        Command::Stop(id) => vec![for_task(*id, &|task| format!("This will stop the timer of {}.", task))],
+        Command::Validate => vec!["This will check every task against the limits.".to_string()],
+        Command::ResetIds => vec![format!("This will number the {} task(s) from 1 in the order they are in.", tasks.len())],
+        Command::Stats => vec!["This will count the tasks by status and add up the estimates of those that are not done.".to_string()],
+        Command::Report(range) => {
+            let (from, to) = range.days(Local::now().date_naive());
+            vec![format!("This will count the tasks completed and added each week from {} to {}, including archived tasks.", from, to)]
+        },
+        Command::Projects => vec!["This will list the projects that have a task file.".to_string()],
+        Command::Path => vec!["This will print the path of the task file.".to_string()],
+        Command::Note(_, id) => vec![for_task(*id, &|task| format!("This will add a note to {}.", task))],
+        Command::Notes(id) => vec![for_task(*id, &|task| format!("This will show the notes of {}.", task))],
+        Command::History(id) => vec![for_task(*id, &|task| format!("This will show the history of {}.", task))],
+        Command::Repair => vec!["This will move the task file aside if it is not valid and start a new, empty task list.".to_string()],
+        Command::Migrate => vec![format!("This will rewrite the task file in version {} of the file layout.", FORMAT_VERSION)],
+        Command::Doctor => vec!["This will check the task file for tasks sharing an ID and offer to give them new IDs.".to_string()],
+        Command::Help => vec!["This will print the list of commands.".to_string()],
+        Command::Completions(shell) => vec![format!("This will print the completion script for {}.", shell)],
+        Command::Show(task) => match task.resolve(tasks) {
+            Ok(id) => vec![for_task(id, &|task| format!("This will show {}.", task))],
+            Err(error) => vec![error.to_string()]
+        },
+        Command::Advance(id, wrap) => vec![for_task(*id, &|task| {
+            let status = tasks.iter().find(|task| task.id == *id).map(|task| task.status).unwrap_or(Status::Todo);
+            match status.next(*wrap) {
+                Some(next) => format!("This will mark {} as {}.", task, next),
+                None => format!("This will fail, since {} is already done.", task)
+            }
+        })],
+        Command::Toggle(id) => vec![for_task(*id, &|task| {
+            let status = tasks.iter().find(|task| task.id == *id).map(|task| task.status).unwrap_or(Status::Todo);
+            format!("This will mark {} as {}.", task, status.toggled())
+        })],
+        Command::Block(id, on) => vec![for_task(*id, &|task| format!("This will make {} depend on task {}.", task, on))],
+        Command::Unblock(id, on) => vec![for_task(*id, &|task| format!("This will remove the dependency of {} on task {}.", task, on))],
+        Command::Reopen(id) => vec![for_task(*id, &|task| format!("This will mark {} as todo again, if it is done.", task))],
+        Command::Copy(id, _) => vec![for_task(*id, &|task| format!("This will add a copy of {} (ID: {}).", task, Task::next_id(tasks)))],
+        Command::EditDescription(id) => vec![for_task(*id, &|task| format!("This will open the description of {} in {}.", task, editor::from_env()))],
+        Command::ClearNotes(id) => vec![for_task(*id, &|task| format!("This will remove all notes of {}.", task))],
+        Command::Move(id, position) => vec![for_task(*id, &|task| format!("This will move {} to position {} in the list.", task, position))],
+    };
+    sentences.join("\n")
+}
+
+/// Flags that apply to every command and may appear anywhere on the command line. 
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Print tasks and errors as JSON instead of text. 
+    pub json: bool, 
+    /// Print the tasks in `list` as one compact JSON object per line. 
+    pub ndjson: bool, 
+    /// Describe what the command will do before doing it. 
+    pub explain: bool, 
+    /// Go through the command without writing anything to the database. 
+    pub dry_run: bool, 
+    /// Put the output of `list` on the system clipboard instead of printing it. 
+    pub clipboard: bool, 
+    /// Longest description `add` and `update` accept, in characters. 
+    pub max_description_len: usize, 
+    /// Shorten descriptions over the limit instead of refusing them. 
+    pub truncate: bool, 
+    /// Work on tasks.<project>.json instead of tasks.json. 
+    pub project: Option<String>, 
+    /// How to report what commands did: `--quiet` prints nothing but the ID of a new task (errors are still printed, to
+    /// stderr) and `--porcelain` prints stable lines for scripts. 
+    pub output: OutputMode, 
+    /// Use this task file instead of the one in the current directory. 
+    pub file: Option<String>, 
+    /// Move archived tasks to this file instead of the one next to the task file. 
+    pub archive_file: Option<String>, 
+    /// Keep backups in this directory instead of `backups` next to the task file, from `--backup-dir` or the config file. 
+    pub backup_dir: Option<String>, 
+    /// Don't ask for confirmation before deleting. 
+    pub yes: bool, 
+    /// Store the tasks with this backend instead of picking it from the file extension. 
+    pub backend: Option<Backend>, 
+    /// Show the exact times tasks were created and updated in `list`, instead of e.g. "3 days ago". 
+    pub absolute: bool, 
+    /// Show times as RFC 3339 in UTC, e.g. 2025-01-12T14:02:28Z. 
+    pub iso: bool, 
+    /// Print text even if the config file asks for JSON. 
+    pub text: bool, 
+    /// Status `list` shows when none is given, from the config file. 
+    pub(crate) default_status: Option<StatusFilter>, 
+    /// Print what the command works on (file, task counts, parsed command) to stderr. 
+    pub verbose: bool, 
+    /// Mark tasks as done even if tasks they depend on are not. 
+    pub force: bool, 
+    /// Fail on problems that are otherwise only reported, like ids that don't exist or an invalid config file. 
+    pub strict: bool, 
+    /// Refuse to mark tasks as done while some of their subtasks are not, from the config file. 
+    pub(crate) require_subtasks_done: bool, 
+    /// How to lay out JSON task files, from `--compact` or the config file. 
+    pub(crate) json_style: JsonStyle, 
+    /// How `run` picked the task file; unset when it was given directly. 
+    pub(crate) path_source: Option<PathSource>, 
+    /// Append changes to a journal next to the task file instead of rewriting it, from `--journal` or the config file. 
+    pub journal: bool
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { json: false, ndjson: false, explain: false, dry_run: false, clipboard: false, max_description_len: DEFAULT_MAX_DESCRIPTION_LEN, truncate: false, project: None, output: OutputMode::Normal, file: None, archive_file: None, backup_dir: None, yes: false, backend: None, absolute: false, iso: false, text: false, default_status: None, verbose: false, force: false, strict: false, require_subtasks_done: false, json_style: JsonStyle::Pretty, path_source: None, journal: false }
+    }
+}
+
+impl Options {
+    /// Removes the global flags from the args and returns them. 
+    pub fn extract(args: &mut Vec<String>) -> Result<Options, TaskError> {
+        let mut options = Options::default();
+        // Nothing after `--` is a flag; it is put back untouched for the command to read 
+        let literal = match args.iter().position(|arg| arg == "--") {
+            Some(index) => args.split_off(index),
+            None => vec![]
+        };
+        if let Some(project) = take_value(args, "--project")? {
+            validate_project_name(&project).map_err(TaskError::Usage)?;
+            options.project = Some(project);
+        }
+        options.file = take_value(args, "--file")?;
+        options.archive_file = take_value(args, "--archive-file")?;
+        options.backup_dir = take_value(args, "--backup-dir")?;
+        if let Some(backend) = take_value(args, "--backend")? {
+            options.backend = Some(backend.parse().map_err(TaskError::Usage)?);
+        }
+        if options.file.is_some() && options.project.is_some() {
+            return Err(TaskError::Usage("--file and --project can't be used together".to_string()));
+        }
+        let mut error = None;
+        args.retain(|arg| match arg.as_str() {
+            "--json" => {
+                options.json = true;
+                false
+            },
+            "--ndjson" => {
+                options.ndjson = true;
+                false
+            },
+            "--explain" => {
+                options.explain = true;
+                false
+            },
+            "--dry-run" => {
+                options.dry_run = true;
+                false
+            },
+            "--clipboard" => {
+                options.clipboard = true;
+                false
+            },
+            "--yes" | "-y" => {
+                options.yes = true;
+                false
+            },
+            "--quiet" | "-q" | "--porcelain" => {
+                let mode = if arg == "--porcelain" { OutputMode::Porcelain } else { OutputMode::Quiet };
+                if ![OutputMode::Normal, mode].contains(&options.output) {
+                    error = Some(TaskError::Usage("--quiet and --porcelain can't be used together".to_string()));
+                }
+                options.output = mode;
+                false
+            },
+            "--absolute" => {
+                options.absolute = true;
+                false
+            },
+            "--iso" => {
+                options.iso = true;
+                false
+            },
+            // Relative times used to be opt-in; they are the default now 
+            "--relative" => false,
+            "--text" => {
+                options.text = true;
+                false
+            },
+            "--truncate" => {
+                options.truncate = true;
+                false
+            },
+            "--verbose" => {
+                options.verbose = true;
+                false
+            },
+            "--force" => {
+                options.force = true;
+                false
+            },
+            "--strict" => {
+                options.strict = true;
+                false
+            },
+            "--compact" => {
+                options.json_style = JsonStyle::Compact;
+                false
+            },
+            "--journal" => {
+                options.journal = true;
+                false
+            },
+            _ => match arg.strip_prefix("--max-description-len=") {
+                Some(value) => {
+                    match value.parse() {
+                        Ok(limit) if limit > 0 => options.max_description_len = limit,
+                        _ => error = Some(TaskError::Usage(format!("Invalid description length limit: {}", value)))
+                    }
+                    false
+                },
+                None => true
+            }
+        });
+        args.extend(literal);
+        match error {
+            Some(error) => Err(error),
+            None => Ok(options)
+        }
+    }
+}
+
+/// Removes a flag and the value following it from the args, returning the value. 
+fn take_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, TaskError> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        },
+        Some(_) => Err(TaskError::Usage("Not enough arguments".to_string())),
+        None => Ok(None)
+    }
+}
+
+/// Turns the command line (from `env::args_os`) into strings, refusing arguments that are not valid UTF-8. 
+pub fn utf8_args(args: impl IntoIterator<Item = OsString>) -> Result<Vec<String>, TaskError> {
+    // Rather than guessing at them: a description would otherwise be saved with replacement characters in it
+    args.into_iter()
+        .enumerate()
+        .map(|(index, arg)| arg.into_string().map_err(|arg| {
+            TaskError::Usage(format!("Argument {} is not valid UTF-8: {}", index, arg.to_string_lossy()))
+        }))
+        .collect()
+}
+
+/// Runs the command given by args (as returned by `env::args`, minus the global flags) against tasks.json, the
+/// task file of the selected project, or the file given with `--file`. Options not given on the command line are
+/// taken from the config file next to the task file, if there is one. 
+pub fn run(args: Vec<String>, options: &Options) -> Result<(), TaskError> {
+    let env_file = std::env::var(FILE_ENV_VAR).ok();
+    let data_dir = dirs::data_dir().map(|dir| dir.join(DATA_DIR_NAME));
+    let backend = options.backend.unwrap_or(Backend::Json);
+    let (file_path, source) = resolve_path(options.file.as_deref(), env_file.as_deref(), Path::new(""), data_dir.as_deref(), options.project.as_deref(), backend);
+    let mut options = options.clone();
+    options.path_source = Some(source);
+    let dir = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if source == PathSource::DataDir && !options.dry_run {
+        std::fs::create_dir_all(dir).map_err(|error| TaskError::io(&dir.to_string_lossy(), error))?;
+    }
+    config::load(dir, &options)?.apply(&mut options);
+    run_at(&file_path.to_string_lossy(), args, &options)
+}
+
+/// Saves the tasks, unless this is a dry run. 
+fn save(storage: &mut dyn Storage, tasks: &[Task], options: &Options) -> Result<(), TaskError> {
+    if options.dry_run {
+        Ok(())
+    } else {
+        storage.save(tasks)
+    }
+}
+
+/// Prints a diagnostic line to stderr with `--verbose`, leaving stdout as it is. 
+fn verbose(options: &Options, message: &str) {
+    // Nothing useful can be done if stderr is gone
+    let _ = verbose_to(options, message, &mut io::stderr());
+}
+
+fn verbose_to(options: &Options, message: &str, out: &mut dyn Write) -> io::Result<()> {
+    if options.verbose {
+        writeln!(out, "[verbose] {}", message)
+    } else {
+        Ok(())
+    }
+}
+
+/// The lines `--verbose` prints before running a command. 
+fn describe_run(file_path: &str, loaded: usize, command: &Command) -> Vec<String> {
+    let resolved = std::path::absolute(file_path).unwrap_or_else(|_| PathBuf::from(file_path));
+    vec![format!("task file: {}", resolved.display()), format!("loaded {} task(s)", loaded), format!("command: {:?}", command)]
+}
+
+fn run_at(file_path: &str, args: Vec<String>, options: &Options) -> Result<(), TaskError> {
+    let parsed_args = parse_args(args).map_err(TaskError::Usage)?;
+    if run_without_tasks(&parsed_args, file_path, options)? {
+        return Ok(());
+    }
+    let backend = options.backend.unwrap_or_else(|| Backend::for_path(file_path));
+    let archive_path = options.archive_file.clone().unwrap_or_else(|| archive_path(file_path));
+    let mut stores = Stores {
+        tasks: storage::open(backend, file_path, options.json_style, options.journal), 
+        archive: storage::open(backend, &archive_path, options.json_style, options.journal), 
+        trash: storage::open_trash(backend, &trash_path(file_path), options.json_style, options.journal)
+    };
+    // Held until the command is done, so that concurrent commands can't overwrite each other's changes
+    let _lock = lock::Lock::acquire(file_path, lock::ATTEMPTS, lock::RETRY_DELAY)?;
+    let stdin = io::stdin();
+    let mut input = Input { reader: &mut stdin.lock(), ask: stdin.is_terminal() };
+    // These work on the file itself: the point of repairing is that the tasks can't be loaded 
+    if let Command::Repair | Command::Migrate | Command::Doctor = parsed_args {
+        if options.explain {
+            println!("{}", explain(&parsed_args, &[]));
+        }
+        return match parsed_args {
+            Command::Repair => repair(file_path, backend, options, Utc::now()),
+            Command::Migrate => migrate(file_path, backend, stores.tasks.as_mut(), options),
+            _ => doctor(file_path, backend, stores.tasks.as_mut(), &mut input, options)
+        };
+    }
+    if let Command::Interactive = parsed_args {
+        return interactive(&mut input, file_path, stores, options);
+    }
+    if let Command::Watch(filter) = parsed_args {
+        drop(_lock);
+        if options.explain {
+            println!("{}", explain(&Command::Watch(filter.clone()), &[]));
+        }
+        return watch(filter, file_path, stores.tasks.as_mut(), options);
+    }
+    execute(parsed_args, file_path, &mut stores, &mut input, options)
+}
+
+/// Runs the commands that don't touch the tasks, which must not create a task file. Returns whether the command was
+/// one of them. 
+fn run_without_tasks(command: &Command, file_path: &str, options: &Options) -> Result<bool, TaskError> {
+    match command {
+        Command::Help => print!("{}", help::usage()),
+        Command::Path => {
+            let path = std::path::absolute(file_path).map_err(|error| TaskError::io(file_path, error))?;
+            match options.path_source {
+                Some(source) => println!("{} ({})", path.display(), source), 
+                None => println!("{}", path.display())
+            }
+        },
+        Command::Completions(shell) => print!("{}", completions::script(*shell)),
+        Command::Backups => {
+            let dir = backup::dir(file_path, options.backup_dir.as_deref());
+            let backups = backup::list(&dir, file_path)?;
+            if backups.is_empty() {
+                println!("No backups found in {}.", dir.display());
+            }
+            for (index, path) in backups.iter().enumerate() {
+                println!("{}. {}", index + 1, path.display());
+            }
+        },
+        Command::Projects => {
+            let dir = Path::new(file_path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let projects = list_projects(dir)?;
+            if projects.is_empty() {
+                println!("No projects found.");
+            }
+            for project in projects {
+                println!("{}", project);
+            }
+        },
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+/// The task list along with its archive and trash. 
+struct Stores {
+    tasks: Box<dyn Storage>, 
+    archive: Box<dyn Storage>, 
+    trash: Box<dyn Storage>
+}
+
+impl Stores {
+    /// Writes out any changes held back in memory, the archive and the trash before the tasks moved there. 
+    fn flush(&mut self) -> Result<(), TaskError> {
+        self.archive.flush()?;
+        self.trash.flush()?;
+        self.tasks.flush()
+    }
+}
+
+/// Runs a command on the tasks, once the task file is locked. 
+fn execute(mut parsed_args: Command, file_path: &str, stores: &mut Stores, input: &mut Input, options: &Options) -> Result<(), TaskError> {
+    let Stores { tasks: storage, archive, trash } = stores;
+    // A dry run must not even create the database 
+    let mut tasks = if options.dry_run && !storage.exists() {
+        vec![]
+    } else {
+        storage.load()?
+    }; 
+    check_unique_ids(file_path, &tasks)?;
+    Task::sort(&mut tasks);
+    if let Command::List(filter, source, _) = &mut parsed_args {
+        filter.status = filter.status.or(options.default_status);
+        // Done tasks are left out of the task list unless asked for; the archive and the trash show everything
+        if *source == ListSource::Tasks {
+            filter.status = filter.status.or(Some(StatusFilter::OPEN));
+        }
+    }
+    if options.verbose {
+        for line in describe_run(file_path, tasks.len(), &parsed_args) {
+            verbose(options, &line);
+        }
+    }
+    if options.explain {
+        println!("{}", explain(&parsed_args, &tasks));
+    }
+    match parsed_args {
+        Command::List(filter, source, output) => {
+            let tasks = match source {
+                ListSource::Tasks => tasks,
+                ListSource::Archive => read_optional(archive.as_mut())?,
+                ListSource::Trash => read_optional(trash.as_mut())?
+            };
+            match output {
+                ListOutput::Tasks => list_tasks(&filter, tasks, options)?,
+                // Just the number, whatever the output format, so that scripts can use it as it is
+                ListOutput::Count => println!("{}", render_list(&filter, tasks, options)?.1)
+            }
+        }, 
+        Command::Mark(status, ids) => {
+            let ids = TaskRef::resolve_all(&ids, &tasks)?;
+            // Nothing has been changed yet, so a refusal leaves every task as it was
+            check_unblocked(&tasks, &ids, status, options)?;
+            let mut updated = vec![];
+            let mut respawned = vec![];
+            let mut not_found = vec![];
+            for id in ids {
+                if tasks.iter().any(|task| task.id == id) {
+                    respawned.extend(set_status(&mut tasks, id, status));
+                    updated.push(id);
+                } else {
+                    not_found.push(id);
+                }
+            }
+            // With --strict, nothing is saved if any of the ids is missing
+            report_not_found(&not_found, options)?;
+            if !updated.is_empty() {
+                save(storage.as_mut(), &tasks, options)?;
+            }
+            for id in updated {
+                report(options, Outcome::marked(id, status, format!("Successfully updated task (ID: {}).", id)));
+            }
+            for next_id in respawned {
+                report(options, Outcome::respawned(next_id));
+            }
+        }, 
+        Command::Delete(ids, subtasks) => {
+            let ids = TaskRef::resolve_all(&ids, &tasks)?;
+            for id in delete_tasks(tasks, ids, subtasks, storage.as_mut(), trash.as_mut(), input, options)? {
+                report(options, Outcome::deleted(id));
+            }
+        }, 
+        Command::DeleteDone => {
+            let (ids, kept) = done_tasks(&tasks);
+            for id in kept {
+                let message = format!("Task (ID: {}) is done but has subtasks that are not, so it was kept.", id);
+                tolerate(options, TaskError::PreconditionFailed { id, message: message.clone() }, format!("Warning: {}", message))?;
+            }
+            if ids.is_empty() {
+                report(options, "There are no done tasks to delete.".to_string());
+            } else {
+                // Every subtask of these is done as well, so cascading only takes done tasks with it
+                let deleted = delete_tasks(tasks, ids, Subtasks::Cascade, storage.as_mut(), trash.as_mut(), input, options)?;
+                if !deleted.is_empty() {
+                    report(options, Outcome::each(format!("Successfully deleted {} done task(s).", deleted.len()), "deleted", deleted));
+                }
+            }
+        }, 
+        Command::Update(mut changes, task) => {
+            let id = task.resolve(&tasks)?;
+            changes.description = changes.description.map(|description| check_description(description, options.max_description_len, options.truncate)).transpose()?;
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                let completes = changes.status == Some(Status::Done) && task.status != Status::Done;
+                if let Some(status) = changes.status {
+                    check_unblocked(&tasks, &[id], status, options)?;
+                }
+                let task = tasks.iter_mut().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+                changes.apply(task, Utc::now())?;
+                let respawned = if completes { respawn(&mut tasks, id, Utc::now()) } else { None };
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+                if let Some(next_id) = respawned {
+                    report(options, Outcome::respawned(next_id));
+                }
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Progress(percent, id) => {
+            if tasks.iter().any(|task| task.id == id) {
+                if percent == 100 {
+                    check_unblocked(&tasks, &[id], Status::Done, options)?;
+                }
+                let task = tasks.iter_mut().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+                task.update_progress(percent);
+                // A task that is all the way there is done
+                let respawned = if percent == 100 { set_status(&mut tasks, id, Status::Done) } else { None };
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+                if let Some(next_id) = respawned {
+                    report(options, Outcome::respawned(next_id));
+                }
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Prioritize(priority, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.update_priority(priority);
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Assign(assignee, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.update_assignee(assignee);
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Estimate(estimate, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.update_estimate(estimate);
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::SetRepeat(recurrence, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.update_recurrence(recurrence);
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Due(due_at, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.update_due(due_at, Utc::now());
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Remind(minutes_before, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.add_reminder(minutes_before)?;
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Note(text, id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.add_note(text, Utc::now());
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::new(format!("Successfully added a note to task (ID: {}).", id), format!("updated {}", id)));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Show(task) => print!("{}", render_show(task.resolve(&tasks)?, &tasks, options)?), 
+        Command::Advance(id, wrap) => {
+            let task = tasks.iter().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+            let status = task.status;
+            let Some(next) = status.next(wrap) else {
+                let message = format!("Task (ID: {}) is already done; add --wrap to start it over as todo.", id);
+                return Err(TaskError::PreconditionFailed { id, message });
+            };
+            check_unblocked(&tasks, &[id], next, options)?;
+            let respawned = set_status(&mut tasks, id, next);
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::marked(id, next, format!("Successfully updated task (ID: {}) from {} to {}.", id, status, next)));
+            if let Some(next_id) = respawned {
+                report(options, Outcome::respawned(next_id));
+            }
+        }, 
+        Command::Toggle(id) | Command::Reopen(id) => {
+            let task = tasks.iter().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+            let status = task.status;
+            if matches!(parsed_args, Command::Reopen(_)) && status != Status::Done {
+                let message = format!("Task (ID: {}) is not done, so there is nothing to reopen.", id);
+                return Err(TaskError::PreconditionFailed { id, message });
+            }
+            let next = status.toggled();
+            check_unblocked(&tasks, &[id], next, options)?;
+            let respawned = set_status(&mut tasks, id, next);
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::marked(id, next, format!("Successfully updated task (ID: {}) from {} to {}.", id, status, next)));
+            if let Some(next_id) = respawned {
+                report(options, Outcome::respawned(next_id));
+            }
+        }, 
+        Command::Block(id, on) => {
+            add_dependency(&mut tasks, id, on)?;
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::new(format!("Task (ID: {}) now depends on task (ID: {}).", id, on), format!("blocked {} {}", id, on)));
+        }, 
+        Command::Unblock(id, on) => {
+            let task = tasks.iter_mut().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+            if !task.depends_on.contains(&on) {
+                let message = format!("Task (ID: {}) does not depend on task (ID: {}).", id, on);
+                return Err(TaskError::PreconditionFailed { id, message });
+            }
+            task.depends_on.retain(|other| *other != on);
+            task.updated_at = Some(Utc::now());
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::new(format!("Task (ID: {}) no longer depends on task (ID: {}).", id, on), format!("unblocked {} {}", id, on)));
+        }, 
+        Command::Copy(id, description) => {
+            let original = tasks.iter().find(|task| task.id == id).ok_or(TaskError::NotFound { id })?;
+            let description = description.map(|description| check_description(description, options.max_description_len, options.truncate)).transpose()?;
+            let copy = original.copy(Task::next_id(&tasks), description.as_deref())?;
+            let new_id = copy.id;
+            tasks.push(copy);
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::new(format!("Successfully copied task (ID: {}) to task (ID: {}).", id, new_id), format!("copied {} {}", id, new_id)));
+        }, 
+        Command::EditDescription(id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                let edited = editor::edit(&editor::from_env(), &task.description, id)?;
+                task.update_description(&check_description(edited, options.max_description_len, options.truncate)?)?;
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::updated(id));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Move(id, position) => {
+            if let Some(position) = Task::move_to(&mut tasks, id, position) {
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::new(format!("Successfully moved task (ID: {}) to position {}.", id, position), format!("moved {} {}", id, position)));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::ClearNotes(id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.clear_notes(Utc::now());
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::new(format!("Successfully cleared the notes of task (ID: {}).", id), format!("updated {}", id)));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Notes(id) => {
+            if let Some(task) = tasks.iter().find(|task| task.id == id) {
+                if task.notes.is_empty() {
+                    println!("Task (ID: {}) has no notes.", id);
+                } else {
+                    println!("{}", format_notes(&task.notes, TimeFormat::for_details(options)));
+                }
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::History(id) => {
+            if let Some(task) = tasks.iter().find(|task| task.id == id) {
+                if task.history.is_empty() {
+                    println!("Task (ID: {}) has no recorded history.", id);
+                } else {
+                    println!("{}", format_history(&task.history, TimeFormat::for_details(options)));
+                }
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Start(id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                task.start_timer(Utc::now())?;
+                save(storage.as_mut(), &tasks, options)?;
+                report(options, Outcome::new(format!("Successfully started task (ID: {}).", id), format!("started {}", id)));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Stop(id) => {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+                let elapsed = task.stop_timer(Utc::now())?;
+                let total = task.time_spent;
+                save(storage.as_mut(), &tasks, options)?;
+                let message = format!("Successfully stopped task (ID: {}) after {} ({} in total).", id, format_duration(elapsed), format_duration(total));
+                report(options, Outcome::new(message, format!("stopped {}", id)));
+            } else {
+                not_found(id, options)?
+            }
+        }, 
+        Command::Help | Command::Completions(_) | Command::Projects | Command::Path | Command::Backups | Command::Repair | Command::Migrate | Command::Doctor | Command::Interactive | Command::Watch(_) => {
+            unreachable!("handled before loading the tasks")
+        }, 
+        Command::Report(range) => {
+            // Archiving done tasks shouldn't take them out of the weeks they were done in
+            let mut all = tasks;
+            all.extend(read_optional(archive.as_mut())?);
+            let (from, to) = range.days(Local::now().date_naive());
+            let report = report::weekly(&all, from, to, &Local);
+            if options.json {
+                let json = serde_json::to_string_pretty(&report).map_err(|error| TaskError::json("<stdout>", error))?;
+                println!("{}", json);
+            } else {
+                print!("{}", report.render());
+            }
+        }, 
+        Command::Stats => {
+            let tasks: Vec<Task> = tasks.into_iter().filter(|task| !task.archived).collect();
+            let (estimate, unestimated) = outstanding_estimate(&tasks);
+            println!("{}", summarize(&tasks, tasks.len()));
+            println!("Estimated effort outstanding: {} ({} open task(s) without an estimate)", estimate, unestimated);
+        }, 
+        Command::ResetIds => {
+            if !options.yes && !options.dry_run {
+                return Err(TaskError::Usage("reset-ids gives tasks new IDs for good; run it with --yes (or see what it would do with --dry-run)".to_string()));
+            }
+            let renumbered = reset_ids(&mut tasks);
+            if renumbered.is_empty() {
+                report(options, format!("The IDs already run from 1 to {}.", tasks.len()));
+                return Ok(());
+            }
+            save(storage.as_mut(), &tasks, options)?;
+            for (old, new) in renumbered {
+                report(options, Outcome::new(format!("Task (ID: {}) is now task (ID: {}).", old, new), format!("renumbered {} {}", old, new)));
+            }
+        }, 
+        Command::Validate => {
+            let findings = long_descriptions(&tasks, options.max_description_len);
+            if findings.is_empty() {
+                println!("All tasks are valid.");
+            }
+            for (id, length) in findings {
+                println!("Task (ID: {}) has a description of {} characters, over the limit of {}.", id, length, options.max_description_len);
+            }
+        }, 
+        Command::SendReminders => {
+            let fired = sweep_reminders(&mut tasks, Utc::now());
+            if fired.is_empty() {
+                println!("No reminders due.");
+            } else {
+                save(storage.as_mut(), &tasks, options)?;
+                for task in tasks.iter().filter(|task| fired.contains(&task.id)) {
+                    let due_at = task.due_at.map(|due_at| timestamp::format(&due_at, &Local)).unwrap_or_default();
+                    println!("Reminder: task (ID: {}) \"{}\" is due {}.", task.id, task.description, due_at);
+                }
+            }
+        }, 
+        Command::Archive(cutoff) => {
+            let moved = take_archivable(&mut tasks, cutoff);
+            let count = moved.len();
+            let ids: Vec<u32> = moved.iter().map(|task| task.id).collect();
+            if count > 0 {
+                let mut archived = read_optional(archive.as_mut())?;
+                archived.extend(moved);
+                // The archive is written first: if saving the tasks fails, they end up in both files rather than neither
+                save(archive.as_mut(), &archived, options)?;
+                save(storage.as_mut(), &tasks, options)?;
+            }
+            report(options, Outcome::each(format!("Successfully archived {} task(s).", count), "archived", ids));
+        }, 
+        Command::Restore(id) => {
+            let mut in_trash = read_optional(trash.as_mut())?;
+            // The same id can be in the trash more than once; the task deleted last is the one restored
+            if let Some(index) = in_trash.iter().rposition(|task| task.id == id) {
+                let mut task = in_trash.remove(index);
+                if tasks.iter().any(|other| other.id == id) {
+                    if options.strict {
+                        let message = format!("Task (ID: {}) can't be restored with its ID, which is now used by another task.", id);
+                        return Err(TaskError::PreconditionFailed { id, message });
+                    }
+                    task.id = Task::next_id(&tasks);
+                }
+                let new_id = task.id;
+                tasks.push(task);
+                // The tasks are written first: if saving the trash fails, the task ends up in both files rather than neither
+                save(storage.as_mut(), &tasks, options)?;
+                save(trash.as_mut(), &in_trash, options)?;
+                let message = if new_id == id {
+                    format!("Successfully restored task (ID: {}).", id)
+                } else {
+                    format!("Successfully restored task (ID: {}) as task (ID: {}), since its ID is now used by another task.", id, new_id)
+                };
+                report(options, Outcome::new(message, format!("restored {} {}", id, new_id)));
+            } else {
+                tolerate(options, TaskError::NotFound { id }, "Error: ID not found in the trash.".to_string())?
+            }
+        }, 
+        Command::EmptyTrash => {
+            let in_trash = read_optional(trash.as_mut())?;
+            if in_trash.is_empty() {
+                report(options, "The trash is empty.".to_string());
+                return Ok(());
+            }
+            let question = format!("Permanently delete the {} task(s) in the trash?", in_trash.len());
+            if !input.confirm(&question, options)? {
+                report(options, "Nothing was deleted.".to_string());
+                return Ok(());
+            }
+            save(trash.as_mut(), &[], options)?;
+            report(options, Outcome::each(format!("Permanently deleted {} task(s) from the trash.", in_trash.len()), "purged", in_trash.iter().map(|task| task.id)));
+        },
+        Command::Backup(keep) => {
+            let path = back_up(file_path, &tasks, options, Utc::now())?;
+            report(options, format!("Backed up {} task(s) to {}.", tasks.len(), path.display()));
+            if let Some(keep) = keep {
+                let dir = backup::dir(file_path, options.backup_dir.as_deref());
+                // In a dry run the new backup isn't there to count
+                let keep = if options.dry_run { keep - 1 } else { keep };
+                for old in backup::list(&dir, file_path)?.into_iter().skip(keep) {
+                    if !options.dry_run {
+                        std::fs::remove_file(&old).map_err(|error| TaskError::io(&old.to_string_lossy(), error))?;
+                    }
+                    report(options, format!("Deleted old backup {}.", old.display()));
+                }
+            }
+        },
+        Command::RestoreBackup(target) => {
+            let dir = backup::dir(file_path, options.backup_dir.as_deref());
+            let source = backup::find(&target, &dir, file_path)?;
+            let source_path = source.to_string_lossy();
+            let backend = options.backend.unwrap_or_else(|| Backend::for_path(&source_path));
+            // The backup is read in full before anything is written, so one that can't be read leaves the tasks alone
+            let restored = storage::open(backend, &source_path, options.json_style, false).load()?;
+            check_unique_ids(&source_path, &restored)?;
+            let safety = back_up(file_path, &tasks, options, Utc::now())?;
+            save(storage.as_mut(), &restored, options)?;
+            report(options, format!("Restored {} task(s) from {}; the {} task(s) before were backed up to {}.", restored.len(), source.display(), tasks.len(), safety.display()));
+        },
+        Command::Compact => {
+            if options.dry_run {
+                report(options, "Would write the journals back into the task files.".to_string());
+                return Ok(());
+            }
+            let mut compacted = 0;
+            for store in [&mut *storage, &mut *archive, &mut *trash] {
+                if store.compact()? {
+                    compacted += 1;
+                }
+            }
+            report(options, match compacted {
+                0 => "There was no journal to compact.".to_string(),
+                count => format!("Compacted {} journal(s).", count)
+            });
+        }, 
+        Command::Prune(days) => {
+            let pruned = take_prunable(&mut tasks, days_before(Utc::now(), days)?);
+            if pruned.is_empty() {
+                report(options, format!("No done tasks older than {} day(s) to prune.", days));
+            } else {
+                let mut summary = String::new();
+                for task in &pruned {
+                    let _ = writeln!(summary, "Task (ID: {}): {}", task.id, task.description);
+                }
+                let question = format!("{}Delete these {} done task(s) for good?", summary, pruned.len());
+                if !input.confirm(&question, options)? {
+                    report(options, "Prune cancelled.".to_string());
+                    return Ok(());
+                }
+                save(storage.as_mut(), &tasks, options)?;
+                let ids: Vec<String> = pruned.iter().map(|task| task.id.to_string()).collect();
+                report(options, Outcome::each(format!("Successfully pruned {} task(s) (ID: {}).", pruned.len(), ids.join(", ")), "pruned", pruned.iter().map(|task| task.id)));
+            }
+        }, 
+        Command::Add(description, add_options) => {
+            if let Some(parent) = add_options.parent {
+                if !tasks.iter().any(|task| task.id == parent) {
+                    return Err(TaskError::NotFound { id: parent });
+                }
+            }
+            let description = check_description(description, options.max_description_len, options.truncate)?;
+            let id = Task::next_id(&tasks); 
+            tasks.push(add_options.new_task(id, &description)?); 
+            save(storage.as_mut(), &tasks, options)?;
+            report(options, Outcome::added(id));
+        }, 
+        Command::Merge(path) => {
+            if !Path::new(&path).is_file() {
+                return Err(TaskError::io(&path, io::Error::new(io::ErrorKind::NotFound, "no such task file")));
+            }
+            let backend = options.backend.unwrap_or_else(|| Backend::for_path(&path));
+            let mut other = storage::open(backend, &path, options.json_style, false).load()?;
+            check_unique_ids(&path, &other)?;
+            Task::sort(&mut other);
+            let merged = merge::merge(tasks, other);
+            if !merged.added.is_empty() || merged.conflicts.iter().any(|conflict| conflict.kept == merge::Side::Other) {
+                save(storage.as_mut(), &merged.tasks, options)?;
+            }
+            for (old, new) in &merged.renumbered {
+                report(options, format!("Task (ID: {}) in {} is a different task from task (ID: {}) here, so it was added as task (ID: {}).", old, path, old, new));
+            }
+            for conflict in &merged.conflicts {
+                match conflict.kept {
+                    merge::Side::Other => report(options, Outcome::new(format!("Conflict: task (ID: {}) was changed in both; kept the version in {}, changed last.", conflict.id, path), format!("updated {}", conflict.id))),
+                    merge::Side::Local => report(options, format!("Conflict: task (ID: {}) was changed in both; kept the version here, changed last.", conflict.id))
+                }
+            }
+            let updated = merged.conflicts.iter().filter(|conflict| conflict.kept == merge::Side::Other).count();
+            let message = format!("Merged {}: added {} task(s), updated {}, {} conflict(s).", path, merged.added.len(), updated, merged.conflicts.len());
+            report(options, Outcome::each(message, "added", merged.added));
+        }, 
+        Command::ImportTodoTxt(path) => {
+            let data = std::fs::read_to_string(&path).map_err(|error| TaskError::io(&path, error))?;
+            let (imported, skipped) = import_todotxt(&mut tasks, &data, options, Utc::now())?;
+            if !imported.is_empty() {
+                save(storage.as_mut(), &tasks, options)?;
+            }
+            let ids = match (imported.first(), imported.last()) {
+                (Some(first), Some(last)) if first != last => format!(" (IDs: {}-{})", first, last),
+                (Some(first), _) => format!(" (ID: {})", first),
+                _ => String::new()
+            };
+            let message = format!("Imported {} task(s){}, skipped {} line(s).", imported.len(), ids, skipped);
+            report(options, Outcome::each(message, "added", imported));
+        }, 
+        Command::ExportIcs(path) => {
+            let (calendar, skipped) = ics::calendar(&tasks);
+            let exported = tasks.len() - skipped;
+            match path {
+                Some(path) => {
+                    if !options.dry_run {
+                        std::fs::write(&path, calendar).map_err(|error| TaskError::io(&path, error))?;
+                    }
+                    let message = format!("Exported {} task(s) to {}, skipped {} without a due date.", exported, path, skipped);
+                    report(options, Outcome::each(message, "exported", tasks.iter().filter(|task| task.due_at.is_some()).map(|task| task.id)));
+                },
+                None => {
+                    print!("{}", calendar);
+                    // The calendar itself goes to stdout, so the summary can't
+                    if options.output == OutputMode::Normal {
+                        eprintln!("Exported {} task(s), skipped {} without a due date.", exported, skipped);
+                    }
+                }
+            }
+        }, 
+        Command::AddFromStdin(add_options) => {
+            if let Some(parent) = add_options.parent {
+                if !tasks.iter().any(|task| task.id == parent) {
+                    return Err(TaskError::NotFound { id: parent });
+                }
+            }
+            let lines = read_task_lines(input.reader).map_err(|error| TaskError::io("<stdin>", error))?;
+            let (first_id, count) = (Task::next_id(&tasks), lines.len() as u32);
+            for (id, (number, line)) in (first_id..).zip(lines) {
+                // One bad line leaves the whole batch unwritten, so that it can be fixed and piped in again
+                let description = check_description(line, options.max_description_len, options.truncate)
+                    .map_err(|error| TaskError::Usage(format!("Line {}: {}", number, error)))?;
+                tasks.push(add_options.new_task(id, &description).map_err(|error| TaskError::Usage(format!("Line {}: {}", number, error)))?);
+            }
+            if count == 0 {
+                report(options, "No tasks to add.".to_string());
+                return Ok(());
+            }
+            save(storage.as_mut(), &tasks, options)?;
+            let message = if count == 1 {
+                format!("Successfully added 1 task (ID: {}).", first_id)
+            } else {
+                format!("Successfully added {} tasks (IDs: {}-{}).", count, first_id, first_id + count - 1)
+            };
+            report(options, Outcome::each(message, "added", first_id..first_id + count));
+        }
+    }
+    if options.verbose {
+        let count = if storage.exists() { storage.load()?.len() } else { 0 };
+        let note = if options.dry_run { " (dry run, so nothing was saved)" } else { "" };
+        verbose(options, &format!("{} task(s) after the command{}", count, note));
+    }
+    Ok(()) 
+}
+
+
+// UNIT TESTS
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    #[test]
+    fn file_does_not_exist() {
+        let result = read_db("nonexistent.json"); 
+        assert!(result.is_err())
+    }
+    #[test]
+    fn create_and_read_empty_file() {
+        let result = create_db("test.json"); 
+        assert!(result.is_ok()); 
+        let tasks = read_db("test.json"); 
+        assert!(tasks.is_ok()); 
+        assert!(tasks.unwrap().is_empty()); 
+        // Clean up
+        let result = std::fs::remove_file("test.json"); 
+        assert!(result.is_ok())
+    }
+    // #[test]
+    #[test]
+    fn add_task() {
+        let mut tasks = vec![];
+        let description = "New Task".to_string();
+        let id = Task::next_id(&tasks);
+        let new_task = Task::new(id, description.clone());
+        tasks.push(new_task);
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].description, description);
+        assert_eq!(tasks[0].status, Status::Todo);
+    }
+
+    #[test]
+    fn update_task_description() {
+        let mut task = Task::new(1, "Old Description".to_string());
+        task.update_description("New Description").unwrap();
+
+        assert_eq!(task.description, "New Description");
+        assert!(task.updated_at.is_some());
+    }
+
+    #[test]
+    fn update_task_status() {
+        let mut task = Task::new(1, "Task".to_string());
+        task.update_status(Status::InProgress);
+
+        assert_eq!(task.status, Status::InProgress);
+        assert!(task.updated_at.is_some());
+    }
+
+    #[test]
+    fn delete_task() {
+        let mut tasks = vec![Task::new(1, "Task to be deleted".to_string())];
+        tasks.retain(|task| task.id != 1);
+
+        assert!(tasks.is_empty());
+    }
+
+    #[test]
+    fn list_tasks_by_status() {
+        let tasks = vec![
+            Task::new(1, "Task 1".to_string()),
+            Task::new(2, "Task 2".to_string()),
+        ];
+        let filtered_tasks: Vec<Task> = tasks.into_iter().filter(|task| task.status == Status::Todo).collect();
+
+        assert_eq!(filtered_tasks.len(), 2);
+    }
+
+    #[test]
+    fn parse_add_command() {
+        let args = vec!["task-tracker".to_string(), "add".to_string(), "New Task".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::Add(description, options) => {
+                assert_eq!(description, "New Task");
+                assert!(options.parent.is_none());
+            },
+            _ => panic!("Expected Add command"),
+        }
+    }
+
+    #[test]
+    fn parse_update_command() {
+        let args = vec!["task-tracker".to_string(), "update".to_string(), "1".to_string(), "Updated Task".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::Update(changes, id) => {
+                assert_eq!(changes, TaskChanges { description: Some("Updated Task".to_string()), ..TaskChanges::default() });
+                assert_eq!(id, TaskRef::Id(1));
+            },
+            _ => panic!("Expected Update command"),
+        }
+    }
+
+    #[test]
+    fn parse_delete_command() {
+        let args = vec!["task-tracker".to_string(), "delete".to_string(), "1".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::Delete(ids, subtasks) => {
+                assert_eq!(ids, vec![TaskRef::Id(1)]);
+                assert_eq!(subtasks, Subtasks::Refuse);
+            },
+            _ => panic!("Expected Delete command"),
+        }
+    }
+
+    #[test]
+    fn parse_mark_command() {
+        let args = vec!["task-tracker".to_string(), "mark-done".to_string(), "1".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::Mark(status, ids) => {
+                assert_eq!(status, Status::Done);
+                assert_eq!(ids, vec![TaskRef::Id(1)]);
+            },
+            _ => panic!("Expected Mark command"),
+        }
+    }
+
+    #[test]
+    fn parse_list_command() {
+        let args = vec!["task-tracker".to_string(), "list".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::List(filter, ..) => assert_eq!(filter, ListFilter::default()),
+            _ => panic!("Expected List command"),
+        }
+    }
+
+    #[test]
+    fn advance_daily_interval() {
+        let date = NaiveDateTime::parse_from_str("2025-01-31 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        let next = Recurrence::Daily.advance(date);
+
+        assert_eq!(next.format("%Y-%m-%d %H:%M:%S").to_string(), "2025-02-01 09:00:00");
+    }
+
+    #[test]
+    fn advance_weekly_interval() {
+        let date = NaiveDateTime::parse_from_str("2025-01-12 14:02:28", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        let next = Recurrence::Weekly.advance(date);
+
+        assert_eq!(next.format("%Y-%m-%d %H:%M:%S").to_string(), "2025-01-19 14:02:28");
+        assert!("fortnightly".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn parse_recur_command() {
+        let args = vec!["task-tracker".to_string(), "recur".to_string(), "1".to_string(), "weekly".to_string()];
+        let command = parse_args(args).unwrap();
+
+        match command {
+            Command::SetRepeat(recurrence, id) => {
+                assert_eq!(recurrence, Recurrence::Weekly);
+                assert_eq!(id, 1);
+            },
+            _ => panic!("Expected SetRepeat command"),
+        }
+
+        let args = vec!["task-tracker".to_string(), "recur".to_string(), "1".to_string(), "hourly".to_string()];
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn parse_add_subtask_and_cascade_delete() {
+        let args = vec!["task-tracker".to_string(), "add".to_string(), "Subtask".to_string(), "--parent".to_string(), "3".to_string()];
+        match parse_args(args).unwrap() {
+            Command::Add(description, options) => {
+                assert_eq!(description, "Subtask");
+                assert_eq!(options.parent, Some(3));
+            },
+            _ => panic!("Expected Add command"),
+        }
+
+        let args = vec!["task-tracker".to_string(), "delete".to_string(), "3".to_string(), "--cascade".to_string()];
+        match parse_args(args).unwrap() {
+            Command::Delete(ids, subtasks) => {
+                assert_eq!(ids, vec![TaskRef::Id(3)]);
+                assert_eq!(subtasks, Subtasks::Cascade);
+            },
+            _ => panic!("Expected Delete command"),
+        }
+    }
+
+    #[test]
+    fn subtask_descendants() {
+        let mut tasks = vec![
+            Task::new(1, "Parent".to_string()),
+            Task::new(2, "Child".to_string()),
+            Task::new(3, "Grandchild".to_string()),
+            Task::new(4, "Unrelated".to_string()),
+        ];
+        tasks[1].parent = Some(1);
+        tasks[2].parent = Some(2);
+
+        assert_eq!(Task::descendants(&tasks, 1), vec![2, 3]);
+        assert!(Task::descendants(&tasks, 4).is_empty());
+    }
+
+    #[test]
+    fn subtasks_are_added_kept_or_required_done() {
+        let path = temp_db("subtasks");
+        let trash = trash_path(&path);
+        let yes = Options { yes: true, ..Options::default() };
+        let strict = Options { require_subtasks_done: true, ..yes.clone() };
+        run_at(&path, args(&["add", "Plan trip"]), &yes).unwrap();
+        run_at(&path, args(&["subtask", "1", "Book flights"]), &yes).unwrap();
+        run_at(&path, args(&["subtask", "2", "Pick seats"]), &yes).unwrap();
+        run_at(&path, args(&["subtask", "1", "Book hotel"]), &yes).unwrap();
+        let missing_parent = run_at(&path, args(&["subtask", "9", "Orphan"]), &yes);
+        let parents: Vec<Option<u32>> = read_db(&path).unwrap().iter().map(|task| task.parent).collect();
+
+        // Flights (and with it its seats) are open, so the trip can't be done; hotel is marked in the same go
+        run_at(&path, args(&["mark-done", "3"]), &yes).unwrap();
+        let refused = run_at(&path, args(&["mark-done", "1", "4"]), &strict).unwrap_err();
+        let allowed = run_at(&path, args(&["mark-done", "2", "4"]), &strict).and_then(|_| run_at(&path, args(&["mark-done", "1"]), &strict));
+        run_at(&path, args(&["mark-todo", "1"]), &yes).unwrap();
+
+        run_at(&path, args(&["delete", "2", "--reparent"]), &yes).unwrap();
+        let after_delete: Vec<(u32, Option<u32>)> = read_db(&path).unwrap().iter().map(|task| (task.id, task.parent)).collect();
+        for file in [&path, &trash] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(parents, [None, Some(1), Some(2), Some(1)]);
+        assert!(matches!(missing_parent, Err(TaskError::NotFound { id: 9 })));
+        assert_eq!(refused.to_string(), "Task (ID: 1) has subtask(s) 2 that are not done; finish them first, or add --force to mark it as done anyway.");
+        assert!(allowed.is_ok());
+        assert_eq!(after_delete, [(1, None), (3, Some(1)), (4, Some(1))]);
+        assert!(matches!(parse_args(args(&["subtask", "1", "--", "--x"])), Ok(Command::Add(description, AddOptions { parent: Some(1), .. })) if description == "--x"));
+        assert!(parse_args(args(&["subtask", "1", "Two", "words"])).is_err());
+        assert!(parse_args(args(&["delete", "1", "--cascade", "--reparent"])).is_err());
+    }
+
+    #[test]
+    fn old_database_without_parent_loads() {
+        let data = r#"[{"id":1,"description":"Old","status":"Todo","created_at":"2025-01-12T14:02:28","updated_at":null}]"#;
+        let tasks: Vec<Task> = serde_json::from_str(data).unwrap();
+
+        assert!(tasks[0].parent.is_none());
+    }
+
+    #[test]
+    fn parse_list_min_priority() {
+        let args = vec!["task-tracker".to_string(), "list".to_string(), "todo".to_string(), "--min-priority".to_string(), "high".to_string()];
+        match parse_args(args).unwrap() {
+            Command::List(filter, ..) => {
+                assert_eq!(filter.status, Some(StatusFilter::only(Status::Todo)));
+                assert_eq!(filter.min_priority, Some(Priority::High));
+            },
+            _ => panic!("Expected List command"),
+        }
+
+        let args = vec!["task-tracker".to_string(), "list".to_string(), "--min-priority".to_string(), "urgent".to_string()];
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn min_priority_filter() {
+        let mut tasks = [
+            Task::new(1, "Low".to_string()),
+            Task::new(2, "Medium".to_string()),
+            Task::new(3, "High".to_string()),
+            Task::new(4, "Unset".to_string()),
+        ];
+        tasks[0].update_priority(Priority::Low);
+        tasks[1].update_priority(Priority::Medium);
+        tasks[2].update_priority(Priority::High);
+
+        let high: Vec<u32> = tasks.iter().filter(|task| task.has_priority_at_least(Priority::High)).map(|task| task.id).collect();
+        assert_eq!(high, vec![3]);
+        let medium: Vec<u32> = tasks.iter().filter(|task| task.has_priority_at_least(Priority::Medium)).map(|task| task.id).collect();
+        assert_eq!(medium, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn utc_timestamp_formats_in_local_offset() {
+        let stored = timestamp::parse("2025-01-12T14:02:28Z").unwrap();
+        let offset = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+
+        assert_eq!(timestamp::format(&stored, &offset), "2025-01-12 16:02:28");
+        assert_eq!(timestamp::format(&stored, &Utc), "2025-01-12 14:02:28");
+    }
+
+    #[test]
+    fn legacy_naive_timestamps_still_load() {
+        let data = r#"[{"id":1,"description":"Old","status":"Todo","created_at":"2025-01-12T14:02:28.123456","updated_at":"2025-01-13T08:00:00"}]"#;
+        let tasks: Vec<Task> = serde_json::from_str(data).unwrap();
+
+        assert_eq!(timestamp::format(&tasks[0].created_at, &Local), "2025-01-12 14:02:28");
+        assert_eq!(timestamp::format(&tasks[0].updated_at.unwrap(), &Local), "2025-01-13 08:00:00");
+        let saved = serde_json::to_string(&tasks).unwrap();
+        let reloaded: Vec<Task> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded[0].created_at, tasks[0].created_at);
+    }
+
+    #[test]
+    fn parse_repeat_intervals() {
+        assert_eq!("3d".parse::<Recurrence>(), Ok(Recurrence::EveryNDays(3)));
+        assert!("0d".parse::<Recurrence>().is_err());
+        assert!("d".parse::<Recurrence>().is_err());
+
+        let args = vec!["task-tracker".to_string(), "add".to_string(), "Water plants".to_string(), "--repeat".to_string(), "3d".to_string()];
+        match parse_args(args).unwrap() {
+            Command::Add(_, options) => assert_eq!(options.recurrence, Some(Recurrence::EveryNDays(3))),
+            _ => panic!("Expected Add command"),
+        }
+    }
+
+    #[test]
+    fn completing_recurring_task_respawns_it() {
+        let now = timestamp::parse("2025-03-01T10:00:00Z").unwrap();
+        let mut tasks = vec![Task::new(1, "Water plants".to_string()), Task::new(2, "One-off".to_string())];
+        tasks[0].recurrence = Some(Recurrence::EveryNDays(3));
+        tasks[0].due_at = Some(timestamp::parse("2025-02-28T09:00:00Z").unwrap());
+
+        assert_eq!(respawn(&mut tasks, 2, now), None);
+        assert_eq!(respawn(&mut tasks, 1, now), Some(3));
+        let copy = &tasks[2];
+        assert_eq!(copy.description, "Water plants");
+        assert_eq!(copy.status, Status::Todo);
+        assert_eq!(copy.created_at, now);
+        assert_eq!(copy.recurrence, Some(Recurrence::EveryNDays(3)));
+        assert_eq!(copy.due_at, Some(timestamp::parse("2025-03-03T09:00:00Z").unwrap()));
+    }
+
+    #[test]
+    fn recurrence_strings_from_older_files_load() {
+        let data = r#"[{"id":1,"description":"Old","status":"Done","created_at":"2025-01-12T14:02:28Z","updated_at":null,"recurrence":"daily"}]"#;
+        let tasks: Vec<Task> = serde_json::from_str(data).unwrap();
+
+        assert_eq!(tasks[0].recurrence, Some(Recurrence::Daily));
+        assert!(serde_json::to_string(&tasks).unwrap().contains(r#""recurrence":"daily""#));
+    }
+
+    fn temp_db(name: &str) -> String {
+        std::env::temp_dir().join(format!("task-tracker-{}-{}.json", name, std::process::id())).to_string_lossy().to_string()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("task-tracker").chain(args.iter().copied()).map(String::from).collect()
+    }
+
+    #[test]
+    fn backups_are_rotated_and_restored() {
+        let path = temp_db("backups");
+        let dir = std::env::temp_dir().join(format!("task-tracker-backups-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = Options { backup_dir: Some(dir.to_string_lossy().to_string()), ..Options::default() };
+        let counts = |backups: &[PathBuf]| -> Vec<usize> { backups.iter().map(|backup| read_db(&backup.to_string_lossy()).unwrap().len()).collect() };
+        for description in ["Buy milk", "Walk the dog", "Water the plants"] {
+            run_at(&path, args(&["add", description]), &options).unwrap();
+            run_at(&path, args(&["backup", "--keep", "2"]), &options).unwrap();
+        }
+        let rotated = counts(&backup::list(&dir, &path).unwrap());
+
+        let corrupt = dir.join("corrupt.json");
+        std::fs::write(&corrupt, "[{\"id\": 1,\n  oops").unwrap();
+        let from_corrupt = run_at(&path, args(&["restore", "--backup", &corrupt.to_string_lossy()]), &options);
+        let after_corrupt = (read_db(&path).unwrap().len(), backup::list(&dir, &path).unwrap().len());
+        run_at(&path, args(&["restore", "--backup", "2"]), &options).unwrap();
+        let restored: Vec<String> = read_db(&path).unwrap().into_iter().map(|task| task.description).collect();
+        let after_restore = counts(&backup::list(&dir, &path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(rotated, [3, 2]);
+        assert!(matches!(from_corrupt, Err(TaskError::Parse { .. })));
+        assert_eq!(after_corrupt, (3, 2));
+        assert_eq!(restored, ["Buy milk", "Walk the dog"]);
+        // The tasks from before the restore were backed up first
+        assert_eq!(after_restore, [3, 3, 2]);
+    }
+
+    #[test]
+    fn merge_writes_nothing_in_a_dry_run() {
+        let path = temp_db("merge");
+        let other = temp_db("merge-other");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        std::fs::copy(&path, &other).unwrap();
+        run_at(&path, args(&["add", "Walk the dog"]), &options).unwrap();
+        run_at(&other, args(&["add", "Water the plants"]), &options).unwrap();
+        run_at(&other, args(&["update", "1", "Buy oat milk"]), &options).unwrap();
+
+        run_at(&path, args(&["merge", &other]), &Options { dry_run: true, ..Options::default() }).unwrap();
+        let before: Vec<String> = read_db(&path).unwrap().into_iter().map(|task| task.description).collect();
+        run_at(&path, args(&["merge", &other]), &options).unwrap();
+        let after: Vec<(u32, String)> = read_db(&path).unwrap().into_iter().map(|task| (task.id, task.description)).collect();
+        let missing = run_at(&path, args(&["merge", "no-such-file.json"]), &options);
+        for file in [&path, &other] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(before, ["Buy milk", "Walk the dog"]);
+        assert_eq!(after, [(1, "Buy oat milk".to_string()), (2, "Walk the dog".to_string()), (3, "Water the plants".to_string())]);
+        assert!(matches!(missing, Err(TaskError::Io { .. })));
+    }
+
+    #[test]
+    fn strict_mode_turns_warnings_into_errors() {
+        let path = temp_db("strict");
+        let trash = trash_path(&path);
+        let lenient = Options { yes: true, ..Options::default() };
+        let strict = Options { strict: true, ..lenient.clone() };
+        run_at(&path, args(&["add", "Buy milk"]), &lenient).unwrap();
+        run_at(&path, args(&["add", "Walk the dog"]), &lenient).unwrap();
+
+        let lenient_delete = run_at(&path, args(&["delete", "9"]), &lenient);
+        let strict_delete = run_at(&path, args(&["delete", "9"]), &strict);
+        // With --strict a missing id fails the whole command, so task 1 is kept too
+        let strict_bulk = run_at(&path, args(&["delete", "1", "9"]), &strict);
+        let strict_mark = run_at(&path, args(&["mark-done", "2", "8"]), &strict);
+        let remaining: Vec<(u32, Status)> = read_db(&path).unwrap().iter().map(|task| (task.id, task.status)).collect();
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&trash);
+
+        assert!(lenient_delete.is_ok());
+        assert!(matches!(strict_delete, Err(TaskError::NotFound { id: 9 })));
+        assert!(matches!(strict_bulk, Err(TaskError::NotFound { id: 9 })));
+        assert!(matches!(strict_mark, Err(TaskError::NotFound { id: 8 })));
+        assert_eq!(remaining, [(1, Status::Todo), (2, Status::Todo)]);
+        assert!(tolerate(&lenient, TaskError::NotFound { id: 3 }, "Error: ID not found.".to_string()).is_ok());
+        assert!(tolerate(&strict, TaskError::NotFound { id: 3 }, "Error: ID not found.".to_string()).is_err());
+    }
+
+    #[test]
+    fn json_error_codes_and_context() {
+        let json = Options { json: true, ..Options::default() };
+        let path = temp_db("errors");
+        create_db(&path).unwrap();
+        run_at(&path, args(&["add", "Parent"]), &json).unwrap();
+        run_at(&path, args(&["add", "Child", "--parent", "1"]), &json).unwrap();
+
+        let error = run_at(&path, args(&["frobnicate"]), &json).unwrap_err().to_json();
+        assert_eq!(error["code"], "usage");
+
+        let error = run_at(&path, args(&["add", "Orphan", "--parent", "42"]), &json).unwrap_err().to_json();
+        assert_eq!(error["code"], "not_found");
+        assert_eq!(error["id"], 42);
+
+        let error = run_at(&path, args(&["delete", "1"]), &json).unwrap_err().to_json();
+        assert_eq!(error["code"], "precondition_failed");
+        assert_eq!(error["id"], 1);
+
+        std::fs::write(&path, "[{\"id\": 1,\n  oops").unwrap();
+        let error = read_db(&path).unwrap_err().to_json();
+        assert_eq!(error["code"], "parse");
+        assert_eq!(error["path"], path.as_str());
+        assert_eq!(error["line"], 2);
+        assert_eq!(error["column"], 3);
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = std::env::temp_dir().join("task-tracker-missing-dir").join("tasks.json").to_string_lossy().to_string();
+        let error = run_at(&missing, args(&["list"]), &json).unwrap_err().to_json();
+        assert_eq!(error["code"], "io");
+        // The lock is the first thing the command tries to create in the missing directory
+        assert_eq!(error["path"], format!("{}.lock", missing));
+    }
+
+    #[test]
+    fn extract_global_flags() {
+        let mut arguments = args(&["list", "--json", "todo"]);
+        let options = Options::extract(&mut arguments).unwrap();
+
+        assert!(options.json);
+        assert_eq!(arguments, args(&["list", "todo"]));
+    }
+
+    #[test]
+    fn flags_go_anywhere_until_a_double_dash() {
+        // Global flags are taken out wherever they are, and command flags can come in any order
+        for order in [&["--json", "add", "Buy milk", "--priority", "high", "--due", "2025-03-01"][..], &["add", "--due", "2025-03-01", "--json", "Buy milk", "--priority", "high"]] {
+            let mut arguments = args(order);
+            assert!(Options::extract(&mut arguments).unwrap().json);
+            match parse_args(arguments).unwrap() {
+                Command::Add(description, options) => {
+                    assert_eq!(description, "Buy milk");
+                    assert_eq!(options.priority, Some(Priority::High));
+                    assert!(options.due_at.is_some());
+                },
+                command => panic!("{:?}", command)
+            }
+        }
+
+        // After `--`, nothing is a flag, for the command or globally
+        let mut arguments = args(&["add", "--priority", "low", "--", "--json"]);
+        assert!(!Options::extract(&mut arguments).unwrap().json);
+        assert!(matches!(parse_args(arguments).unwrap(), Command::Add(description, _) if description == "--json"));
+        assert!(matches!(parse_args(args(&["add", "--", "-5 degrees"])).unwrap(), Command::Add(description, _) if description == "-5 degrees"));
+        assert!(parse_args(args(&["add", "Buy milk", "--", "--json"])).is_err());
+        let changes = match parse_args(args(&["update", "1", "--", "--status"])).unwrap() {
+            Command::Update(changes, _) => changes,
+            command => panic!("{:?}", command)
+        };
+        assert_eq!(changes.description.as_deref(), Some("--status"));
+        assert!(changes.status.is_none());
+
+        // Unknown flags are named in the error
+        for (arguments, token) in [(&["add", "Buy milk", "--colour", "red"][..], "--colour"), (&["list", "todo", "--bogus"], "--bogus"), (&["mark-done", "1", "--bogus"], "--bogus"), (&["archive", "--after", "2025-01-01"], "--after"), (&["prune", "--newer-than", "3"], "--newer-than")] {
+            let error = parse_args(args(arguments)).unwrap_err();
+            assert!(error.starts_with(&format!("Unknown option: {}", token)), "{:?}: {}", arguments, error);
+        }
+    }
+
+    #[test]
+    fn completion_time_is_recorded_and_cleared() {
+        let mut task = Task::new(1, "Task".to_string());
+        task.update_status(Status::Done);
+        let completed_at = task.completed_at;
+        assert!(completed_at.is_some());
+
+        task.update_status(Status::Done);
+        assert_eq!(task.completed_at, completed_at);
+        task.update_status(Status::Todo);
+        assert!(task.completed_at.is_none());
+    }
+
+    #[test]
+    fn archive_only_tasks_completed_before_cutoff() {
+        let cutoff = Some(timestamp::parse("2025-01-01T00:00:00Z").unwrap());
+        let mut tasks: Vec<Task> = (1..=4).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        tasks[0].status = Status::Done;
+        tasks[0].completed_at = Some(timestamp::parse("2024-12-31T23:59:59Z").unwrap());
+        tasks[1].status = Status::Done;
+        tasks[1].completed_at = Some(timestamp::parse("2025-01-01T00:00:00Z").unwrap());
+        tasks[2].updated_at = Some(timestamp::parse("2024-06-01T12:00:00Z").unwrap());
+        tasks[3].status = Status::Done;
+        tasks[3].updated_at = Some(timestamp::parse("2024-06-01T12:00:00Z").unwrap());
+
+        let archived: Vec<u32> = take_archivable(&mut tasks, cutoff).iter().map(|task| task.id).collect();
+        assert_eq!(archived, vec![1, 4]);
+        assert!(take_archivable(&mut tasks, cutoff).is_empty());
+        let archived: Vec<u32> = take_archivable(&mut tasks, None).iter().map(|task| task.id).collect();
+        assert_eq!(archived, vec![2]);
+        assert_eq!(tasks.len(), 1);
+    }
+
+    #[test]
+    fn parse_archive_command() {
+        match parse_args(args(&["archive", "--before", "2025-01-01"])).unwrap() {
+            Command::Archive(Some(cutoff)) => assert_eq!(timestamp::format(&cutoff, &Local), "2025-01-01 00:00:00"),
+            _ => panic!("Expected Archive command"),
+        }
+        assert!(parse_args(args(&["archive", "--before", "01/01/2025"])).is_err());
+        assert!(matches!(parse_args(args(&["archive"])), Ok(Command::Archive(None))));
+    }
+
+    #[test]
+    fn parse_multiple_ids() {
+        match parse_args(args(&["mark-done", "1,2,5"])).unwrap() {
+            Command::Mark(status, ids) => {
+                assert_eq!(status, Status::Done);
+                assert_eq!(ids, [1, 2, 5].map(TaskRef::Id));
+            },
+            _ => panic!("Expected Mark command"),
+        }
+        match parse_args(args(&["delete", "1,2", "5", "7", "8", "9", "10", "--cascade"])).unwrap() {
+            Command::Delete(ids, subtasks) => {
+                assert_eq!(ids, [1, 2, 5, 7, 8, 9, 10].map(TaskRef::Id));
+                assert_eq!(subtasks, Subtasks::Cascade);
+            },
+            _ => panic!("Expected Delete command"),
+        }
+        assert!(parse_args(args(&["mark-todo", "1,x"])).is_err());
+        assert!(parse_args(args(&["mark-todo", ","])).is_err());
+        assert!(parse_args(args(&["mark-todo", "1", "--cascade"])).is_err());
+    }
+
+    #[test]
+    fn delete_all_done_leaves_open_tasks() {
+        let path = temp_db("delete-done");
+        let options = Options { yes: true, ..Options::default() };
+        for description in ["Buy milk", "Walk the dog", "Call mom", "Plan trip", "Book flights", "Pack"] {
+            run_at(&path, args(&["add", description]), &options).unwrap();
+        }
+        // Plan trip is done, but one of its subtasks is not
+        run_at(&path, args(&["add", "Book hotel", "--parent", "4"]), &options).unwrap();
+        run_at(&path, args(&["add", "Check in", "--parent", "6"]), &options).unwrap();
+        for (status, id) in [("done", "1"), ("in-progress", "2"), ("done", "4"), ("done", "5"), ("done", "6"), ("done", "8")] {
+            run_at(&path, args(&[&format!("mark-{}", status), id]), &options).unwrap();
+        }
+        run_at(&path, args(&["delete", "--all-done"]), &options).unwrap();
+        let left: Vec<(u32, Status)> = read_db(&path).unwrap().iter().map(|task| (task.id, task.status)).collect();
+        let trashed: Vec<u32> = read_db(&trash_path(&path)).unwrap().iter().map(|task| task.id).collect();
+        let _ = std::fs::remove_file(trash_path(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(left, [(2, Status::InProgress), (3, Status::Todo), (4, Status::Done), (7, Status::Todo)]);
+        assert_eq!(trashed, [1, 5, 6, 8]);
+        assert!(matches!(parse_args(args(&["rm", "--all-done"])), Ok(Command::DeleteDone)));
+        assert!(parse_args(args(&["delete", "--all-done", "3"])).is_err());
+        assert!(parse_args(args(&["delete", "--all-done", "--cascade"])).is_err());
+    }
+
+    #[test]
+    fn bulk_mark_reports_missing_ids() {
+        let path = temp_db("bulk");
+        create_db(&path).unwrap();
+        for description in ["One", "Two", "Three"] {
+            run_at(&path, args(&["add", description]), &Options::default()).unwrap();
+        }
+
+        run_at(&path, args(&["mark-done", "1,3,9"]), &Options::default()).unwrap();
+        let statuses: Vec<Status> = read_db(&path).unwrap().iter().map(|task| task.status).collect();
+        assert_eq!(statuses, vec![Status::Done, Status::Todo, Status::Done]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_reminder_offsets() {
+        assert_eq!(parse_offset("2d"), Ok(2 * 24 * 60));
+        assert_eq!(parse_offset("3h"), Ok(180));
+        assert_eq!(parse_offset("45m"), Ok(45));
+        assert!(parse_offset("2w").is_err());
+        assert!(parse_offset("d").is_err());
+        assert_eq!(format_offset(2 * 24 * 60), "2d");
+        assert_eq!(format_offset(90), "90m");
+    }
+
+    #[test]
+    fn reminder_fires_at_boundary_once() {
+        let due_at = timestamp::parse("2025-03-10T09:00:00Z").unwrap();
+        let mut tasks = vec![Task::new(1, "Report".to_string())];
+        tasks[0].due_at = Some(due_at);
+        tasks[0].add_reminder(2 * 24 * 60).unwrap();
+
+        let just_before = timestamp::parse("2025-03-08T08:59:59Z").unwrap();
+        assert!(sweep_reminders(&mut tasks, just_before).is_empty());
+        let boundary = timestamp::parse("2025-03-08T09:00:00Z").unwrap();
+        assert_eq!(sweep_reminders(&mut tasks, boundary), vec![1]);
+        assert!(tasks[0].reminders[0].delivered);
+        assert!(sweep_reminders(&mut tasks, boundary + Duration::hours(1)).is_empty());
+    }
+
+    #[test]
+    fn moving_due_date_rearms_reminders() {
+        let now = timestamp::parse("2025-03-09T12:00:00Z").unwrap();
+        let mut task = Task::new(1, "Report".to_string());
+        task.due_at = Some(timestamp::parse("2025-03-10T09:00:00Z").unwrap());
+        task.add_reminder(24 * 60).unwrap();
+        task.add_reminder(60).unwrap();
+        task.reminders[0].delivered = true;
+
+        // Now fires at 2025-03-09T10:00, which has already passed, so it stays delivered 
+        task.update_due(timestamp::parse("2025-03-10T10:00:00Z").unwrap(), now);
+        assert!(task.reminders[0].delivered);
+        task.update_due(timestamp::parse("2025-03-12T09:00:00Z").unwrap(), now);
+        assert!(!task.reminders[0].delivered);
+        assert!(!task.reminders[1].delivered);
+    }
+
+    #[test]
+    fn reminder_requires_due_date() {
+        let mut task = Task::new(3, "Someday".to_string());
+        let error = task.add_reminder(60).unwrap_err();
+
+        assert_eq!(error.code(), "precondition_failed");
+        assert!(error.to_string().contains("due 3 <date>"));
+        assert!(task.reminders.is_empty());
+    }
+
+    #[test]
+    fn explain_mark_done() {
+        let tasks = vec![Task::new(1, "Buy milk".to_string())];
+        let mut arguments = args(&["mark-done", "1", "--explain"]);
+        let options = Options::extract(&mut arguments).unwrap();
+        assert!(options.explain);
+
+        let command = parse_args(arguments).unwrap();
+        assert_eq!(explain(&command, &tasks), "This will mark task 1 ('Buy milk') as done.");
+        let command = parse_args(args(&["mark-done", "1,7"])).unwrap();
+        assert_eq!(explain(&command, &tasks), "This will mark task 1 ('Buy milk') as done.\nTask 7 doesn't exist, so nothing will happen to it.");
+    }
+
+    #[test]
+    fn dry_run_delete_leaves_file_unchanged() {
+        let path = temp_db("dry-run");
+        create_db(&path).unwrap();
+        run_at(&path, args(&["add", "Keep me"]), &Options::default()).unwrap();
+        let before = std::fs::read_to_string(&path).unwrap();
+
+        let dry_run = Options { dry_run: true, ..Options::default() };
+        run_at(&path, args(&["delete", "1"]), &dry_run).unwrap();
+        run_at(&path, args(&["add", "Not me"]), &dry_run).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+        std::fs::remove_file(&path).unwrap();
+
+        run_at(&path, args(&["add", "Nowhere"]), &dry_run).unwrap();
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[test]
+    fn clipboard_gets_filtered_output_in_selected_format() {
+        let mut tasks = vec![Task::new(1, "Buy milk".to_string()), Task::new(2, "Call mom".to_string())];
+        tasks[1].update_status(Status::Done);
+        let expected = format!("{}\n", serde_json::to_string_pretty(&tasks[1..]).unwrap());
+        let options = Options { json: true, clipboard: true, ..Options::default() };
+        let (output, count) = render_list(&ListFilter { status: Some(StatusFilter::only(Status::Done)), ..ListFilter::default() }, tasks, &options).unwrap();
+
+        let mut clipboard = clipboard::tests::MockClipboard::default();
+        let mut out = vec![];
+        clipboard::copy_or_print(&output, count, Ok(&mut clipboard), &mut out).unwrap();
+        assert_eq!(clipboard.text, Some(expected));
+        assert_eq!(String::from_utf8(out).unwrap(), "copied 1 tasks to clipboard\n");
+    }
+
+    #[test]
+    fn timer_accumulates_elapsed_time() {
+        let start = timestamp::parse("2025-03-01T09:00:00Z").unwrap();
+        let mut task = Task::new(1, "Billable".to_string());
+
+        task.start_timer(start).unwrap();
+        assert_eq!(task.start_timer(start).unwrap_err().code(), "precondition_failed");
+        assert_eq!(task.stop_timer(start + Duration::minutes(135)).unwrap(), 135 * 60);
+        assert!(task.stop_timer(start).is_err());
+        task.start_timer(start + Duration::hours(3)).unwrap();
+        task.stop_timer(start + Duration::hours(4)).unwrap();
+
+        assert_eq!(task.time_spent, (135 + 60) * 60);
+        assert_eq!(format_duration(task.time_spent), "3h 15m");
+        assert_eq!(format_duration(59), "0h 0m");
+        assert_eq!(format_duration(45 * 60 + 59), "0h 45m");
+        assert_eq!(format_duration(100 * 3600), "100h 0m");
+        assert_eq!(elapsed_seconds(start, start - Duration::minutes(5)), 0);
+    }
+
+    #[test]
+    fn marking_running_task_done_stops_it() {
+        let mut task = Task::new(1, "Billable".to_string());
+        task.start_timer(Utc::now() - Duration::hours(1)).unwrap();
+        task.update_status(Status::Done);
+
+        assert!(task.started_at.is_none());
+        assert!(task.time_spent >= 3600);
+    }
+
+    #[test]
+    fn long_descriptions_are_rejected() {
+        let error = check_description("x".repeat(11), 10, false).unwrap_err();
+        assert_eq!(error.code(), "usage");
+        assert!(error.to_string().contains("11 characters"));
+
+        // Ten characters but thirty bytes
+        let accented = "é".repeat(5) + &"日".repeat(5);
+        assert_eq!(check_description(accented.clone(), 10, false).unwrap(), accented);
+    }
+
+    #[test]
+    fn truncation_keeps_whole_characters() {
+        let truncated = check_description("日本語のテキスト".to_string(), 4, true).unwrap();
+        assert_eq!(truncated, "日本語…");
+        assert_eq!(check_description("short".to_string(), 4, true).unwrap(), "sho…");
+        assert_eq!(check_description("fits".to_string(), 4, true).unwrap(), "fits");
+    }
+
+    #[test]
+    fn validate_flags_long_descriptions() {
+        let tasks = vec![Task::new(1, "Fine".to_string()), Task::new(2, "y".repeat(20))];
+        assert_eq!(long_descriptions(&tasks, 10), vec![(2, 20)]);
+        assert!(long_descriptions(&tasks, 20).is_empty());
+    }
+
+    #[test]
+    fn default_limit_allows_normal_descriptions() {
+        let options = Options::default();
+        let paragraph = "Write up the quarterly roadmap and share it with the team. ".repeat(50);
+        assert!(check_description(paragraph, options.max_description_len, options.truncate).is_ok());
+
+        let mut arguments = args(&["add", "Task", "--max-description-len=3", "--truncate"]);
+        let options = Options::extract(&mut arguments).unwrap();
+        assert_eq!((options.max_description_len, options.truncate), (3, true));
+        assert_eq!(arguments, args(&["add", "Task"]));
+        assert!(Options::extract(&mut args(&["list", "--max-description-len=lots"])).is_err());
+    }
+
+    #[test]
+    fn projects_are_kept_apart() {
+        let dir = std::env::temp_dir().join(format!("task-tracker-projects-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let work = db_path(&dir, Some("work"), Backend::Json).to_string_lossy().to_string();
+        let home = db_path(&dir, Some("home"), Backend::Json).to_string_lossy().to_string();
+
+        run_at(&work, args(&["add", "Write report"]), &Options::default()).unwrap();
+        run_at(&home, args(&["add", "Water plants"]), &Options::default()).unwrap();
+        run_at(&home, args(&["add", "Fix sink"]), &Options::default()).unwrap();
+        let work_tasks = read_db(&work).unwrap();
+        let home_tasks = read_db(&home).unwrap();
+        let projects = list_projects(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(work_tasks.len(), 1);
+        assert_eq!(work_tasks[0].description, "Write report");
+        assert_eq!(home_tasks.len(), 2);
+        assert_eq!(projects, vec!["home", "work"]);
+        assert_eq!(db_path(Path::new(""), None, Backend::Json), PathBuf::from("tasks.json"));
+        assert_eq!(db_path(Path::new(""), Some("work"), Backend::Sqlite), PathBuf::from("tasks.work.db"));
+    }
+
+    #[test]
+    fn project_names_cannot_escape_the_directory() {
+        for name in ["", "../work", "a/b", "work.old", ".."] {
+            assert!(validate_project_name(name).is_err(), "{:?}", name);
+        }
+        assert!(validate_project_name("side-project_2").is_ok());
+
+        let mut arguments = args(&["--project", "work", "list"]);
+        let options = Options::extract(&mut arguments).unwrap();
+        assert_eq!(options.project.as_deref(), Some("work"));
+        assert_eq!(arguments, args(&["list"]));
+        assert_eq!(Options::extract(&mut args(&["list", "--project", "../etc"])).unwrap_err().code(), "usage");
+        assert!(Options::extract(&mut args(&["list", "--project"])).is_err());
+        assert!(Options::extract(&mut args(&["list", "--project", "work", "--file", "work.json"])).is_err());
+    }
+
+    #[test]
+    fn output_modes_are_flags() {
+        let mut arguments = args(&["add", "Task", "-q"]);
+        let quiet = Options::extract(&mut arguments).unwrap();
+        assert_eq!(quiet.output, OutputMode::Quiet);
+        assert_eq!(arguments, args(&["add", "Task"]));
+
+        assert_eq!(Options::extract(&mut args(&["--porcelain", "list"])).unwrap().output, OutputMode::Porcelain);
+        assert_eq!(Options::extract(&mut args(&["list"])).unwrap().output, OutputMode::Normal);
+        assert!(Options::extract(&mut args(&["--porcelain", "list", "--quiet"])).is_err());
+    }
+
+    #[test]
+    fn notes_are_appended_and_displayed() {
+        let now = timestamp::parse("2025-03-01T09:00:00Z").unwrap();
+        let mut task = Task::new(1, "Plan trip".to_string());
+        task.add_note("Book flights\nWindow seat".to_string(), now);
+        task.add_note("Ask about visas".to_string(), now);
+
+        assert_eq!(task.updated_at, Some(now));
+        let time = timestamp::format(&now, &Local);
+        let expected = format!("Notes:\n    - [{time}] Book flights\n      Window seat\n    - [{time}] Ask about visas");
+        assert!(task.to_string().ends_with(&expected), "{}", task);
+
+        task.clear_notes(now);
+        assert!(task.notes.is_empty());
+        assert!(!task.to_string().contains("Notes:"));
+
+        let command = parse_args(args(&["note", "1", "Ask about visas"])).unwrap();
+        assert!(matches!(command, Command::Note(text, 1) if text == "Ask about visas"));
+    }
+
+    #[test]
+    fn plain_output_is_a_key_per_line() {
+        let mut task = Task::new(3, "First line\nsecond line, with a \\".to_string());
+        task.created_at = timestamp::parse("2025-03-01T09:00:00Z").unwrap();
+        task.status = Status::InProgress;
+        task.tags = vec!["work".to_string(), "urgent".to_string()];
+        let plain = task.plain(TimeFormat::Iso);
+
+        assert_eq!(plain.lines().collect::<Vec<_>>(), [
+            "id=3", "status=in-progress", "desc=First line\\nsecond line, with a \\\\", "created=2025-03-01T09:00:00Z",
+            "updated=", "completed=", "due=", "priority=", "parent=", "assignee=", "tags=work,urgent"
+        ]);
+        assert!(plain.ends_with("tags=work,urgent\n"));
+        assert!(parse_args(args(&["list", "--plain", "--template", "{id}"])).is_err());
+    }
+
+    #[test]
+    fn history_records_each_change_up_to_the_limit() {
+        let mut task = Task::new(1, "Plan trip".to_string());
+        task.update_status(Status::InProgress);
+        task.update_status(Status::InProgress);
+        task.update_description("Plan the trip").unwrap();
+        task.update_description("  Plan the trip ").unwrap();
+        let changes: Vec<&Change> = task.history.iter().map(|entry| &entry.change).collect();
+        assert_eq!(changes, [
+            &Change::Created { description: "Plan trip".to_string() },
+            &Change::Status { from: Status::Todo, to: Status::InProgress },
+            &Change::Description { from: "Plan trip".to_string(), to: "Plan the trip".to_string() },
+        ]);
+        let time = timestamp::iso(&task.history[1].at);
+        let formatted = format_history(&task.history, TimeFormat::Iso);
+        assert_eq!(formatted.lines().nth(1), Some(format!("    - [{time}] status: todo → in progress").as_str()));
+
+        for _ in 0..HISTORY_LIMIT {
+            task.update_status(if task.status == Status::Done { Status::Todo } else { Status::Done });
+        }
+        assert_eq!(task.history.len(), HISTORY_LIMIT);
+        assert!(!task.history.iter().any(|entry| matches!(entry.change, Change::Created { .. } | Change::Description { .. })));
+
+        let json = serde_json::to_string(&task.history[0]).unwrap();
+        assert!(json.contains(r#""change":"status""#), "{}", json);
+        let old: Task = serde_json::from_str(r#"{"id": 1, "description": "Old task", "status": "Todo", "created_at": "2024-01-01T00:00:00Z"}"#).unwrap();
+        assert!(old.history.is_empty());
+        assert!(matches!(parse_args(args(&["history", "1"])), Ok(Command::History(1))));
+    }
+
+    #[test]
+    fn tasks_without_notes_still_load() {
+        let json = r#"[{"id": 1, "description": "Old", "status": "Todo", "created_at": "2025-03-01T09:00:00Z", "updated_at": null}]"#;
+        let tasks: Vec<Task> = serde_json::from_str(json).unwrap();
+        assert!(tasks[0].notes.is_empty());
+    }
+
+    #[test]
+    fn show_prints_every_field() {
+        let mut parent = Task::new(1, "Plan trip".to_string());
+        parent.priority = Some(Priority::High);
+        parent.add_note("Book flights".to_string(), Utc::now());
+        let mut child = Task::new(2, "Book hotel".to_string());
+        child.parent = Some(1);
+        let tasks = vec![parent, child];
+
+        let details = render_show(1, &tasks, &Options::default()).unwrap();
+        assert!(details.starts_with("Task 1: Plan trip\nStatus:       todo\nPriority:     high\n"), "{}", details);
+        assert!(details.contains("\nDue:          -\n"));
+        assert!(details.contains("\nSubtasks:     2\n"));
+        assert!(details.contains("\nTime spent:   0h 0m\n"));
+        assert!(details.contains("] Book flights\n"));
+
+        let json = Options { json: true, ..Options::default() };
+        let object: serde_json::Value = serde_json::from_str(&render_show(2, &tasks, &json).unwrap()).unwrap();
+        assert_eq!(object["id"], 2);
+        assert_eq!(object["parent"], 1);
+    }
+
+    #[test]
+    fn show_missing_task_fails() {
+        let file_path = temp_db("show-missing");
+        let result = run_at(&file_path, args(&["show", "7"]), &Options::default());
+        std::fs::remove_file(&file_path).unwrap();
+
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), "not_found");
+        assert_eq!(error.to_string(), "Task (ID: 7) not found.");
+    }
+
+    #[test]
+    fn completions_need_a_known_shell() {
+        assert!(matches!(parse_args(args(&["completions", "fish"])), Ok(Command::Completions(completions::Shell::Fish))));
+        assert_eq!(parse_args(args(&["completions", "powershell"])).unwrap_err(), "Unknown shell: powershell (expected bash, zsh or fish)");
+        assert!(parse_args(args(&["completions"])).is_err());
+        assert!(parse_args(args(&["completions", "bash", "zsh"])).is_err());
+
+        let bash = completions::script(completions::Shell::Bash);
+        for name in ["add", "update", "delete", "mark-todo", "mark-in-progress", "mark-done", "list", "count", "path", "duplicate"] {
+            assert!(bash.contains(name), "{}", name);
+        }
+        assert!(bash.contains("all not-done todo in-progress done"));
+    }
+
+    #[test]
+    fn help_lists_every_command() {
+        let usage = help::usage();
+        let names = [
+            "add", "update", "delete", "mark-todo", "mark-in-progress", "mark-done", "list", "show", "priority", "set-repeat",
+            "recur", "due", "remind", "start", "stop", "note", "notes", "clear-notes", "archive", "validate", "projects", "repair", "migrate", "doctor", "fsck", "move", "help",
+            "prune", "restore", "empty-trash", "edit-desc", "copy", "mark", "advance", "progress", "interactive", "repl", "import", "export", "toggle", "reopen", "uncomplete", "estimate", "stats", "completions", "block", "unblock", "assign", "unassign", "rm", "ls", "done", "watch", "report", "history", "search", "subtask", "count", "compact", "duplicate", "path", "backup", "backups", "merge", "reset-ids"
+        ];
+        for name in names {
+            assert!(usage.contains(name), "{} is missing from the help", name);
+        }
+        for command in help::COMMANDS {
+            let error = parse_args(args(&[command.name])).err().unwrap_or_default();
+            assert!(!error.starts_with("Unknown command"), "{} is not parsed", command.name);
+        }
+        for flag in ["help", "--help", "-h"] {
+            assert!(matches!(parse_args(args(&[flag])), Ok(Command::Help)));
+        }
+    }
+
+    #[test]
+    fn unknown_command_suggests_help() {
+        let error = parse_args(args(&["frobnicate"])).unwrap_err();
+        assert_eq!(error, "Unknown command: frobnicate (run `task-tracker help` to see the commands)");
+    }
+
+    #[test]
+    fn archive_moves_done_tasks_and_appends() {
+        let file_path = temp_db("archive-move");
+        let archive = archive_path(&file_path);
+        for description in ["Buy milk", "Call mom", "Walk the dog"] {
+            run_at(&file_path, args(&["add", description]), &Options::default()).unwrap();
+        }
+        run_at(&file_path, args(&["mark-done", "1,3"]), &Options::default()).unwrap();
+        run_at(&file_path, args(&["archive"]), &Options::default()).unwrap();
+        let (active, archived) = (read_db(&file_path).unwrap(), read_db(&archive).unwrap());
+
+        run_at(&file_path, args(&["mark-done", "2"]), &Options::default()).unwrap();
+        run_at(&file_path, args(&["archive"]), &Options::default()).unwrap();
+        let (active_after, archived_after) = (read_db(&file_path).unwrap(), read_db(&archive).unwrap());
+        std::fs::remove_file(&file_path).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+
+        assert_eq!(active.len(), 1);
+        assert_eq!(archived.iter().map(|task| task.id).collect::<Vec<u32>>(), vec![1, 3]);
+        assert!(active_after.is_empty());
+        assert_eq!(archived_after.iter().map(|task| task.id).collect::<Vec<u32>>(), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn archive_sits_next_to_task_file() {
+        assert_eq!(archive_path("tasks.json"), "archive.json");
+        assert_eq!(archive_path("tasks.work.json"), "archive.work.json");
+        assert_eq!(archive_path(&format!("dir{}todo.json", std::path::MAIN_SEPARATOR)), format!("dir{}todo.archive.json", std::path::MAIN_SEPARATOR));
+        assert!(matches!(parse_args(args(&["list", "--archived", "done"])), Ok(Command::List(ListFilter { status: Some(status), .. }, ListSource::Archive, ListOutput::Tasks)) if status == StatusFilter::only(Status::Done)));
+    }
+
+    #[test]
+    fn blank_descriptions_are_rejected() {
+        for description in ["", "   ", "\n", "\r\n\t"] {
+            let error = Task::try_new(1, description).unwrap_err();
+            assert_eq!(error.code(), "usage");
+            assert_eq!(error.to_string(), "Description can't be empty.");
+        }
+        let mut task = Task::new(1, "Buy milk".to_string());
+        assert!(task.update_description("  \n ").is_err());
+        assert_eq!(task.description, "Buy milk");
+    }
+
+    #[test]
+    fn descriptions_are_cleaned_up() {
+        assert_eq!(Task::try_new(1, "  Buy\tmilk\n\nand bread \n").unwrap().description, "Buy milk and bread");
+
+        let long = "word ".repeat(2_000);
+        assert_eq!(Task::clean_description(&long).unwrap().chars().count(), 9_999);
+    }
+
+    #[test]
+    fn delete_needs_explicit_yes() {
+        for (answer, expected) in [("y\n", true), ("YES\n", true), ("n\n", false), ("\n", false), ("", false), ("yep\n", false)] {
+            let mut out = vec![];
+            let confirmed = confirm("Task (ID: 1): Buy milk\nDelete this task?", &mut io::Cursor::new(answer), &mut out).unwrap();
+            assert_eq!(confirmed, expected, "{:?}", answer);
+            assert_eq!(String::from_utf8(out).unwrap(), "Task (ID: 1): Buy milk\nDelete this task? [y/N] ");
+        }
+        let mut arguments = args(&["delete", "1", "-y"]);
+        assert!(Options::extract(&mut arguments).unwrap().yes);
+        assert_eq!(arguments, args(&["delete", "1"]));
+    }
+
+    #[test]
+    fn corrupt_database_is_never_overwritten() {
+        let path = temp_db("corrupt");
+        let malformed = [
+            ("truncated", "[{\"id\": 1, \"descr"),
+            ("object instead of a list", "{\"id\": 1}"),
+            ("bad status", "[{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Finished\", \"created_at\": \"2025-01-12T14:02:28Z\"}]"),
+        ];
+        for (problem, contents) in malformed {
+            std::fs::write(&path, contents).unwrap();
+            for command in [&["add", "Start over"][..], &["list"], &["mark-done", "1"]] {
+                let error = run_at(&path, args(command), &Options::default()).unwrap_err();
+                assert!(matches!(error, TaskError::Parse { line: 1, .. }), "{}: {:?}", problem, error);
+                assert!(error.to_string().contains("task-tracker repair"), "{}", error);
+            }
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), contents, "{}", problem);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn repair_moves_a_corrupt_database_aside() {
+        let path = temp_db("repair");
+        let now = timestamp::parse("2025-01-12T14:02:28Z").unwrap();
+        let backup = format!("{}.corrupt-20250112T140228Z", path);
+        std::fs::write(&path, "[{\"id\": 1, \"descr").unwrap();
+        std::fs::write(&backup, "older").unwrap();
+
+        repair(&path, Backend::Json, &Options { dry_run: true, ..Options::default() }, now).unwrap();
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        repair(&path, Backend::Json, &Options::default(), now).unwrap();
+        let moved = std::fs::read_to_string(format!("{}.1", backup)).unwrap();
+        run_at(&path, args(&["add", "Start over"]), &Options::default()).unwrap();
+        // A valid file is left as it is
+        repair(&path, Backend::Json, &Options::default(), now).unwrap();
+        let fresh = read_db(&path).unwrap();
+        let older = std::fs::read_to_string(&backup).unwrap();
+        for file in [&path, &backup, &format!("{}.1", backup)] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(untouched, "[{\"id\": 1, \"descr");
+        assert_eq!(moved, "[{\"id\": 1, \"descr");
+        assert_eq!(older, "older");
+        assert_eq!(fresh.len(), 1);
+        assert!(matches!(parse_args(args(&["repair"])), Ok(Command::Repair)));
+    }
+
+    /// Runs the same commands against a task file and returns what ended up in it and in its archive. 
+    fn run_scenario(file_path: &str) -> (Vec<Task>, Vec<Task>) {
+        let archive = archive_path(file_path);
+        let options = Options { yes: true, ..Options::default() };
+        for command in [
+            &["add", "Buy milk"][..], &["add", "Walk the dog"], &["add", "Call mom"], &["add", "Book hotel", "--parent", "3"],
+            &["mark-done", "1"], &["update", "2", "Walk the cat"], &["priority", "3", "high"], &["note", "3", "Sunday"],
+            &["archive"], &["delete", "2"], &["add", "Water plants"],
+        ] {
+            run_at(file_path, args(command), &options).unwrap();
+        }
+        let backend = Backend::for_path(file_path);
+        let tasks = storage::open(backend, file_path, JsonStyle::Pretty, false).load().unwrap();
+        let archived = storage::open(backend, &archive, JsonStyle::Pretty, false).load().unwrap();
+        for file in [file_path, &archive] {
+            std::fs::remove_file(file).unwrap();
+        }
+        (tasks, archived)
+    }
+
+    #[test]
+    fn backends_behave_the_same() {
+        let (json_tasks, json_archive) = run_scenario(&temp_db("parity"));
+        let (sqlite_tasks, sqlite_archive) = run_scenario(&temp_db("parity").replace(".json", ".db"));
+
+        let summary = |tasks: &[Task]| -> Vec<String> {
+            tasks.iter().map(|task| format!("{} {} [{}] {:?} {:?} {}", task.id, task.description, task.status, task.priority, task.parent, task.notes.len())).collect()
+        };
+        assert_eq!(summary(&json_tasks), summary(&sqlite_tasks));
+        assert_eq!(summary(&json_archive), summary(&sqlite_archive));
+        assert_eq!(json_tasks.iter().map(|task| task.id).collect::<Vec<u32>>(), vec![3, 4, 5]);
+        assert_eq!(json_archive.len(), 1);
+    }
+
+    #[test]
+    fn trash_keeps_deleted_tasks_that_had_the_same_id() {
+        for path in [temp_db("trash-same-id"), temp_db("trash-same-id").replace(".json", ".db")] {
+            let options = Options { yes: true, ..Options::default() };
+            for command in [&["add", "T1"][..], &["delete", "1"], &["add", "T2"], &["delete", "1"]] {
+                run_at(&path, args(command), &options).unwrap();
+            }
+            let trash = trash_path(&path);
+            let trashed = storage::open_trash(Backend::for_path(&path), &trash, JsonStyle::Pretty, false).load().unwrap();
+            for file in [&path, &trash] {
+                std::fs::remove_file(file).unwrap();
+            }
+            assert_eq!(trashed.iter().map(|task| (task.id, task.description.as_str())).collect::<Vec<_>>(), [(1, "T1"), (1, "T2")], "{}", path);
+        }
+    }
+
+    #[test]
+    fn backend_follows_extension_unless_given() {
+        assert_eq!(Backend::for_path("tasks.json"), Backend::Json);
+        assert_eq!(Backend::for_path("tasks.db"), Backend::Sqlite);
+        assert_eq!(Backend::for_path("tasks.sqlite3"), Backend::Sqlite);
+        assert_eq!(archive_path("tasks.work.db"), "archive.work.db");
+
+        let mut arguments = args(&["list", "--backend", "sqlite"]);
+        assert_eq!(Options::extract(&mut arguments).unwrap().backend, Some(Backend::Sqlite));
+        assert!(Options::extract(&mut args(&["list", "--backend", "csv"])).is_err());
+    }
+
+    #[test]
+    fn relative_timestamps() {
+        let now = timestamp::parse("2025-03-10T12:00:00Z").unwrap();
+        let ago = |duration: Duration| timestamp::relative(&(now - duration), &now);
+
+        assert_eq!(ago(Duration::seconds(0)), "just now");
+        assert_eq!(ago(Duration::seconds(59)), "just now");
+        assert_eq!(ago(Duration::seconds(60)), "1 minute ago");
+        assert_eq!(ago(Duration::minutes(5)), "5 minutes ago");
+        assert_eq!(ago(Duration::minutes(90)), "1 hour ago");
+        assert_eq!(ago(Duration::hours(23)), "23 hours ago");
+        assert_eq!(ago(Duration::seconds(86_399)), "23 hours ago");
+        assert_eq!(ago(Duration::seconds(86_400)), "1 day ago");
+        assert_eq!(ago(Duration::days(2)), "2 days ago");
+        assert_eq!(ago(Duration::days(45)), "1 month ago");
+        assert_eq!(ago(Duration::days(800)), "2 years ago");
+        assert_eq!(ago(-Duration::days(3)), "in 3 days");
+        // A clock a little ahead of this one 
+        assert_eq!(ago(-Duration::seconds(20)), "just now");
+    }
+
+    #[test]
+    fn list_shows_relative_times_unless_absolute() {
+        let tasks = || {
+            let mut task = Task::new(1, "Buy milk".to_string());
+            task.created_at = Utc::now() - Duration::days(3);
+            vec![task]
+        };
+
+        let (output, _) = render_list(&ListFilter::default(), tasks(), &Options::default()).unwrap();
+        assert!(output.contains("Created at: 3 days ago\nLast Update: -"), "{}", output);
+        let absolute = Options { absolute: true, ..Options::default() };
+        let (output, _) = render_list(&ListFilter::default(), tasks(), &absolute).unwrap();
+        assert!(!output.contains("ago"));
+    }
+
+    #[test]
+    fn smart_order_puts_open_and_urgent_tasks_first() {
+        let created = |days_ago: i64| Utc::now() - Duration::days(days_ago);
+        let task = |id: u32, status: Status, priority: Option<Priority>, days_ago: i64| {
+            let mut task = Task::new(id, format!("Task {}", id));
+            task.status = status;
+            task.priority = priority;
+            task.created_at = created(days_ago);
+            task
+        };
+        let mut tasks = vec![
+            task(1, Status::Done, Some(Priority::High), 9),
+            task(2, Status::Todo, None, 8),
+            task(3, Status::Todo, Some(Priority::Low), 7),
+            task(4, Status::InProgress, Some(Priority::Low), 6),
+            task(5, Status::Todo, Some(Priority::High), 1),
+            task(6, Status::Todo, Some(Priority::Medium), 10),
+            task(7, Status::InProgress, Some(Priority::High), 2),
+        ];
+        // A tie on status, priority and creation time falls back to the id 
+        let mut twin = task(8, Status::Todo, Some(Priority::Low), 0);
+        twin.created_at = tasks[2].created_at;
+        tasks.insert(0, twin);
+        let order = |tasks: &[Task]| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+
+        let file_order = order(&tasks);
+        SortKey::Smart.sort(&mut tasks);
+        // No priority counts as medium, so 2 comes after the older 6
+        assert_eq!(order(&tasks), [7, 4, 5, 6, 2, 3, 8, 1]);
+        SortKey::Id.sort(&mut tasks);
+        assert_eq!(order(&tasks), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_ne!(file_order, order(&tasks));
+
+        // Sorting is only for display: the file keeps its order
+        let path = temp_db("smart-sort");
+        for (description, priority) in [("Low", "low"), ("High", "high")] {
+            run_at(&path, args(&["add", description, "--priority", priority]), &Options::default()).unwrap();
+        }
+        run_at(&path, args(&["list", "--sort", "smart"]), &Options::default()).unwrap();
+        let stored: Vec<u32> = read_db(&path).unwrap().iter().map(|task| task.id).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stored, [1, 2]);
+        assert!(parse_args(args(&["list", "--sort", "random"])).is_err());
+    }
+
+    #[test]
+    fn pages_come_after_filtering_and_sorting() {
+        let tasks = || {
+            let mut tasks: Vec<Task> = (1..=7).map(|id| Task::new(id, format!("Task {}", id))).collect();
+            for task in tasks.iter_mut().filter(|task| task.id % 3 == 0) {
+                task.update_status(Status::Done);
+            }
+            for task in &mut tasks {
+                task.estimate = Some(10 - task.id);
+            }
+            tasks
+        };
+        let page = |arguments: &[&str]| match parse_args(args(arguments)).unwrap() {
+            Command::List(filter, ..) => render_list(&filter, tasks(), &Options::default()).unwrap(),
+            command => panic!("{:?}", command)
+        };
+        let ids = |output: &str| output.lines().filter_map(|line| line.strip_prefix("ID: ")).map(|line| line[..1].to_string()).collect::<Vec<_>>().join(",");
+
+        // Todo tasks are 1, 2, 4, 5 and 7
+        let (output, count) = page(&["list", "todo", "--limit", "2", "--page", "2"]);
+        assert_eq!(ids(&output), "4,5");
+        assert!(output.ends_with("showing 3–4 of 5\n"), "{}", output);
+        assert_eq!(count, 5);
+        let (output, _) = page(&["list", "todo", "--sort", "estimate", "--limit", "2"]);
+        assert_eq!(ids(&output), "7,5");
+        let (output, _) = page(&["list", "todo", "--limit", "2", "--page", "3"]);
+        assert_eq!(ids(&output), "7");
+        let (output, _) = page(&["list", "todo", "--limit", "2", "--page", "9"]);
+        assert_eq!(ids(&output), "");
+        assert!(output.ends_with("showing none of 5\n"), "{}", output);
+        let (output, _) = page(&["list", "done", "--limit", "0"]);
+        assert_eq!(ids(&output), "3,6");
+        assert!(!output.contains("showing"));
+
+        assert!(parse_args(args(&["list", "--page", "2"])).is_err());
+        assert!(parse_args(args(&["list", "--limit", "5", "--page", "0"])).is_err());
+        assert!(parse_args(args(&["list", "--limit", "-1"])).is_err());
+    }
+
+    #[test]
+    fn iso_times_are_rfc3339_in_utc() {
+        let created_at = DateTime::parse_from_rfc3339("2025-01-12T16:02:28.250+02:00").unwrap().with_timezone(&Utc);
+        assert_eq!(timestamp::iso(&created_at), "2025-01-12T14:02:28Z");
+
+        let mut task = Task::new(1, "Buy milk".to_string());
+        task.created_at = created_at;
+        task.update_due(created_at + Duration::days(1), created_at);
+        task.add_note("Oat milk".to_string(), created_at);
+        let iso = Options { iso: true, ..Options::default() };
+        let (output, _) = render_list(&ListFilter::default(), vec![task.clone()], &iso).unwrap();
+        assert!(output.contains("Created at: 2025-01-12T14:02:28Z\nLast Update: 2025-01-12T14:02:28Z"), "{}", output);
+        assert!(output.contains("Due: 2025-01-13T14:02:28Z"), "{}", output);
+        assert!(output.contains("- [2025-01-12T14:02:28Z] Oat milk"), "{}", output);
+        let shown = render_show(1, &[task], &iso).unwrap();
+        assert!(shown.contains("Created at:   2025-01-12T14:02:28Z"), "{}", shown);
+        assert!(Options::extract(&mut args(&["list", "--iso"])).unwrap().iso);
+    }
+
+    /// A task with every optional field set, for round trips. 
+    fn full_task() -> Task {
+        let now = timestamp::parse("2025-03-01T09:00:00Z").unwrap();
+        let mut task = Task::new(2, "Plan \"trip\": flights, hotel".to_string());
+        task.created_at = now;
+        task.update_due(now + Duration::days(7), now);
+        task.add_reminder(60).unwrap();
+        task.recurrence = Some(Recurrence::EveryNDays(3));
+        task.parent = Some(1);
+        task.priority = Some(Priority::High);
+        task.status = Status::Done;
+        task.completed_at = Some(now);
+        task.time_spent = 5_400;
+        task.started_at = Some(now);
+        task.add_note("Window seat\nNo layovers".to_string(), now);
+        task.tags = vec!["travel".to_string(), "2025".to_string()];
+        task
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for extension in ["json", "toml", "yaml", "yml"] {
+            let path = temp_db(&format!("format-{}", extension)).replace(".json", &format!(".{}", extension));
+            create_db(&path).unwrap();
+            let empty = read_db(&path).unwrap();
+
+            let minimal = Task::new(1, "Buy milk".to_string());
+            let tasks = vec![minimal, full_task()];
+            write_db(&path, &tasks, JsonStyle::Pretty).unwrap();
+            let loaded = read_db(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(empty.is_empty(), "{}", extension);
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&tasks).unwrap(), "{}", extension);
+            assert!(loaded[0].updated_at.is_none() && loaded[0].priority.is_none() && loaded[0].notes.is_empty());
+        }
+    }
+
+    #[test]
+    fn hand_written_toml_and_yaml_load() {
+        let toml = "[[tasks]]\nid = 1\ndescription = \"Buy milk\"\nstatus = \"Todo\"\ncreated_at = \"2025-03-01T09:00:00Z\"\n";
+        let tasks = toml::from_str::<Envelope>(toml).unwrap().tasks;
+        assert_eq!((tasks[0].id, tasks[0].description.as_str()), (1, "Buy milk"));
+
+        let yaml = "- id: 1\n  description: Buy milk\n  status: InProgress\n  created_at: 2025-03-01T10:00:00Z\n";
+        let tasks: Vec<Task> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(tasks[0].status, Status::InProgress);
+
+        let path = temp_db("format-bad").replace(".json", ".toml");
+        std::fs::write(&path, "tasks = [\n  { id = 1,\n").unwrap();
+        let error = read_db(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.code(), "parse");
+        assert!(matches!(error, TaskError::Parse { line, .. } if line > 0));
+    }
+
+    #[test]
+    fn move_tasks_to_front_end_and_middle() {
+        let ids = |tasks: &[Task]| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        let mut tasks: Vec<Task> = (1..=5).map(|id| Task::new(id, format!("Task {}", id))).collect();
+
+        assert_eq!(Task::move_to(&mut tasks, 4, 1), Some(1));
+        assert_eq!(ids(&tasks), [4, 1, 2, 3, 5]);
+        assert_eq!(Task::move_to(&mut tasks, 1, 99), Some(5));
+        assert_eq!(ids(&tasks), [4, 2, 3, 5, 1]);
+        assert_eq!(Task::move_to(&mut tasks, 5, 2), Some(2));
+        assert_eq!(ids(&tasks), [4, 5, 2, 3, 1]);
+        assert_eq!(tasks.iter().map(|task| task.order).collect::<Vec<_>>(), [1, 2, 3, 4, 5].map(Some));
+        assert_eq!(Task::move_to(&mut tasks, 9, 1), None);
+
+        // New tasks go to the end, and keep getting new ids
+        tasks.push(Task::new(Task::next_id(&tasks), "Task 6".to_string()));
+        tasks.reverse();
+        Task::sort(&mut tasks);
+        assert_eq!(ids(&tasks), [4, 5, 2, 3, 1, 6]);
+        assert!(parse_args(args(&["move", "3", "0"])).is_err());
+    }
+
+    #[test]
+    fn moved_order_is_saved() {
+        let path = temp_db("move");
+        for description in ["Buy milk", "Walk the dog", "Call mom"] {
+            run_at(&path, args(&["add", description]), &Options::default()).unwrap();
+        }
+        run_at(&path, args(&["move", "3", "1"]), &Options::default()).unwrap();
+        run_at(&path, args(&["add", "Water plants"]), &Options::default()).unwrap();
+        run_at(&path, args(&["move", "1", "3"]), &Options::default()).unwrap();
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn version_one_files_are_migrated() {
+        let version_one = [
+            ("json", "[{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Todo\", \"created_at\": \"2025-01-12T14:02:28Z\"}]"),
+            ("toml", "[[tasks]]\nid = 1\ndescription = \"Buy milk\"\nstatus = \"Todo\"\ncreated_at = \"2025-01-12T14:02:28Z\"\n"),
+            ("yaml", "- id: 1\n  description: Buy milk\n  status: Todo\n  created_at: 2025-01-12T14:02:28Z\n"),
+        ];
+        for (extension, contents) in version_one {
+            let path = temp_db("migrate").replace(".json", &format!(".{}", extension));
+            std::fs::write(&path, contents).unwrap();
+            let (version, tasks) = read_document(&path).unwrap();
+            assert_eq!((version, tasks.len()), (1, 1), "{}", extension);
+
+            run_at(&path, args(&["migrate"]), &Options { dry_run: true, ..Options::default() }).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), contents, "{}", extension);
+            run_at(&path, args(&["migrate"]), &Options::default()).unwrap();
+            let (version, tasks) = read_document(&path).unwrap();
+            assert_eq!((version, tasks[0].description.as_str()), (FORMAT_VERSION, "Buy milk"), "{}", extension);
+
+            // Any other save writes the new layout too
+            std::fs::write(&path, contents).unwrap();
+            run_at(&path, args(&["add", "Walk the dog"]), &Options::default()).unwrap();
+            let (version, tasks) = read_document(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!((version, tasks.len()), (FORMAT_VERSION, 2), "{}", extension);
+        }
+    }
+
+    #[test]
+    fn versioned_files_load() {
+        let task = "{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Todo\", \"created_at\": \"2025-01-12T14:02:28Z\"}";
+        let versioned = [
+            ("json", format!("{{\"version\": 2, \"tasks\": [{}]}}", task), 1),
+            ("json", "{\"version\": 2}".to_string(), 0),
+            ("yaml", "version: 2\ntasks:\n- id: 1\n  description: Buy milk\n  status: Todo\n  created_at: 2025-01-12T14:02:28Z\n".to_string(), 1),
+        ];
+        for (extension, contents, count) in versioned {
+            let path = temp_db("versioned").replace(".json", &format!(".{}", extension));
+            std::fs::write(&path, &contents).unwrap();
+            let loaded = read_document(&path);
+            std::fs::remove_file(&path).unwrap();
+            let (version, tasks) = loaded.unwrap();
+            assert_eq!((version, tasks.len()), (2, count), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let path = temp_db("newer");
+        let contents = "{\"version\": 3, \"tasks\": [{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Todo\", \"created_at\": \"2025-01-12T14:02:28Z\", \"colour\": \"red\"}]}";
+        std::fs::write(&path, contents).unwrap();
+        let error = run_at(&path, args(&["add", "Walk the dog"]), &Options::default()).unwrap_err();
+        let repaired = run_at(&path, args(&["repair"]), &Options::default());
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(error, TaskError::UnsupportedVersion { version: 3, .. }));
+        assert!(error.to_string().contains("upgrade"), "{}", error);
+        assert!(repaired.is_err());
+        assert_eq!(untouched, contents);
+    }
+
+    #[test]
+    fn duplicate_ids_are_detected_and_reassigned() {
+        let path = temp_db("duplicates");
+        let task = |id: u32, description: &str| format!("{{\"id\": {}, \"description\": \"{}\", \"status\": \"Todo\", \"created_at\": \"2025-01-12T14:02:28Z\"}}", id, description);
+        let tasks = [task(1, "Buy milk"), task(2, "Walk the dog"), task(1, "Call mom"), task(2, "Water plants"), task(1, "Book hotel"), task(3, "Pay rent")];
+        let contents = format!("{{\"version\": 2, \"tasks\": [{}]}}", tasks.join(", "));
+        std::fs::write(&path, &contents).unwrap();
+
+        let error = run_at(&path, args(&["mark-done", "1"]), &Options::default()).unwrap_err();
+        run_at(&path, args(&["doctor"]), &Options { dry_run: true, ..Options::default() }).unwrap();
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        run_at(&path, args(&["fsck"]), &Options::default()).unwrap();
+        let repaired = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(&error, TaskError::DuplicateIds { ids, .. } if ids == &[1, 2]));
+        assert!(error.to_string().contains("1, 2"), "{}", error);
+        assert_eq!(untouched, contents);
+        let ids: Vec<(u32, &str)> = repaired.iter().map(|task| (task.id, task.description.as_str())).collect();
+        assert_eq!(ids, [(1, "Buy milk"), (2, "Walk the dog"), (4, "Call mom"), (5, "Water plants"), (6, "Book hotel"), (3, "Pay rent")]);
+    }
+
+    #[test]
+    fn commands_that_rewrite_the_file_deal_with_its_journal_first() {
+        let path = temp_db("rewrite-journal");
+        let journal = storage::journal_path(&path);
+        let options = Options { journal: true, ..Options::default() };
+        // Two tasks sharing an ID in the file, and a third added in the journal
+        write_db(&path, &[Task::new(1, "Buy milk".to_string()), Task::new(1, "Walk the dog".to_string())], JsonStyle::Pretty).unwrap();
+        let mut tasks = storage::open(Backend::Json, &path, JsonStyle::Pretty, true);
+        let mut loaded = tasks.load().unwrap();
+        loaded.push(Task::new(2, "Call mom".to_string()));
+        tasks.save(&loaded).unwrap();
+        assert!(Path::new(&journal).exists());
+
+        run_at(&path, args(&["doctor"]), &Options { yes: true, ..options.clone() }).unwrap();
+        let repaired = read_db(&path).unwrap();
+        let doctored_journal = Path::new(&journal).exists();
+
+        // A journal of changes to a corrupt file goes aside with it
+        run_at(&path, args(&["add", "Pay rent"]), &options).unwrap();
+        std::fs::write(&path, "[{\"id\": 1, \"descr").unwrap();
+        let now = timestamp::parse("2025-01-12T14:02:28Z").unwrap();
+        let backup = format!("{}.corrupt-20250112T140228Z", path);
+        repair(&path, Backend::Json, &Options::default(), now).unwrap();
+        let fresh = storage::open(Backend::Json, &path, JsonStyle::Pretty, false).load().unwrap();
+        let moved = std::fs::read_to_string(format!("{}.journal", backup)).unwrap();
+        for file in [&path, &backup, &format!("{}.journal", backup)] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        let ids: Vec<(u32, &str)> = repaired.iter().map(|task| (task.id, task.description.as_str())).collect();
+        assert_eq!(ids, [(1, "Buy milk"), (3, "Walk the dog"), (2, "Call mom")]);
+        assert!(!doctored_journal);
+        assert!(fresh.is_empty());
+        assert!(moved.contains("Pay rent"));
+    }
+
+    #[test]
+    fn reset_ids_numbers_tasks_from_one_and_follows_references() {
+        let mut tasks: Vec<Task> = [(17, "Plan trip"), (4, "Book flights"), (203, "Pick seats"), (1, "Pack")]
+            .iter()
+            .map(|(id, description)| Task::new(*id, description.to_string()))
+            .collect();
+        tasks[1].parent = Some(17);
+        tasks[2].parent = Some(4);
+        tasks[3].depends_on = vec![203, 17];
+
+        let renumbered = reset_ids(&mut tasks);
+        let ids: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, (1..=4).collect::<Vec<_>>());
+        assert_eq!(renumbered, [(17, 1), (4, 2), (203, 3), (1, 4)]);
+        assert_eq!(tasks[1].parent, Some(1));
+        assert_eq!(tasks[2].parent, Some(2));
+        assert_eq!(tasks[3].depends_on, [3, 1]);
+        // Already contiguous
+        assert!(reset_ids(&mut tasks).is_empty());
+    }
+
+    #[test]
+    fn reset_ids_needs_yes() {
+        let path = temp_db("reset-ids");
+        let trash = trash_path(&path);
+        let yes = Options { yes: true, ..Options::default() };
+        for description in ["Buy milk", "Walk the dog", "Call mom", "Pay rent"] {
+            run_at(&path, args(&["add", description]), &yes).unwrap();
+        }
+        run_at(&path, args(&["block", "4", "--on", "3"]), &yes).unwrap();
+        run_at(&path, args(&["delete", "1,2"]), &yes).unwrap();
+
+        let refused = run_at(&path, args(&["reset-ids"]), &Options::default());
+        run_at(&path, args(&["reset-ids"]), &Options { dry_run: true, ..Options::default() }).unwrap();
+        let untouched: Vec<u32> = read_db(&path).unwrap().iter().map(|task| task.id).collect();
+        run_at(&path, args(&["reset-ids"]), &yes).unwrap();
+        let reset: Vec<(u32, Vec<u32>)> = read_db(&path).unwrap().into_iter().map(|task| (task.id, task.depends_on)).collect();
+        for file in [&path, &trash] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert!(matches!(refused, Err(TaskError::Usage(_))));
+        assert_eq!(untouched, [3, 4]);
+        assert_eq!(reset, [(1, vec![]), (2, vec![1])]);
+    }
+
+    #[test]
+    fn last_and_description_text_name_tasks() {
+        let tasks: Vec<Task> = ["Buy milk", "Walk the dog", "Buy bread", "Call mom"]
+            .iter()
+            .enumerate()
+            .map(|(index, description)| Task::new(index as u32 + 1, description.to_string()))
+            .collect();
+
+        assert!(matches!(parse_args(args(&["mark-done", "last", "2,last"])), Ok(Command::Mark(_, ids)) if ids == [TaskRef::Last, TaskRef::Id(2), TaskRef::Last]));
+        assert_eq!(TaskRef::Last.resolve(&tasks).unwrap(), 4);
+        assert_eq!(TaskRef::Text("walk".to_string()).resolve(&tasks).unwrap(), 2);
+        assert_eq!(TaskRef::Text("Buy b".to_string()).resolve(&tasks).unwrap(), 3);
+
+        let error = TaskRef::Text("Buy".to_string()).resolve(&tasks).unwrap_err();
+        assert_eq!(error.code(), "usage");
+        assert!(error.to_string().contains("1 ('Buy milk'), 3 ('Buy bread')"), "{}", error);
+        assert!(TaskRef::Text("Sell".to_string()).resolve(&tasks).is_err());
+        // Anywhere in the description, not just at the start
+        assert_eq!(TaskRef::Text("DOG".to_string()).resolve(&tasks).unwrap(), 2);
+        assert_eq!(TaskRef::Text("mom".to_string()).resolve(&tasks).unwrap(), 4);
+        assert_eq!(TaskRef::Text("read".to_string()).resolve(&tasks).unwrap(), 3);
+        let error = TaskRef::Text("l".to_string()).resolve(&tasks).unwrap_err();
+        assert!(error.to_string().contains("1 ('Buy milk'), 2 ('Walk the dog'), 4 ('Call mom')"), "{}", error);
+        assert!(TaskRef::Text("groceries".to_string()).resolve(&tasks).unwrap_err().to_string().starts_with("No match"));
+        // Numbers are always ids, even if a description contains them
+        let numbered = [Task::new(1, "Call 2 people".to_string()), Task::new(2, "Pay rent".to_string())];
+        assert!(matches!(parse_args(args(&["mark-done", "2"])), Ok(Command::Mark(_, ids)) if ids == [TaskRef::Id(2)]));
+        assert_eq!(TaskRef::Id(2).resolve(&numbered).unwrap(), 2);
+        assert!(matches!(parse_args(args(&["show", "2"])), Ok(Command::Show(TaskRef::Id(2)))));
+        assert!(matches!(parse_args(args(&["show", "rent"])), Ok(Command::Show(TaskRef::Text(text))) if text == "rent"));
+        assert!(parse_args(args(&["show", "1,2"])).is_err());
+        assert!(parse_args(args(&["show", "1", "2"])).is_err());
+        assert!(TaskRef::Last.resolve(&[]).is_err());
+
+        let path = temp_db("last");
+        for description in ["Buy milk", "Walk the dog"] {
+            run_at(&path, args(&["add", description]), &Options::default()).unwrap();
+        }
+        run_at(&path, args(&["mark-done", "last"]), &Options::default()).unwrap();
+        run_at(&path, args(&["update", "buy", "Buy oat milk"]), &Options::default()).unwrap();
+        run_at(&path, args(&["add", "Buy bread"]), &Options::default()).unwrap();
+        let ambiguous = run_at(&path, args(&["delete", "buy"]), &Options { yes: true, ..Options::default() });
+        let unmatched = run_at(&path, args(&["mark-done", "groceries"]), &Options::default());
+        let shown = run_at(&path, args(&["show", "oat"]), &Options::default());
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tasks[1].status, Status::Done);
+        assert_eq!(tasks[0].description, "Buy oat milk");
+        assert_eq!(ambiguous.unwrap_err().code(), "usage");
+        assert_eq!(unmatched.unwrap_err().code(), "usage");
+        assert!(shown.is_ok());
+        assert_eq!(tasks.len(), 3);
+    }
+
+    #[test]
+    fn ndjson_prints_one_task_per_line() {
+        let mut tasks = vec![Task::new(1, "Buy milk".to_string()), Task::new(2, "Walk the dog".to_string()), Task::new(3, "Call mom".to_string())];
+        tasks[2].update_status(Status::Done);
+        tasks[1].add_note("Twice,\nif it rains".to_string(), Utc::now());
+        let options = Options { ndjson: true, ..Options::default() };
+        let (output, count) = render_list(&ListFilter { status: Some(StatusFilter::only(Status::Todo)), ..ListFilter::default() }, tasks, &options).unwrap();
+
+        assert_eq!(count, 2);
+        assert!(output.ends_with('\n') && !output.starts_with('['));
+        let lines: Vec<Task> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(lines[1].notes[0].text, "Twice,\nif it rains");
+    }
+
+    #[test]
+    fn date_filters_include_the_first_midnight_but_not_the_last() {
+        // A Wednesday afternoon; times are in UTC here, and in local time when the command runs
+        let now = timestamp::parse("2025-06-11T15:30:00Z").unwrap();
+        let at = |value: &str| timestamp::parse(value).unwrap();
+        let task = |id: u32, created_at: &str, updated_at: Option<&str>| {
+            let mut task = Task::new(id, format!("Task {}", id));
+            task.created_at = at(created_at);
+            task.updated_at = updated_at.map(at);
+            task
+        };
+        let tasks = [
+            task(1, "2025-06-08T23:59:59Z", None),
+            task(2, "2025-06-09T00:00:00Z", Some("2025-06-11T00:00:00Z")),
+            task(3, "2025-06-10T23:59:59Z", Some("2025-06-10T23:59:59Z")),
+            task(4, "2025-06-11T00:00:00Z", None),
+            task(5, "2025-06-15T23:59:59Z", None),
+            task(6, "2025-06-16T00:00:00Z", None),
+        ];
+        let ids = |filter: ListFilter| tasks.iter().filter(|task| filter.matches(task, &now)).map(|task| task.id).collect::<Vec<_>>();
+        let created = |period| ListFilter { created: Some(period), ..ListFilter::default() };
+        let day = |value: &str| timestamp::parse_day(value).unwrap();
+
+        assert_eq!(ids(created(Period::Today)), [4]);
+        assert_eq!(ids(created(Period::ThisWeek)), [2, 3, 4, 5]);
+        assert_eq!(ids(ListFilter { updated: Some(Period::Today), ..ListFilter::default() }), [2]);
+        assert_eq!(ids(created(Period::Between(Some(day("2025-06-09")), Some(day("2025-06-10"))))), [2, 3]);
+        assert_eq!(ids(ListFilter { status: Some(StatusFilter::only(Status::Done)), ..created(Period::ThisWeek) }), Vec::<u32>::new());
+
+        assert!(matches!(parse_args(args(&["list", "todo", "--created", "today"])), Ok(Command::List(ListFilter { status: Some(status), created: Some(Period::Today), .. }, ListSource::Tasks, ListOutput::Tasks)) if status == StatusFilter::only(Status::Todo)));
+        assert!(matches!(parse_args(args(&["list", "--updated", "this-week"])), Ok(Command::List(ListFilter { updated: Some(Period::ThisWeek), .. }, ListSource::Tasks, ListOutput::Tasks))));
+        assert!(parse_args(args(&["list", "--created", "yesterday"])).is_err());
+        assert!(parse_args(args(&["list", "--created", "today", "--since", "2025-06-01"])).is_err());
+    }
+
+    #[test]
+    fn descriptions_are_searched_as_text_or_regex() {
+        let now = Utc::now();
+        let tasks: Vec<Task> = ["JIRA-123 fix login", "Review jira-45", "Ask about JIRA-7", "Café crème", "Écrire le café"].iter().enumerate().map(|(index, description)| {
+            let mut task = Task::new(index as u32 + 1, description.to_string());
+            task.status = if index == 1 { Status::Done } else { Status::Todo };
+            task
+        }).collect();
+        let found = |arguments: &[&str]| -> Vec<u32> {
+            let Ok(Command::List(filter, ..)) = parse_args(args(arguments)) else { panic!("Expected List command for {:?}", arguments) };
+            tasks.iter().filter(|task| filter.matches(task, &now)).map(|task| task.id).collect()
+        };
+
+        // Anchors only match at the start (or end) of the description
+        assert_eq!(found(&["search", "--regex", r"^JIRA-\d+"]), [1]);
+        assert_eq!(found(&["search", "--regex", r"^jira-\d+", "-i"]), [1]);
+        assert_eq!(found(&["search", "-i", "--regex", r"jira-\d+$"]), [2, 3]);
+        // Plain text is not a pattern, and `search` includes done tasks
+        assert_eq!(found(&["search", "JIRA-"]), [1, 3]);
+        assert!(found(&["search", "^JIRA"]).is_empty());
+        // Unicode letters, with their case folded by -i
+        assert_eq!(found(&["search", "café"]), [5]);
+        assert_eq!(found(&["search", "-i", "CAFÉ"]), [4, 5]);
+        assert_eq!(found(&["search", "--regex", r"^\w+ crème$"]), [4]);
+        // On `list`, alongside the other filters
+        assert_eq!(found(&["list", "todo", "--regex", "(?i)jira"]), [1, 3]);
+
+        let error = parse_args(args(&["search", "--regex", "JIRA-(\\d+"])).unwrap_err();
+        assert!(error.starts_with("Invalid pattern: regex parse error:") && error.contains("unclosed group"), "{}", error);
+        assert!(parse_args(args(&["list", "-i"])).is_err());
+        assert!(parse_args(args(&["search", "two", "words"])).is_err());
+        assert_eq!(found(&["search", "--", "-i"]), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn completed_today_is_the_local_day_of_completion() {
+        let now = timestamp::parse("2025-06-11T15:30:00Z").unwrap();
+        let task = |id: u32, completed_at: Option<&str>| {
+            let mut task = Task::new(id, format!("Task {}", id));
+            task.created_at = timestamp::parse("2025-06-01T09:00:00Z").unwrap();
+            if let Some(completed_at) = completed_at {
+                task.status = Status::Done;
+                task.completed_at = Some(timestamp::parse(completed_at).unwrap());
+            }
+            task
+        };
+        let tasks = [
+            task(1, Some("2025-06-10T23:59:59Z")),
+            task(2, Some("2025-06-11T00:00:00Z")),
+            task(3, Some("2025-06-11T15:00:00Z")),
+            task(4, None),
+            task(5, Some("2025-06-12T00:00:00Z")),
+        ];
+        let filter = match parse_args(args(&["list", "completed-today"])).unwrap() {
+            Command::List(filter, ..) => filter,
+            command => panic!("{:?}", command)
+        };
+        let ids: Vec<u32> = tasks.iter().filter(|task| filter.matches(task, &now)).map(|task| task.id).collect();
+        // Yesterday's is left out, and so is the open task
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(filter.status, Some(StatusFilter::ALL));
+
+        // A reopened task is no longer completed at all
+        let mut reopened = tasks[2].clone();
+        reopened.update_status(Status::Todo);
+        assert!(!filter.matches(&reopened, &now));
+        assert!(matches!(parse_args(args(&["list", "--completed", "this-week", "todo"])), Ok(Command::List(ListFilter { completed: Some(Period::ThisWeek), status: Some(status), .. }, ..)) if status == StatusFilter::only(Status::Todo)));
+    }
+
+    #[test]
+    fn report_ranges_end_today() {
+        // A Wednesday
+        let today = timestamp::parse_day("2025-06-11").unwrap();
+        let day = |value: &str| timestamp::parse_day(value).unwrap();
+        let range = |arguments: &[&str]| match parse_args(args(arguments)) {
+            Ok(Command::Report(range)) => Ok(range.days(today)),
+            Ok(command) => panic!("{:?}", command),
+            Err(error) => Err(error)
+        };
+
+        assert_eq!(range(&["report"]), Ok((day("2025-05-19"), today)));
+        assert_eq!(range(&["report", "--weeks", "1"]), Ok((day("2025-06-09"), today)));
+        assert_eq!(range(&["report", "--from", "2025-01-01", "--to", "2025-03-31"]), Ok((day("2025-01-01"), day("2025-03-31"))));
+        assert_eq!(range(&["report", "--from", "2025-06-01"]), Ok((day("2025-06-01"), today)));
+        for invalid in [&["report", "--weeks", "0"][..], &["report", "--weeks", "2", "--from", "2025-01-01"], &["report", "--to", "2025-01-01"], &["report", "--from", "2025-02-01", "--to", "2025-01-01"], &["report", "--days", "3"]] {
+            assert!(range(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn stale_tasks_were_not_touched_within_the_window() {
+        let now = timestamp::parse("2025-06-11T12:00:00Z").unwrap();
+        let at = |value: &str| timestamp::parse(value).unwrap();
+        let task = |id: u32, created_at: &str, updated_at: Option<&str>| {
+            let mut task = Task::new(id, format!("Task {}", id));
+            task.created_at = at(created_at);
+            task.updated_at = updated_at.map(at);
+            task
+        };
+        let mut tasks = [
+            task(1, "2025-01-01T00:00:00Z", None),
+            task(2, "2025-01-01T00:00:00Z", Some("2025-06-10T09:00:00Z")),
+            // Exactly 30 days is not older than 30 days; a second more is
+            task(3, "2025-01-01T00:00:00Z", Some("2025-05-12T12:00:00Z")),
+            task(4, "2025-01-01T00:00:00Z", Some("2025-05-12T11:59:59Z")),
+            task(5, "2025-06-01T00:00:00Z", None),
+            task(6, "2025-02-01T00:00:00Z", Some("2025-03-01T00:00:00Z")),
+        ];
+        tasks[5].status = Status::Done;
+        let stale = |arguments: &[&str]| -> Vec<u32> {
+            let Ok(Command::List(filter, ..)) = parse_args(args(arguments)) else { panic!("Expected List command") };
+            tasks.iter().filter(|task| filter.matches(task, &now)).map(|task| task.id).collect()
+        };
+
+        assert_eq!(stale(&["list", "all", "--stale", "30"]), [1, 4, 6]);
+        assert_eq!(stale(&["list", "todo", "--stale", "30"]), [1, 4]);
+        assert_eq!(stale(&["list", "all", "--stale", "7"]), [1, 3, 4, 5, 6]);
+        assert_eq!(stale(&["list", "all", "--stale", "0"]), [1, 2, 3, 4, 5, 6]);
+        assert!(parse_args(args(&["list", "--stale", "a month"])).is_err());
+        assert_eq!(parse_args(args(&["list", "--stale", "4000000000"])).unwrap_err(), "Invalid number of days: 4000000000 (too far back)");
+        assert!(parse_args(args(&["count", "--stale", "4000000000"])).is_err());
+        assert!(matches!(days_before(Utc::now(), u32::MAX), Err(TaskError::Usage(_))));
+    }
+
+    #[test]
+    fn prune_removes_done_tasks_before_the_cutoff() {
+        let now = timestamp::parse("2025-06-11T12:00:00Z").unwrap();
+        let cutoff = days_before(now, 30).unwrap();
+        assert_eq!(cutoff, timestamp::parse("2025-05-12T12:00:00Z").unwrap());
+        assert_eq!(days_before(now, 0).unwrap(), now);
+
+        let mut tasks: Vec<Task> = (1..=5).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        for task in &mut tasks[..4] {
+            task.status = Status::Done;
+        }
+        tasks[0].completed_at = Some(cutoff - Duration::seconds(1));
+        tasks[1].completed_at = Some(cutoff);
+        // Done before completion times were recorded
+        tasks[2].updated_at = Some(cutoff - Duration::days(1));
+        tasks[3].completed_at = Some(cutoff - Duration::days(90));
+        tasks[3].status = Status::Todo;
+        tasks[4].updated_at = Some(cutoff - Duration::days(90));
+
+        let pruned = take_prunable(&mut tasks, cutoff);
+        assert_eq!(pruned.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [2, 4, 5]);
+    }
+
+    #[test]
+    fn prune_age_straddles_the_cutoff() {
+        let now = timestamp::parse("2025-06-11T12:00:00Z").unwrap();
+        let cutoff = days_before(now, 7).unwrap();
+        let done = |created_at: &str, updated_at: Option<&str>| {
+            let mut task = Task::new(1, "Task".to_string());
+            task.status = Status::Done;
+            task.created_at = timestamp::parse(created_at).unwrap();
+            task.updated_at = updated_at.map(|updated_at| timestamp::parse(updated_at).unwrap());
+            task
+        };
+        // Never updated: its age comes from when it was created
+        assert!(is_prunable(&done("2025-06-04T11:59:59Z", None), cutoff));
+        assert!(!is_prunable(&done("2025-06-04T12:00:00Z", None), cutoff));
+        assert!(!is_prunable(&done("2025-06-10T00:00:00Z", None), cutoff));
+        // Created long ago, but updated since
+        assert!(!is_prunable(&done("2024-01-01T00:00:00Z", Some("2025-06-05T00:00:00Z")), cutoff));
+        assert!(is_prunable(&done("2024-01-01T00:00:00Z", Some("2025-06-03T23:59:59Z")), cutoff));
+        let mut open = done("2024-01-01T00:00:00Z", None);
+        open.status = Status::InProgress;
+        assert!(!is_prunable(&open, cutoff));
+    }
+
+    #[test]
+    fn pruning_nothing_leaves_the_file_alone() {
+        let path = temp_db("prune");
+        let contents = "[{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Done\", \"created_at\": \"2025-01-12T14:02:28Z\", \"completed_at\": \"2025-01-12T15:00:00Z\"}]";
+        std::fs::write(&path, contents).unwrap();
+        run_at(&path, args(&["prune", "--older-than", "100000"]), &Options::default()).unwrap();
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        run_at(&path, args(&["prune", "--older-than", "1"]), &Options { dry_run: true, ..Options::default() }).unwrap();
+        let previewed = std::fs::read_to_string(&path).unwrap();
+        run_at(&path, args(&["prune", "--older-than", "1"]), &Options::default()).unwrap();
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(untouched, contents);
+        assert_eq!(previewed, contents);
+        assert!(tasks.is_empty());
+        assert!(parse_args(args(&["prune", "--older-than", "-3"])).is_err());
+        assert!(parse_args(args(&["prune"])).is_err());
+        assert!(parse_args(args(&["prune", "--older-than", "4000000000"])).unwrap_err().ends_with("(too far back)"));
+        assert_eq!(explain(&Command::Prune(u32::MAX), &[]), format!("{} day(s) is too far back, so nothing will be pruned.", u32::MAX));
+    }
+
+    #[test]
+    fn since_and_until_filter_by_creation_day() {
+        let tasks: Vec<Task> = ["2025-06-01", "2025-06-15", "2025-06-30", "2025-07-01"]
+            .iter()
+            .enumerate()
+            .map(|(index, day)| {
+                let mut task = Task::new(index as u32 + 1, format!("Task {}", index + 1));
+                task.created_at = timestamp::parse_date(day).unwrap() + Duration::hours(12);
+                task
+            })
+            .collect();
+        let ids = |list: &[&str]| match parse_args(args(list)).unwrap() {
+            Command::List(filter, ..) => tasks.iter().filter(|task| filter.matches(task, &Local::now())).map(|task| task.id).collect::<Vec<_>>(),
+            _ => panic!("Expected List command")
+        };
+
+        assert_eq!(ids(&["list", "--since", "2025-06-15"]), [2, 3, 4]);
+        assert_eq!(ids(&["list", "--until", "2025-06-15"]), [1, 2]);
+        assert_eq!(ids(&["list", "--since", "2025-06-02", "--until", "2025-06-30"]), [2, 3]);
+        assert_eq!(ids(&["list", "--until", "2025-05-31"]), Vec::<u32>::new());
+
+        for invalid in [&["list", "--since", "2025-13-01"][..], &["list", "--until", "yesterday"], &["list", "--since"]] {
+            assert!(parse_args(args(invalid)).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(run_at(&temp_db("since"), args(&["list", "--since", "June"]), &Options::default()).unwrap_err().code(), "usage");
+    }
+
+    #[test]
+    fn list_ends_with_a_summary() {
+        let statuses = [Status::Todo, Status::Done, Status::InProgress, Status::Todo, Status::Done, Status::Done];
+        let tasks: Vec<Task> = statuses
+            .iter()
+            .enumerate()
+            .map(|(index, status)| {
+                let mut task = Task::new(index as u32 + 1, format!("Task {}", index + 1));
+                task.status = *status;
+                task
+            })
+            .collect();
+
+        assert_eq!(summarize(&tasks, 6), "2 todo, 1 in progress, 3 done (6 total)");
+        assert_eq!(summarize(&tasks[..2], 6), "1 todo, 0 in progress, 1 done (2 listed, 6 total)");
+        assert_eq!(summarize(&[], 0), "0 todo, 0 in progress, 0 done (0 total)");
+
+        let done = ListFilter { status: Some(StatusFilter::only(Status::Done)), ..ListFilter::default() };
+        let (output, _) = render_list(&done, tasks, &Options::default()).unwrap();
+        assert!(output.ends_with("------------\n0 todo, 0 in progress, 3 done (3 listed, 6 total)\n"), "{}", output);
+    }
+
+    #[test]
+    fn deleted_tasks_go_to_the_trash() {
+        let path = temp_db("trash");
+        let trash = trash_path(&path);
+        let yes = Options { yes: true, ..Options::default() };
+        for description in ["Buy milk", "Walk the dog", "Call mom"] {
+            run_at(&path, args(&["add", description]), &yes).unwrap();
+        }
+        run_at(&path, args(&["add", "Buy bread", "--parent", "1"]), &yes).unwrap();
+        run_at(&path, args(&["delete", "1", "--cascade"]), &yes).unwrap();
+        run_at(&path, args(&["delete", "3"]), &yes).unwrap();
+        let deleted: Vec<u32> = read_db(&trash).unwrap().iter().map(|task| task.id).collect();
+
+        // The new task takes ID 3, so task 3 comes back under a new ID while task 1 keeps its own
+        run_at(&path, args(&["add", "Water plants"]), &yes).unwrap();
+        run_at(&path, args(&["restore", "3"]), &yes).unwrap();
+        run_at(&path, args(&["restore", "1"]), &yes).unwrap();
+        let tasks: Vec<(u32, String)> = read_db(&path).unwrap().into_iter().map(|task| (task.id, task.description)).collect();
+        let left: Vec<u32> = read_db(&trash).unwrap().iter().map(|task| task.id).collect();
+        run_at(&path, args(&["empty-trash"]), &yes).unwrap();
+        let emptied = read_db(&trash).unwrap();
+        for file in [&path, &trash] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(deleted, [1, 4, 3]);
+        assert_eq!(left, [4]);
+        assert!(emptied.is_empty());
+        let expected = [(2, "Walk the dog"), (3, "Water plants"), (4, "Call mom"), (1, "Buy milk")];
+        assert_eq!(tasks, expected.map(|(id, description)| (id, description.to_string())));
+        assert_eq!(trash_path("tasks.work.json"), "trash.work.json");
+    }
+
+    #[test]
+    fn old_files_without_a_trash_load() {
+        let path = temp_db("no-trash");
+        std::fs::write(&path, "[{\"id\": 1, \"description\": \"Buy milk\", \"status\": \"Todo\", \"created_at\": \"2025-01-12T14:02:28Z\"}]").unwrap();
+        run_at(&path, args(&["list", "--trash"]), &Options::default()).unwrap();
+        run_at(&path, args(&["restore", "1"]), &Options::default()).unwrap();
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tasks.len(), 1);
+        assert!(!Path::new(&trash_path(&path)).exists());
+    }
+
+    #[test]
+    fn copies_share_nothing_with_the_original() {
+        let path = temp_db("copy");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Review PR #123"]), &options).unwrap();
+        for command in [&["priority", "1", "high"][..], &["due", "1", "2030-01-31"], &["remind", "1", "2d"], &["note", "1", "Asked for changes"], &["mark-done", "1"]] {
+            run_at(&path, args(command), &options).unwrap();
+        }
+        run_at(&path, args(&["copy", "1"]), &options).unwrap();
+        run_at(&path, args(&["copy", "1", "Review PR #124"]), &options).unwrap();
+        // Changing a copy leaves the original alone
+        run_at(&path, args(&["note", "3", "Looks good"]), &options).unwrap();
+        run_at(&path, args(&["priority", "3", "low"]), &options).unwrap();
+        let missing = run_at(&path, args(&["copy", "9"]), &options);
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(missing, Err(TaskError::NotFound { id: 9 })));
+        let [original, copy, renamed] = &tasks[..] else { panic!("Expected three tasks") };
+        assert_eq!((original.status, original.priority, original.notes.len()), (Status::Done, Some(Priority::High), 1));
+        assert_eq!((copy.id, copy.description.as_str()), (2, "Review PR #123"));
+        assert_eq!((copy.status, copy.priority, copy.due_at), (Status::Todo, Some(Priority::High), original.due_at));
+        assert!(copy.updated_at.is_none() && copy.completed_at.is_none() && copy.notes.is_empty());
+        assert!(copy.created_at >= original.created_at);
+        assert_eq!(copy.reminders, original.reminders);
+        assert_eq!((renamed.id, renamed.description.as_str(), renamed.priority), (3, "Review PR #124", Some(Priority::Low)));
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Task {
-    id: u32, 
-    description: String,
-    status: Status, 
-    created_at: NaiveDateTime, 
-    updated_at: Option<NaiveDateTime> 
-}
+    #[test]
+    fn task_files_are_looked_for_in_order() {
+        let cwd = std::env::temp_dir().join(format!("task-tracker-resolve-{}", std::process::id()));
+        let data = cwd.join("data");
+        std::fs::create_dir_all(&cwd).unwrap();
+        let resolve = |file, env_file, data_dir: Option<&Path>, project| resolve_path(file, env_file, &cwd, data_dir, project, Backend::Json);
 
-impl Task {
-    fn new(id: u32, description: String) -> Self {
-        Self { id, description, status: Status::Todo, created_at: Local::now().naive_local(), updated_at: None }
+        assert_eq!(resolve(Some("a.json"), Some("b.json"), Some(&data), None), (PathBuf::from("a.json"), PathSource::Flag));
+        assert_eq!(resolve(None, Some("b.json"), Some(&data), None), (PathBuf::from("b.json"), PathSource::Env));
+        assert_eq!(resolve(None, Some(""), Some(&data), None), (data.join("tasks.json"), PathSource::DataDir));
+        assert_eq!(resolve(None, None, Some(&data), Some("work")), (data.join("tasks.work.json"), PathSource::DataDir));
+        assert_eq!(resolve(None, None, None, None), (cwd.join("tasks.json"), PathSource::WorkingDir));
+        // A file already in the working directory keeps being used, and projects go next to it
+        std::fs::write(cwd.join("tasks.json"), "[]").unwrap();
+        assert_eq!(resolve(None, None, Some(&data), None), (cwd.join("tasks.json"), PathSource::WorkingDir));
+        assert_eq!(resolve(None, None, Some(&data), Some("work")), (cwd.join("tasks.work.json"), PathSource::WorkingDir));
+        std::fs::remove_file(cwd.join("tasks.json")).unwrap();
+        std::fs::write(cwd.join("tasks.home.json"), "[]").unwrap();
+        assert_eq!(resolve(None, None, Some(&data), Some("home")), (cwd.join("tasks.home.json"), PathSource::WorkingDir));
+        assert_eq!(resolve(None, None, Some(&data), Some("work")), (data.join("tasks.work.json"), PathSource::DataDir));
+        std::fs::remove_dir_all(&cwd).unwrap();
     }
-    fn update_status(&mut self, status: Status) {
-        self.status = status; 
-        self.updated_at = Some(Local::now().naive_local());
+
+    #[test]
+    fn duplicate_copies_the_details_but_not_the_progress() {
+        let path = temp_db("duplicate");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Water the plants", "--priority", "medium", "--tag", "home", "--tag", "weekly"]), &options).unwrap();
+        for command in [&["start", "1"][..], &["stop", "1"], &["progress", "1", "40"], &["mark-done", "1"], &["duplicate", "1"]] {
+            run_at(&path, args(command), &options).unwrap();
+        }
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let [original, copy] = &tasks[..] else { panic!("Expected two tasks") };
+        assert_eq!((copy.id, copy.description.as_str(), copy.priority, &copy.tags), (2, "Water the plants", Some(Priority::Medium), &original.tags));
+        assert_eq!((copy.status, copy.updated_at, copy.completed_at, copy.started_at), (Status::Todo, None, None, None));
+        assert_eq!((copy.time_spent, copy.progress), (0, 0));
+        assert!(original.completed_at.is_some() && copy.created_at >= original.updated_at.unwrap());
     }
-    fn update_description(&mut self, description: String) {
-        self.description = description;
-        self.updated_at = Some(Local::now().naive_local());
+
+    #[test]
+    fn status_cycles_forward() {
+        assert_eq!(Status::Todo.next(false), Some(Status::InProgress));
+        assert_eq!(Status::InProgress.next(false), Some(Status::Done));
+        assert_eq!(Status::Done.next(false), None);
+        assert_eq!(Status::Todo.next(true), Some(Status::InProgress));
+        assert_eq!(Status::Done.next(true), Some(Status::Todo));
     }
-    fn next_id(tasks: &[Task]) -> u32 {
-        tasks.last().map_or(0, |task| task.id) + 1
+
+    #[test]
+    fn mark_advances_a_task() {
+        let path = temp_db("advance");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        run_at(&path, args(&["mark", "1"]), &options).unwrap();
+        let started = read_db(&path).unwrap()[0].status;
+        run_at(&path, args(&["advance", "1"]), &options).unwrap();
+        let done = read_db(&path).unwrap().remove(0);
+        let refused = run_at(&path, args(&["mark", "1"]), &options);
+        run_at(&path, args(&["mark", "1", "--wrap"]), &options).unwrap();
+        let wrapped = read_db(&path).unwrap()[0].status;
+        let missing = run_at(&path, args(&["mark", "2"]), &options);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(started, Status::InProgress);
+        assert_eq!(done.status, Status::Done);
+        assert!(done.updated_at.is_some() && done.completed_at.is_some());
+        assert!(matches!(refused, Err(TaskError::PreconditionFailed { id: 1, .. })));
+        assert_eq!(wrapped, Status::Todo);
+        assert!(matches!(missing, Err(TaskError::NotFound { id: 2 })));
+        assert!(parse_args(args(&["mark", "1", "--loop"])).is_err());
+    }
+
+    #[test]
+    fn toggle_flips_done() {
+        assert_eq!(Status::Todo.toggled(), Status::Done);
+        assert_eq!(Status::InProgress.toggled(), Status::Done);
+        assert_eq!(Status::Done.toggled(), Status::Todo);
+
+        let path = temp_db("toggle");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        run_at(&path, args(&["add", "Walk the dog"]), &options).unwrap();
+        run_at(&path, args(&["mark-in-progress", "2"]), &options).unwrap();
+        run_at(&path, args(&["toggle", "1"]), &options).unwrap();
+        run_at(&path, args(&["toggle", "2"]), &options).unwrap();
+        let done = read_db(&path).unwrap();
+        run_at(&path, args(&["toggle", "1"]), &options).unwrap();
+        let refused = run_at(&path, args(&["reopen", "1"]), &options);
+        run_at(&path, args(&["uncomplete", "2"]), &options).unwrap();
+        let reopened = read_db(&path).unwrap();
+        let missing = run_at(&path, args(&["toggle", "3"]), &options);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(done.iter().all(|task| task.status == Status::Done && task.completed_at.is_some() && task.updated_at.is_some()));
+        assert!(matches!(refused, Err(TaskError::PreconditionFailed { id: 1, .. })));
+        assert!(reopened.iter().all(|task| task.status == Status::Todo && task.completed_at.is_none()));
+        assert!(reopened[1].updated_at > done[1].updated_at);
+        assert!(matches!(missing, Err(TaskError::NotFound { id: 3 })));
     }
-    fn print(tasks: &[Task]) {
-        for task in tasks {
-            println!("{}", task); 
+
+    #[test]
+    fn progress_is_a_percentage() {
+        assert!(matches!(parse_args(args(&["progress", "1", "40"])), Ok(Command::Progress(40, 1))));
+        assert!(matches!(parse_args(args(&["progress", "1", "100%"])), Ok(Command::Progress(100, 1))));
+        for invalid in ["101", "-5", "255", "1000", "half", ""] {
+            assert!(parse_args(args(&["progress", "1", invalid])).is_err(), "{}", invalid);
         }
     }
-}
 
+    #[test]
+    fn progress_bar_rendering() {
+        assert_eq!(progress_bar(0), "[----------] 0%");
+        assert_eq!(progress_bar(40), "[####------] 40%");
+        assert_eq!(progress_bar(45), "[####------] 45%");
+        assert_eq!(progress_bar(100), "[##########] 100%");
 
-impl Display for Status {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Status::Todo => write!(f, "todo"),
-            Status::Done => write!(f, "done"),
-            Status::InProgress => write!(f, "in progress")
-        }
+        let mut task = Task::new(1, "Write report".to_string());
+        task.progress = 40;
+        assert!(!task.to_string().contains("Progress"));
+        task.status = Status::InProgress;
+        assert!(task.to_string().contains("\nProgress: [####------] 40%"));
     }
-}
 
-impl Display for Task {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let created_at = self.created_at.format("%Y-%m-%d %H:%M:%S").to_string();
-        let updated_at = match self.updated_at {
-            Some(value) => value.format("%Y-%m-%d %H:%M:%S").to_string(),
-            None => "-".to_string()
+    #[test]
+    fn full_progress_marks_done() {
+        let path = temp_db("progress");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Write report"]), &options).unwrap();
+        run_at(&path, args(&["progress", "1", "60"]), &options).unwrap();
+        let partial = read_db(&path).unwrap().remove(0);
+        run_at(&path, args(&["progress", "1", "100"]), &options).unwrap();
+        let full = read_db(&path).unwrap().remove(0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((partial.progress, partial.status), (60, Status::Todo));
+        assert_eq!((full.progress, full.status), (100, Status::Done));
+    }
+
+    #[test]
+    fn parse_update_flags() {
+        let due_at = timestamp::parse_date("2024-08-01").unwrap();
+        let Ok(Command::Update(changes, TaskRef::Id(4))) = parse_args(args(&["update", "4", "--desc", "New text", "--status", "done", "--due", "2024-08-01"])) else {
+            panic!("Expected Update command")
         };
-        write!(f, "------------\nID: {} [{}]\nTask: {}\nCreated at: {}\nLast Update: {}", self.id, self.status, self.description, created_at, updated_at)
+        let expected = TaskChanges { description: Some("New text".to_string()), status: Some(Status::Done), due_at: Some(due_at), priority: None };
+        assert_eq!(changes, expected);
+        let Ok(Command::Update(changes, _)) = parse_args(args(&["update", "4", "--priority", "high"])) else { panic!("Expected Update command") };
+        assert_eq!(changes, TaskChanges { priority: Some(Priority::High), ..TaskChanges::default() });
+
+        let nothing = parse_args(args(&["update", "4"])).unwrap_err();
+        assert!(nothing.contains("--desc") && nothing.contains("--status"), "{}", nothing);
+        for invalid in [&["--colour", "red"][..], &["--status"], &["--status", "someday"], &["--due", "soon"], &["--desc", "a", "--desc", "b"], &["New text", "--status", "done"]] {
+            let values: Vec<&str> = ["update", "4"].iter().chain(invalid).copied().collect();
+            assert!(parse_args(args(&values)).is_err(), "{:?}", invalid);
+        }
     }
-}
 
-/// Creates a new JSON file as a database with an empty list, if such a file does not already exist. 
-fn create_db(file_path: &str) -> Result<(), std::io::Error> {
-    if !Path::new(file_path).exists() {
-        let mut file = File::create(file_path)?;
-        let _ = file.write_all(b"[]")?;
+    #[test]
+    fn update_applies_each_change() {
+        let now = timestamp::parse_date("2024-07-01").unwrap();
+        let due_at = timestamp::parse_date("2024-08-01").unwrap();
+        let changes = [
+            TaskChanges { description: Some("New text".to_string()), ..TaskChanges::default() },
+            TaskChanges { status: Some(Status::Done), ..TaskChanges::default() },
+            TaskChanges { due_at: Some(due_at), ..TaskChanges::default() },
+            TaskChanges { priority: Some(Priority::Low), ..TaskChanges::default() },
+            TaskChanges { description: Some("New text".to_string()), status: Some(Status::InProgress), due_at: Some(due_at), priority: Some(Priority::High) },
+        ];
+        for changes in changes {
+            let mut task = Task::new(1, "Old text".to_string());
+            let expected = (
+                changes.description.clone().unwrap_or("Old text".to_string()),
+                changes.status.unwrap_or(Status::Todo),
+                changes.due_at,
+                changes.priority
+            );
+            changes.apply(&mut task, now).unwrap();
+            assert_eq!((task.description, task.status, task.due_at, task.priority), expected);
+            assert!(task.updated_at.is_some());
+        }
+        let mut task = Task::new(1, "Old text".to_string());
+        assert!(TaskChanges { description: Some(" \n ".to_string()), ..TaskChanges::default() }.apply(&mut task, now).is_err());
     }
-    Ok(())
-}
 
-/// Opens the JSON file and parses the string into a vector of Tasks using serde_json (from_reader can also be used here, but docs say it is usually slower). 
-fn read_db(file_path: &str) -> Result<Vec<Task>, std::io::Error> {
-    let mut file = File::open(file_path)?;
-    let mut data = String::new(); 
-    file.read_to_string(&mut data)?;
-    let tasks: Vec<Task> = serde_json::from_str(&data)?; 
-    Ok(tasks)
-}
+    #[test]
+    fn update_changes_several_fields_at_once() {
+        let path = temp_db("update-flags");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Old text"]), &options).unwrap();
+        run_at(&path, args(&["update", "1", "--desc", "New text", "--status", "done", "--due", "2024-08-01"]), &options).unwrap();
+        let task = read_db(&path).unwrap().remove(0);
+        run_at(&path, args(&["update", "1", "Newer text"]), &options).unwrap();
+        let renamed = read_db(&path).unwrap().remove(0);
+        std::fs::remove_file(&path).unwrap();
 
-/// Overwrites the contents of the database/JSON file, using the current version of the tasks.  
-fn write_db(file_path: &str, tasks: &[Task]) -> Result<(), std::io::Error> {
-    let updated_data = serde_json::to_string_pretty(tasks)?;
-    let mut file =  OpenOptions::new().write(true).truncate(true).open(file_path)?;
-    file.write_all(updated_data.as_bytes())?;
-    Ok(())
-}
+        assert_eq!((task.description.as_str(), task.status), ("New text", Status::Done));
+        assert_eq!(task.due_at, Some(timestamp::parse_date("2024-08-01").unwrap()));
+        assert!(task.completed_at.is_some());
+        assert_eq!((renamed.description.as_str(), renamed.status), ("Newer text", Status::Done));
+    }
 
-/// Parses args into the desired command (min number of args: 1 + 1, max number of args: 3 + 1)
-fn parse_args(args: Vec<String>) -> Result<Command, String> {
-    if args.len() < 2 {
-        Err("Not enough arguments".to_string())
-    } else if args.len() > 4 {
-        Err("Too many arguments".to_string())
-    } else {
-        let cmd = args[1].as_str(); 
-        let requires_id = ["update", "delete", "mark-todo", "mark-done", "mark-in-progress"]; 
-        if requires_id.contains(&cmd) {
-            let id = args
-            .get(2)
-            .ok_or("Not enough arguments".to_string())?
-            .parse::<u32>()
-            .map_err(|error| error.to_string())?;
-            
-            match cmd {
-                "update" => {
-                    let description = args.get(3).ok_or("Not enough arguments".to_string())?; 
-                    return Ok(Command::Update(description.to_string(), id))
-                }, 
-                "delete" => return Ok(Command::Delete(id)), 
-                "mark-todo" => return Ok(Command::Mark(Status::Todo, id)),
-                "mark-done" => return Ok(Command::Mark(Status::Done, id)),
-                "mark-in-progress" => return Ok(Command::Mark(Status::InProgress, id)),
-                _ => return Err("Invalid argument".to_string())
-            };
-        } else if cmd == "add" {
-            let description = args.get(2).ok_or("Not enough arguments".to_string())?;
-            return Ok(Command::Add(description.to_string()))
-        } else if cmd == "list" {
-            let status = args.get(2);
-            if let Some(status) = status {
-                match status.as_str() {
-                    "done" => return Ok(Command::List(Some(Status::Done))),
-                    "todo" => return Ok(Command::List(Some(Status::Todo))),
-                    "in-progress" => return Ok(Command::List(Some(Status::InProgress))), 
-                    _ => return Err("Invalid option".to_string())
-                } 
-            } else {
-                return Ok(Command::List(None))
-            }
-        } else {
-            return Err("Invalid argument".to_string())
+    #[test]
+    fn list_leaves_out_done_tasks_unless_asked() {
+        let path = temp_db("list-open");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        run_at(&path, args(&["mark-done", "1"]), &options).unwrap();
+        let tasks = || read_db(&path).unwrap();
+        let filter = |status| ListFilter { status: Some(status), ..ListFilter::default() };
+        let (open, open_count) = render_list(&filter(StatusFilter::OPEN), tasks(), &options).unwrap();
+        let (_, all_count) = render_list(&filter(StatusFilter::ALL), tasks(), &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((open.as_str(), open_count), ("All tasks are done; run `list all` to see them\n", 0));
+        assert_eq!(all_count, 1);
+        assert!(matches!(parse_args(args(&["list", "all"])), Ok(Command::List(ListFilter { status: Some(StatusFilter::ALL), .. }, ..))));
+        assert!(matches!(parse_args(args(&["list"])), Ok(Command::List(ListFilter { status: None, .. }, ..))));
+        assert!(StatusFilter::OPEN.matches(Status::InProgress) && !StatusFilter::OPEN.matches(Status::Done));
+    }
+
+    #[test]
+    fn statuses_can_be_combined_or_negated() {
+        let parse = |value: &str| value.parse::<StatusFilter>();
+        assert_eq!(parse("todo"), Ok(StatusFilter::only(Status::Todo)));
+        assert_eq!(parse("todo,in-progress"), Ok(StatusFilter::OPEN));
+        assert_eq!(parse("not-done"), Ok(StatusFilter::OPEN));
+        assert_eq!(parse("Done, WIP"), Ok(StatusFilter { todo: false, in_progress: true, done: true }));
+        assert_eq!(parse("not-done,done"), Ok(StatusFilter::ALL));
+        assert_eq!(parse("todo,todo"), Ok(StatusFilter::only(Status::Todo)));
+        assert_eq!(parse("todo,blocked").unwrap_err(), "Invalid status: 'blocked', expected one of: all, not-done, todo, in-progress, done");
+        assert!(parse("todo,").unwrap_err().starts_with("Invalid status: ''"));
+
+        let todo_or_done = parse("todo,done").unwrap();
+        assert_eq!(todo_or_done.statuses(), [Status::Todo, Status::Done]);
+        assert_eq!(todo_or_done.to_string(), "todo or done");
+        let mut tasks: Vec<Task> = (1..=3).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        tasks[1].status = Status::InProgress;
+        tasks[2].status = Status::Done;
+        let (output, count) = render_list(&ListFilter { status: Some(todo_or_done), ..ListFilter::default() }, tasks.clone(), &Options::default()).unwrap();
+        assert_eq!(count, 2);
+        assert!(output.contains("Task 1") && !output.contains("Task 2") && output.contains("Task 3"), "{}", output);
+        let (output, _) = render_list(&ListFilter { status: Some(todo_or_done), ..ListFilter::default() }, vec![tasks[1].clone()], &Options::default()).unwrap();
+        assert_eq!(output, "No tasks found with the status todo or done\n");
+        assert!(matches!(parse_args(args(&["list", "todo,wip"])), Ok(Command::List(ListFilter { status: Some(StatusFilter::OPEN), .. }, ..))));
+        assert!(parse_args(args(&["list", "todo,started"])).unwrap_err().contains("'started'"));
+    }
+
+    #[test]
+    fn filter_tasks_picks_out_the_matching_tasks() {
+        let now = Utc::now();
+        let mut tasks: Vec<Task> = (1..=5).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        tasks[1].status = Status::InProgress;
+        tasks[2].status = Status::Done;
+        tasks[3].status = Status::Done;
+        let ids = |status: Option<StatusFilter>| -> Vec<u32> {
+            filter_tasks(&ListFilter { status, ..ListFilter::default() }, &tasks, &now).iter().map(|task| task.id).collect()
+        };
+
+        assert_eq!(ids(None), [1, 2, 3, 4, 5]);
+        assert_eq!(ids(Some(StatusFilter::ALL)), [1, 2, 3, 4, 5]);
+        assert_eq!(ids(Some(StatusFilter::OPEN)), [1, 2, 5]);
+        assert_eq!(ids(Some(StatusFilter::only(Status::Todo))), [1, 5]);
+        assert_eq!(ids(Some(StatusFilter::only(Status::InProgress))), [2]);
+        assert_eq!(ids(Some(StatusFilter::only(Status::Done))), [3, 4]);
+        assert_eq!(ids(Some(StatusFilter::NONE)), Vec::<u32>::new());
+
+        // The tasks are borrowed, not copied, and sorting leaves the slice as it was
+        let sorted = filter_tasks(&ListFilter { sort: Some(SortKey::Smart), ..ListFilter::default() }, &tasks, &now);
+        assert_eq!(sorted.iter().map(|task| task.id).collect::<Vec<_>>(), [2, 1, 5, 3, 4]);
+        assert!(std::ptr::eq(sorted[0], &tasks[1]));
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn list_count_prints_the_number_of_matches() {
+        let path = temp_db("count");
+        let options = Options::default();
+        for description in ["Buy milk", "Walk the dog", "Call mom"] {
+            run_at(&path, args(&["add", description]), &options).unwrap();
         }
+        run_at(&path, args(&["mark-done", "2"]), &options).unwrap();
+        let Ok(Command::List(filter, _, ListOutput::Count)) = parse_args(args(&["list", "todo", "--count"])) else { panic!("Expected List command") };
+        let (_, count) = render_list(&filter, read_db(&path).unwrap(), &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(count, 2);
     }
-}
 
-fn list_tasks(status: Option<Status>, tasks: Vec<Task>) {
-    match status {
-        None => Task::print(&tasks),
-        Some(s) => {
-            let filtered_tasks: Vec<Task> = tasks.into_iter().filter(|task| task.status == s).collect(); 
-            if filtered_tasks.is_empty() {
-                println!("No tasks found with the status {}", s)
-            } else {
-                Task::print(&filtered_tasks)
-            }
+    #[test]
+    fn parse_add_flags() {
+        let parse = |values: &[&str]| match parse_args(args(values)) {
+            Ok(Command::Add(description, options)) => Ok((description, options)),
+            Ok(_) => panic!("Expected Add command"),
+            Err(error) => Err(error)
+        };
+        let (description, options) = parse(&["add", "Write report", "--status", "in-progress"]).unwrap();
+        assert_eq!((description.as_str(), options.status), ("Write report", Some(Status::InProgress)));
+        let (description, options) = parse(&["add", "--priority", "high", "Write report"]).unwrap();
+        assert_eq!((description.as_str(), options.priority), ("Write report", Some(Priority::High)));
+        let (description, options) = parse(&["add", "--due", "2030-01-31", "Write report", "--status", "done", "--parent", "2", "--repeat", "weekly"]).unwrap();
+        assert_eq!(description, "Write report");
+        assert_eq!((options.status, options.parent, options.recurrence), (Some(Status::Done), Some(2), Some(Recurrence::Weekly)));
+        assert_eq!(options.due_at, Some(timestamp::parse_date("2030-01-31").unwrap()));
+
+        let invalid = parse(&["add", "Write report", "--status", "started"]).unwrap_err();
+        assert!(invalid.contains("todo, in-progress, done"), "{}", invalid);
+        for invalid in [&["add", "--status", "todo"][..], &["add", "Write report", "Read report"], &["add", "Write report", "--status"], &["add", "Write report", "--colour", "red"]] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
         }
     }
-}
 
-const FILE_PATH: &'static str = "tasks.json"; 
+    #[test]
+    fn add_sets_the_given_fields() {
+        let path = temp_db("add-flags");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Write report", "--status", "in-progress", "--priority", "low"]), &options).unwrap();
+        run_at(&path, args(&["add", "--status", "done", "Send report"]), &options).unwrap();
+        let tasks = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-pub fn run() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-    create_db(FILE_PATH)?;
-    let mut tasks = read_db(FILE_PATH)?; 
-    let parsed_args = parse_args(args)?;
-    match parsed_args {
-        Command::List(status) => list_tasks(status, tasks), 
-        Command::Mark(status, id) => {
-            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
-                task.update_status(status);
-                write_db(FILE_PATH, &tasks)?;
-                println!("Successfully updated task (ID: {}).", id);
-            } else {
-                println!("Error: ID not found.")
-            }
-        }, 
-        Command::Delete(id) => {
-            if let Some(index) = tasks.iter().position(|task| task.id == id) {
-                tasks.remove(index);
-                write_db(FILE_PATH, &tasks)?;
-                println!("Successfully deleted task (ID: {}).", id);
-            }
-        }, 
-        Command::Update(description, id) => {
-            if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
-                task.update_description(description);
-                write_db(FILE_PATH, &tasks)?;
-                println!("Successfully updated task (ID: {}).", id);
-            } else {
-                println!("Error: ID not found.")
+        assert_eq!((tasks[0].status, tasks[0].priority, tasks[0].completed_at), (Status::InProgress, Some(Priority::Low), None));
+        assert_eq!((tasks[1].status, tasks[1].completed_at), (Status::Done, Some(tasks[1].created_at)));
+        assert!(tasks.iter().all(|task| task.updated_at.is_none()));
+    }
+
+    #[test]
+    fn add_flags_combine_in_any_order() {
+        let flags: [&[&str]; 5] = [&["--status", "in-progress"], &["--priority", "high"], &["--tag", "work"], &["--tag", "#urgent"], &["--due", "2025-03-01"]];
+        // Every subset of the flags, each time with the description first and last
+        for subset in 0..(1 << flags.len()) {
+            let chosen: Vec<&str> = (0..flags.len()).filter(|bit| subset & (1 << bit) != 0).flat_map(|bit| flags[bit].iter().copied()).collect();
+            for description_first in [true, false] {
+                let mut arguments = vec!["add"];
+                if description_first {
+                    arguments.push("Fix bug");
+                }
+                arguments.extend(&chosen);
+                if !description_first {
+                    arguments.push("Fix bug");
+                }
+                let Ok(Command::Add(description, options)) = parse_args(args(&arguments)) else { panic!("{:?}", arguments) };
+                let task = options.new_task(7, &description).unwrap();
+
+                assert_eq!(task.description, "Fix bug");
+                assert_eq!(task.status, if subset & 1 != 0 { Status::InProgress } else { Status::Todo }, "{:?}", arguments);
+                assert_eq!(task.priority, (subset & 2 != 0).then_some(Priority::High), "{:?}", arguments);
+                let tags: Vec<&str> = [(4, "work"), (8, "urgent")].iter().filter(|(bit, _)| subset & bit != 0).map(|(_, tag)| *tag).collect();
+                assert_eq!(task.tags, tags, "{:?}", arguments);
+                assert_eq!(task.due_at.is_some(), subset & 16 != 0, "{:?}", arguments);
             }
-        }, 
-        Command::Add(description) => {
-            let id = Task::next_id(&tasks); 
-            let new_task = Task::new(id, description); 
-            tasks.push(new_task); 
-            write_db(FILE_PATH, &tasks)?;
-            println!("Successfully added task (ID: {}).", id);
         }
+
+        let Ok(Command::Add(_, options)) = parse_args(args(&["add", "Fix bug", "--tag", "work", "--tag", "Work"])) else { panic!() };
+        assert_eq!(options.tags, ["work"]);
+        for invalid in [&["add", "Fix bug", "--tag"][..], &["add", "Fix bug", "--tag", "two words"], &["add", "Fix bug", "--tag", "#"], &["add", "Fix bug", "--label", "work"]] {
+            assert!(parse_args(args(invalid)).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(parse_args(args(&["add", "Fix bug", "--label", "work"])).unwrap_err(), "Unknown option: --label");
+        let shown = Task { tags: vec!["work".to_string(), "urgent".to_string()], ..Task::new(1, "Fix bug".to_string()) }.to_string();
+        assert!(shown.ends_with("\nTags: work, urgent"), "{}", shown);
     }
-    Ok(()) 
-}
 
+    #[test]
+    fn task_lines_skip_blanks_and_comments() {
+        let mut input = io::Cursor::new("Buy milk\r\n\r\n# groceries\r\n  Walk the dog  \n\t\n  # indented comment\nCall mom");
+        let lines = read_task_lines(&mut input).unwrap();
+        let expected = [(1, "Buy milk"), (4, "Walk the dog"), (7, "Call mom")];
+        assert_eq!(lines, expected.map(|(number, line)| (number, line.to_string())));
+        assert!(read_task_lines(&mut io::Cursor::new("")).unwrap().is_empty());
+    }
 
-// UNIT TESTS
-#[cfg(test)]
-mod tests {
-    use super::*; 
     #[test]
-    fn file_does_not_exist() {
-        let result = read_db("nonexistent.json"); 
-        assert!(result.is_err())
+    fn parse_add_stdin() {
+        assert!(matches!(parse_args(args(&["add", "--stdin", "--priority", "high"])), Ok(Command::AddFromStdin(AddOptions { priority: Some(Priority::High), .. }))));
+        assert!(parse_args(args(&["add", "--stdin", "Buy milk"])).is_err());
     }
+
+    #[cfg(unix)]
     #[test]
-    fn create_and_read_empty_file() {
-        let result = create_db("test.json"); 
-        assert!(result.is_ok()); 
-        let tasks = read_db("test.json"); 
-        assert!(tasks.is_ok()); 
-        assert!(tasks.unwrap().is_empty()); 
-        // Clean up
-        let result = std::fs::remove_file("test.json"); 
-        assert!(result.is_ok())
+    fn arguments_must_be_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+        let valid = ["task-tracker", "add", "Café"].map(OsString::from);
+        assert_eq!(utf8_args(valid).unwrap(), args(&["add", "Café"]));
+
+        let invalid = [OsString::from("task-tracker"), OsString::from("add"), OsString::from_vec(b"Caf\xe9".to_vec())];
+        let error = utf8_args(invalid).unwrap_err();
+        assert_eq!(error.code(), "usage");
+        assert_eq!(error.to_string(), "Argument 2 is not valid UTF-8: Caf\u{FFFD}");
     }
-    // #[test]
+
     #[test]
-    fn add_task() {
-        let mut tasks = vec![];
-        let description = "New Task".to_string();
-        let id = Task::next_id(&tasks);
-        let new_task = Task::new(id, description.clone());
-        tasks.push(new_task);
+    fn description_length_limit_boundary() {
+        let path = temp_db("length-limit");
+        let options = Options::default();
+        let at_limit = "x".repeat(DEFAULT_MAX_DESCRIPTION_LEN);
+        let over_limit = "é".repeat(DEFAULT_MAX_DESCRIPTION_LEN + 1);
+        run_at(&path, args(&["add", &at_limit]), &options).unwrap();
+        let refused = run_at(&path, args(&["add", &over_limit]), &options);
+        let count = read_db(&path).unwrap().len();
+        std::fs::remove_file(&path).unwrap();
 
-        assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].description, description);
-        assert_eq!(tasks[0].status, Status::Todo);
+        assert_eq!(count, 1);
+        assert!(matches!(refused, Err(TaskError::Usage(message)) if message.contains("10001 characters")));
     }
 
     #[test]
-    fn update_task_description() {
-        let mut task = Task::new(1, "Old Description".to_string());
-        task.update_description("New Description".to_string());
+    fn watch_notices_when_the_file_changes() {
+        let written = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let rewritten = written + std::time::Duration::from_millis(10);
+        assert!(!has_changed(Some((written, 120)), Some((written, 120))));
+        assert!(has_changed(Some((written, 120)), Some((rewritten, 120))));
+        // Saved twice within the same tick of the clock
+        assert!(has_changed(Some((written, 120)), Some((written, 98))));
+        assert!(has_changed(None, Some((written, 120))));
+        assert!(has_changed(Some((written, 120)), None));
+        assert!(!has_changed(None, None));
 
-        assert_eq!(task.description, "New Description");
-        assert!(task.updated_at.is_some());
+        let path = temp_db("watch");
+        assert_eq!(file_stamp(&path), None);
+        run_at(&path, args(&["add", "Buy milk"]), &Options::default()).unwrap();
+        let stamp = file_stamp(&path);
+        assert!(stamp.is_some());
+        assert_eq!(file_stamp(&path), stamp);
+        run_at(&path, args(&["add", "Walk the dog"]), &Options::default()).unwrap();
+        assert!(has_changed(stamp, file_stamp(&path)));
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(parse_args(args(&["watch", "todo"])), Ok(Command::Watch(ListFilter { status: Some(status), .. })) if status == StatusFilter::only(Status::Todo)));
+        assert!(matches!(parse_args(args(&["watch"])), Ok(Command::Watch(filter)) if filter == ListFilter::default()));
+        assert!(parse_args(args(&["watch", "--count"])).is_err());
     }
 
     #[test]
-    fn update_task_status() {
-        let mut task = Task::new(1, "Task".to_string());
-        task.update_status(Status::InProgress);
+    fn interactive_lines_are_split_like_a_shell() {
+        let words = |line: &str| tokenize(line).map(|words| words.join("|"));
+        assert_eq!(words("add \"Buy milk\" --priority high\n"), Ok("add|Buy milk|--priority|high".to_string()));
+        assert_eq!(words("  note 1 'Looks \"fine\" \\o/'  "), Ok("note|1|Looks \"fine\" \\o/".to_string()));
+        assert_eq!(words("update 1 Buy\\ oat\\ milk"), Ok("update|1|Buy oat milk".to_string()));
+        assert_eq!(words("add \"\" x"), Ok("add||x".to_string()));
+        assert_eq!(words("update 1 \"Say \\\"hi\\\"\""), Ok("update|1|Say \"hi\"".to_string()));
+        assert_eq!(words(""), Ok(String::new()));
+        assert!(tokenize("add \"Buy milk").is_err());
+        assert!(tokenize("add milk\\").is_err());
+    }
 
-        assert_eq!(task.status, Status::InProgress);
-        assert!(task.updated_at.is_some());
+    #[test]
+    fn interactive_questions_are_answered_from_the_session() {
+        let path = temp_db("interactive-confirm");
+        let options = Options::default();
+        for description in ["Buy milk", "Walk the dog", "Call mom"] {
+            run_at(&path, args(&["add", description]), &options).unwrap();
+        }
+        let stores = Stores {
+            tasks: storage::open(Backend::Json, &path, JsonStyle::Pretty, false), 
+            archive: storage::open(Backend::Json, &archive_path(&path), JsonStyle::Pretty, false), 
+            trash: storage::open_trash(Backend::Json, &trash_path(&path), JsonStyle::Pretty, false)
+        };
+        // Each question takes the next line of the session as its answer
+        let script = "delete 1\nn\ndelete 2\ny\nadd --stdin\ndelete 3\n";
+        interactive(&mut Input { reader: &mut io::Cursor::new(script), ask: true }, &path, stores, &options).unwrap();
+        let kept: Vec<u32> = read_db(&path).unwrap().iter().map(|task| task.id).collect();
+        for file in [path.clone(), trash_path(&path)] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        // The last delete got no answer, which counts as no
+        assert_eq!(kept, [1, 3]);
     }
 
     #[test]
-    fn delete_task() {
-        let mut tasks = vec![Task::new(1, "Task to be deleted".to_string())];
-        tasks.retain(|task| task.id != 1);
+    fn interactive_session_saves_at_the_end() {
+        let path = temp_db("interactive");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        let stores = |path: &str| Stores {
+            tasks: storage::open(Backend::Json, path, JsonStyle::Pretty, false), 
+            archive: storage::open(Backend::Json, &archive_path(path), JsonStyle::Pretty, false), 
+            trash: storage::open_trash(Backend::Json, &trash_path(path), JsonStyle::Pretty, false)
+        };
+        let script = "add 'Walk the dog'\nmark-done 1\n\nbogus 1\nrepair\nadd \"unterminated\ndelete 2\nlist\nquit\nadd 'Never added'\n";
+        interactive(&mut Input { reader: &mut io::Cursor::new(script), ask: false }, &path, stores(&path), &options).unwrap();
+        let tasks: Vec<(u32, Status)> = read_db(&path).unwrap().iter().map(|task| (task.id, task.status)).collect();
+        let trashed = read_db(&trash_path(&path)).unwrap().len();
 
-        assert!(tasks.is_empty());
+        // Nothing reaches the file until the session ends
+        let mut buffered = storage::Buffered::new(storage::open(Backend::Json, &path, JsonStyle::Pretty, false));
+        let mut loaded = buffered.load().unwrap();
+        loaded.clear();
+        buffered.save(&loaded).unwrap();
+        let before_flush = read_db(&path).unwrap().len();
+        buffered.flush().unwrap();
+        let after_flush = read_db(&path).unwrap().len();
+        for file in [path.clone(), trash_path(&path)] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(tasks, [(1, Status::Done)]);
+        assert_eq!(trashed, 1);
+        assert_eq!((before_flush, after_flush), (1, 0));
+        assert!(matches!(parse_args(args(&["repl"])), Ok(Command::Interactive)));
     }
 
     #[test]
-    fn list_tasks_by_status() {
-        let tasks = vec![
-            Task::new(1, "Task 1".to_string()),
-            Task::new(2, "Task 2".to_string()),
-        ];
-        let filtered_tasks: Vec<Task> = tasks.into_iter().filter(|task| task.status == Status::Todo).collect();
+    fn import_todotxt_skips_bad_lines() {
+        let now = timestamp::parse("2025-01-12T14:02:28Z").unwrap();
+        let mut tasks = vec![Task::new(1, "Already here".to_string())];
+        let data = "(A) 2024-03-01 Call Mom @phone\n\nx 2024-03-05 2024-03-02 Pay rent +home\n2024-13-01 Bad date\nWater plants due:2024-04-01\n+onlytags @here\n";
+        let (imported, skipped) = import_todotxt(&mut tasks, data, &Options::default(), now).unwrap();
 
-        assert_eq!(filtered_tasks.len(), 2);
+        assert_eq!((imported, skipped), (vec![2, 3, 4], 2));
+        let [_, call, rent, water] = &tasks[..] else { panic!("Expected four tasks") };
+        assert_eq!((call.description.as_str(), call.priority, call.status), ("Call Mom", Some(Priority::High), Status::Todo));
+        assert_eq!(call.created_at, timestamp::midnight(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), &Local));
+        assert_eq!((rent.status, rent.completed_at), (Status::Done, Some(timestamp::midnight(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap(), &Local))));
+        assert_eq!((water.created_at, water.due_at), (now, Some(timestamp::midnight(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(), &Local))));
+        assert!(matches!(parse_args(args(&["import", "todotxt", "todo.txt"])), Ok(Command::ImportTodoTxt(path)) if path == "todo.txt"));
+        assert!(parse_args(args(&["import", "csv", "todo.csv"])).is_err());
     }
 
     #[test]
-    fn parse_add_command() {
-        let args = vec!["task-tracker".to_string(), "add".to_string(), "New Task".to_string()];
-        let command = parse_args(args).unwrap();
+    fn export_ics_writes_to_stdout_or_a_file() {
+        assert!(matches!(parse_args(args(&["export", "ics"])), Ok(Command::ExportIcs(None))));
+        assert!(matches!(parse_args(args(&["export", "ics", "tasks.ics"])), Ok(Command::ExportIcs(Some(path))) if path == "tasks.ics"));
+        assert!(parse_args(args(&["export", "csv", "tasks.csv"])).is_err());
+        assert!(parse_args(args(&["export"])).is_err());
+    }
 
-        match command {
-            Command::Add(description) => assert_eq!(description, "New Task"),
-            _ => panic!("Expected Add command"),
+    #[test]
+    fn outstanding_estimate_skips_done_and_unestimated_tasks() {
+        let mut tasks: Vec<Task> = (1..=5).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        tasks[0].estimate = Some(3);
+        tasks[1].estimate = Some(5);
+        tasks[1].status = Status::InProgress;
+        tasks[2].estimate = Some(8);
+        tasks[2].status = Status::Done;
+        tasks[4].status = Status::Done;
+
+        assert_eq!(outstanding_estimate(&tasks), (8, 1));
+        assert_eq!(outstanding_estimate(&[]), (0, 0));
+
+        // More than fits in a u32
+        tasks[2].status = Status::Todo;
+        tasks[2].estimate = Some(4000000000);
+        tasks[3].estimate = Some(4000000000);
+        assert_eq!(outstanding_estimate(&tasks), (8000000008, 0));
+    }
+
+    #[test]
+    fn sort_by_estimate_puts_unestimated_tasks_last() {
+        let mut tasks: Vec<Task> = (1..=5).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        for (task, estimate) in tasks.iter_mut().zip([None, Some(5), Some(1), None, Some(5)]) {
+            task.estimate = estimate;
         }
+        SortKey::Estimate.sort(&mut tasks);
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [3, 2, 5, 1, 4]);
+
+        assert!(matches!(parse_args(args(&["list", "all", "--sort", "estimate"])), Ok(Command::List(ListFilter { sort: Some(SortKey::Estimate), .. }, ..))));
+        assert!(parse_args(args(&["list", "--sort", "size"])).is_err());
+        assert!(matches!(parse_args(args(&["estimate", "2", "8"])), Ok(Command::Estimate(8, 2))));
+        assert!(parse_args(args(&["estimate", "2", "-1"])).is_err());
     }
 
     #[test]
-    fn parse_update_command() {
-        let args = vec!["task-tracker".to_string(), "update".to_string(), "1".to_string(), "Updated Task".to_string()];
-        let command = parse_args(args).unwrap();
+    fn verbose_logs_the_file_counts_and_command() {
+        let lines = describe_run("tasks.json", 3, &Command::Show(TaskRef::Id(2)));
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("task file: ") && lines[0].ends_with("tasks.json"));
+        assert!(Path::new(lines[0].trim_start_matches("task file: ")).is_absolute());
+        assert_eq!(lines[1], "loaded 3 task(s)");
+        assert_eq!(lines[2], "command: Show(Id(2))");
 
-        match command {
-            Command::Update(description, id) => {
-                assert_eq!(description, "Updated Task");
-                assert_eq!(id, 1);
-            },
-            _ => panic!("Expected Update command"),
+        let mut out = Vec::new();
+        verbose_to(&Options::default(), "loaded 3 task(s)", &mut out).unwrap();
+        assert!(out.is_empty());
+        verbose_to(&Options { verbose: true, ..Options::default() }, "loaded 3 task(s)", &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[verbose] loaded 3 task(s)\n");
+
+        let mut args = args(&["list", "--verbose"]);
+        assert!(Options::extract(&mut args).unwrap().verbose);
+        assert_eq!(args, ["task-tracker", "list"]);
+    }
+
+    fn dependent_tasks(edges: &[(u32, u32)]) -> Vec<Task> {
+        let mut tasks: Vec<Task> = (1..=4).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        for &(from, to) in edges {
+            tasks[from as usize - 1].depends_on.push(to);
         }
+        tasks
     }
 
     #[test]
-    fn parse_delete_command() {
-        let args = vec!["task-tracker".to_string(), "delete".to_string(), "1".to_string()];
-        let command = parse_args(args).unwrap();
+    fn dependencies_are_followed_transitively() {
+        let tasks = dependent_tasks(&[(1, 2), (2, 3), (4, 3)]);
+        assert!(depends_on_transitively(&tasks, 1, 2));
+        assert!(depends_on_transitively(&tasks, 1, 3));
+        assert!(!depends_on_transitively(&tasks, 3, 1));
+        assert!(!depends_on_transitively(&tasks, 2, 4));
+        assert!(!depends_on_transitively(&tasks, 1, 4));
+        // A cycle already in the file (e.g. from a hand edit) must not loop forever
+        let looped = dependent_tasks(&[(1, 2), (2, 1)]);
+        assert!(depends_on_transitively(&looped, 1, 1));
+        assert!(!depends_on_transitively(&looped, 1, 3));
+    }
 
-        match command {
-            Command::Delete(id) => assert_eq!(id, 1),
-            _ => panic!("Expected Delete command"),
-        }
+    #[test]
+    fn cycles_and_missing_tasks_are_refused() {
+        let mut tasks = dependent_tasks(&[(1, 2), (2, 3)]);
+        assert!(matches!(add_dependency(&mut tasks, 3, 1), Err(TaskError::PreconditionFailed { id: 3, .. })));
+        assert!(matches!(add_dependency(&mut tasks, 3, 2), Err(TaskError::PreconditionFailed { id: 3, .. })));
+        assert!(matches!(add_dependency(&mut tasks, 4, 4), Err(TaskError::PreconditionFailed { id: 4, .. })));
+        assert!(matches!(add_dependency(&mut tasks, 1, 2), Err(TaskError::PreconditionFailed { id: 1, .. })));
+        assert!(matches!(add_dependency(&mut tasks, 1, 9), Err(TaskError::NotFound { id: 9 })));
+        assert!(matches!(add_dependency(&mut tasks, 9, 1), Err(TaskError::NotFound { id: 9 })));
+        add_dependency(&mut tasks, 1, 3).unwrap();
+        add_dependency(&mut tasks, 4, 1).unwrap();
+        assert_eq!(tasks[0].depends_on, [2, 3]);
+        assert_eq!(tasks[3].depends_on, [1]);
     }
 
     #[test]
-    fn parse_mark_command() {
-        let args = vec!["task-tracker".to_string(), "mark-done".to_string(), "1".to_string()];
-        let command = parse_args(args).unwrap();
+    fn blocked_tasks_cant_be_done() {
+        let path = temp_db("blocked");
+        let options = Options::default();
+        for description in ["Write the report", "Collect the numbers", "Send the report"] {
+            run_at(&path, args(&["add", description]), &options).unwrap();
+        }
+        run_at(&path, args(&["block", "1", "--on", "2"]), &options).unwrap();
+        run_at(&path, args(&["block", "3", "--on", "1"]), &options).unwrap();
+        let cycle = run_at(&path, args(&["block", "2", "--on", "3"]), &options);
+        let refused = [
+            run_at(&path, args(&["mark-done", "1"]), &options),
+            run_at(&path, args(&["toggle", "1"]), &options),
+            run_at(&path, args(&["progress", "1", "100"]), &options),
+            run_at(&path, args(&["update", "1", "--status", "done"]), &options),
+        ];
+        let (output, _) = render_list(&ListFilter::default(), read_db(&path).unwrap(), &options).unwrap();
+        run_at(&path, args(&["mark-done", "3"]), &Options { force: true, ..Options::default() }).unwrap();
+        run_at(&path, args(&["mark-done", "2"]), &options).unwrap();
+        run_at(&path, args(&["mark-done", "1"]), &options).unwrap();
+        let done = read_db(&path).unwrap();
+        run_at(&path, args(&["unblock", "1", "--on", "2"]), &options).unwrap();
+        let not_a_dependency = run_at(&path, args(&["unblock", "1", "--on", "2"]), &options);
+        run_at(&path, args(&["delete", "1"]), &Options { yes: true, ..Options::default() }).unwrap();
+        let after_delete = read_db(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(trash_path(&path)).unwrap();
 
-        match command {
-            Command::Mark(status, id) => {
-                assert_eq!(status, Status::Done);
-                assert_eq!(id, 1);
-            },
-            _ => panic!("Expected Mark command"),
+        assert!(matches!(cycle, Err(TaskError::PreconditionFailed { id: 2, .. })));
+        for result in refused {
+            assert!(matches!(result, Err(TaskError::PreconditionFailed { id: 1, ref message }) if message.contains("blocked by task(s) 2")), "{:?}", result);
         }
+        assert!(output.contains("Depends on: 2\nBlocked by: 2\n"), "{}", output);
+        assert!(done.iter().all(|task| task.status == Status::Done));
+        assert!(matches!(not_a_dependency, Err(TaskError::PreconditionFailed { id: 1, .. })));
+        assert!(after_delete.iter().all(|task| task.depends_on.is_empty()));
+        assert!(parse_args(args(&["block", "1", "2"])).is_err());
+        assert!(matches!(parse_args(args(&["unblock", "1", "--on", "2"])), Ok(Command::Unblock(1, 2))));
     }
 
     #[test]
-    fn parse_list_command() {
-        let args = vec!["task-tracker".to_string(), "list".to_string()];
-        let command = parse_args(args).unwrap();
+    fn assignee_filters() {
+        let mut tasks: Vec<Task> = (1..=3).map(|id| Task::new(id, format!("Task {}", id))).collect();
+        tasks[0].assignee = Some("Alice".to_string());
+        tasks[1].assignee = Some("bob".to_string());
+        let listed = |filter: AssigneeFilter| {
+            let filter = ListFilter { assignee: Some(filter), ..ListFilter::default() };
+            tasks.iter().filter(|task| filter.matches(task, &Local::now())).map(|task| task.id).collect::<Vec<_>>()
+        };
+        assert_eq!(listed(AssigneeFilter::Named("alice".to_string())), [1]);
+        assert_eq!(listed(AssigneeFilter::Named("Bob".to_string())), [2]);
+        assert!(listed(AssigneeFilter::Named("carol".to_string())).is_empty());
+        assert_eq!(listed(AssigneeFilter::Unassigned), [3]);
 
-        match command {
-            Command::List(status) => assert!(status.is_none()),
-            _ => panic!("Expected List command"),
+        let Ok(Command::List(filter, ..)) = parse_args(args(&["list", "todo", "--assignee", "alice"])) else { panic!("Expected List command") };
+        assert_eq!(filter.assignee, Some(AssigneeFilter::Named("alice".to_string())));
+        assert!(matches!(parse_args(args(&["list", "--unassigned"])), Ok(Command::List(ListFilter { assignee: Some(AssigneeFilter::Unassigned), .. }, ..))));
+        assert!(parse_args(args(&["list", "--assignee"])).is_err());
+    }
+
+    #[test]
+    fn assign_and_unassign() {
+        assert!(matches!(parse_args(args(&["assign", "3", "alice"])), Ok(Command::Assign(Some(name), 3)) if name == "alice"));
+        assert!(matches!(parse_args(args(&["unassign", "3"])), Ok(Command::Assign(None, 3))));
+        assert!(parse_args(args(&["assign", "3"])).is_err());
+        assert!(parse_args(args(&["assign", "3", " "])).is_err());
+
+        let path = temp_db("assign");
+        let options = Options::default();
+        run_at(&path, args(&["add", "Buy milk"]), &options).unwrap();
+        run_at(&path, args(&["assign", "1", "alice"]), &options).unwrap();
+        let assigned = read_db(&path).unwrap().remove(0);
+        run_at(&path, args(&["unassign", "1"]), &options).unwrap();
+        let unassigned = read_db(&path).unwrap().remove(0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(assigned.assignee.as_deref(), Some("alice"));
+        assert!(assigned.updated_at.is_some());
+        assert!(assigned.to_string().contains("\nAssigned to: alice"));
+        assert_eq!(unassigned.assignee, None);
+        assert!(unassigned.updated_at >= assigned.updated_at);
+        let old: Task = serde_json::from_str(r#"{"id": 1, "description": "Old task", "status": "Todo", "created_at": "2024-01-01T00:00:00Z"}"#).unwrap();
+        assert_eq!(old.assignee, None);
+    }
+
+    #[test]
+    fn compact_and_pretty_files_read_back_the_same() {
+        let mut tasks: Vec<Task> = (1..=3).map(|id| Task::new(id, format!("Task {}, with \"quotes\" and ünïcode", id))).collect();
+        tasks[1].priority = Some(Priority::High);
+        tasks[1].notes.push(Note { text: "A note".to_string(), created_at: Utc::now() });
+        tasks[2].depends_on = vec![1, 2];
+        let (pretty, compact) = (temp_db("style-pretty"), temp_db("style-compact"));
+        write_db(&pretty, &tasks, JsonStyle::Pretty).unwrap();
+        write_db(&compact, &tasks, JsonStyle::Compact).unwrap();
+        let (pretty_data, compact_data) = (std::fs::read_to_string(&pretty).unwrap(), std::fs::read_to_string(&compact).unwrap());
+        let (from_pretty, from_compact) = (read_db(&pretty).unwrap(), read_db(&compact).unwrap());
+        std::fs::remove_file(&pretty).unwrap();
+        std::fs::remove_file(&compact).unwrap();
+
+        assert_eq!(compact_data.lines().count(), 1);
+        assert!(compact_data.len() < pretty_data.len());
+        assert!(pretty_data.contains("\n  \"tasks\": ["));
+        let json = |tasks: &[Task]| serde_json::to_string(tasks).unwrap();
+        assert_eq!(json(&from_pretty), json(&tasks));
+        assert_eq!(json(&from_compact), json(&tasks));
+    }
+
+    /// Prints how much smaller and faster compact files are; run with `cargo test --release -- --ignored --nocapture`. 
+    #[test]
+    #[ignore]
+    fn compact_files_are_smaller_and_faster() {
+        let tasks: Vec<Task> = (1..=10_000).map(|id| {
+            let mut task = Task::new(id, format!("Task number {} of the benchmark, with a longer description", id));
+            task.tags = vec!["work".to_string()];
+            task
+        }).collect();
+        let mut sizes = vec![];
+        for style in [JsonStyle::Pretty, JsonStyle::Compact] {
+            let path = temp_db(&format!("bench-{:?}", style));
+            let start = std::time::Instant::now();
+            for _ in 0..10 {
+                write_db(&path, &tasks, style).unwrap();
+                assert_eq!(read_db(&path).unwrap().len(), tasks.len());
+            }
+            let size = std::fs::metadata(&path).unwrap().len();
+            std::fs::remove_file(&path).unwrap();
+            println!("{:?}: {} bytes, {:?} per write and read", style, size, start.elapsed() / 10);
+            sizes.push(size);
         }
+        assert!(sizes[1] < sizes[0]);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn commands_and_statuses_ignore_case_and_take_aliases() {
+        let status = |args: &[&str]| match parse_args(args.iter().map(|arg| arg.to_string()).collect()) {
+            Ok(Command::List(ListFilter { status: Some(filter), .. }, ..)) => match filter.statuses()[..] {
+                [status] => Some(status),
+                _ => None
+            },
+            _ => None
+        };
+        assert_eq!(status(&["task-tracker", "LIST", "Done"]), Some(Status::Done));
+        assert_eq!(status(&["task-tracker", "ls", "TODO"]), Some(Status::Todo));
+        for alias in ["in-progress", "in_progress", "inprogress", "wip", "WIP", "In_Progress"] {
+            assert_eq!(status(&["task-tracker", "list", alias]), Some(Status::InProgress), "{}", alias);
+        }
+        for alias in ["done", "complete", "finished", "Finished"] {
+            assert_eq!(status(&["task-tracker", "list", alias]), Some(Status::Done), "{}", alias);
+        }
+        assert!(matches!(parse_args(args(&["List", "ALL"])), Ok(Command::List(ListFilter { status: Some(StatusFilter::ALL), .. }, ..))));
+
+        assert!(matches!(parse_args(args(&["rm", "2"])), Ok(Command::Delete(ids, Subtasks::Refuse)) if ids == [TaskRef::Id(2)]));
+        assert!(matches!(parse_args(args(&["RM", "2", "--cascade"])), Ok(Command::Delete(_, Subtasks::Cascade))));
+        assert!(matches!(parse_args(args(&["done", "3"])), Ok(Command::Mark(Status::Done, ids)) if ids == [TaskRef::Id(3)]));
+        assert!(matches!(parse_args(args(&["Mark-Done", "3"])), Ok(Command::Mark(Status::Done, _))));
+        assert!(matches!(parse_args(args(&["ADVANCE", "3"])), Ok(Command::Advance(3, false))));
+        assert!(matches!(parse_args(args(&["Recur", "3", "daily"])), Ok(Command::SetRepeat(_, 3))));
+        assert!(matches!(parse_args(args(&["update", "3", "--status", "wip"])), Ok(Command::Update(TaskChanges { status: Some(Status::InProgress), .. }, _))));
+        assert!(matches!(parse_args(args(&["HELP"])), Ok(Command::Help)));
+
+        // A status on its own is not a command, except `done`, which is short for mark-done and needs IDs
+        assert!(parse_args(args(&["wip", "3"])).is_err());
+        assert!(parse_args(args(&["done"])).is_err());
+        // Neither names nor statuses are matched by prefix
+        assert!(parse_args(args(&["li"])).is_err());
+        assert!(parse_args(args(&["mark-d", "3"])).is_err());
+        assert!(parse_args(args(&["list", "in"])).is_err());
+        assert!(parse_args(args(&["list", "progress"])).is_err());
+        assert!(parse_args(args(&["list", "--Archived"])).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! An advisory lock around the read-modify-write cycle on the task file.
+//!
+//! The lock is a `<file>.lock` file created with `create_new`, so only one process can hold it at a time. It is
+//! removed when the [`Lock`] is dropped, which also covers commands that fail halfway.
+use std::{fs::{self, OpenOptions}, io::{ErrorKind, Write}, path::Path, process, thread, time::Duration};
+use crate::TaskError;
+
+/// How often a held lock is retried before giving up, and how long to wait in between.
+pub const ATTEMPTS: u32 = 20;
+pub const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub struct Lock {
+    path: String
+}
+
+impl Lock {
+    /// Takes the lock on `file_path`, trying up to `attempts` times while another process holds it.
+    pub fn acquire(file_path: &str, attempts: u32, delay: Duration) -> Result<Lock, TaskError> {
+        let path = format!("{}.lock", file_path);
+        for attempt in 1..=attempts.max(1) {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // The pid only helps whoever has to find out where a stale lock came from
+                    let _ = write!(file, "{}", process::id());
+                    return Ok(Lock { path });
+                },
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    if attempt < attempts {
+                        thread::sleep(delay);
+                    }
+                },
+                Err(error) => return Err(TaskError::io(&path, error))
+            }
+        }
+        Err(TaskError::Locked { path })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if Path::new(&self.path).exists() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquisition_fails_while_held() {
+        let file_path = std::env::temp_dir().join(format!("task-tracker-lock-{}.json", process::id())).to_string_lossy().to_string();
+        let lock = Lock::acquire(&file_path, 1, Duration::ZERO).unwrap();
+        assert!(Path::new(&format!("{}.lock", file_path)).exists());
+
+        let error = Lock::acquire(&file_path, 3, Duration::from_millis(1)).unwrap_err();
+        assert_eq!(error.code(), "locked");
+        assert!(error.to_string().contains("database is locked"));
+
+        drop(lock);
+        assert!(!Path::new(&format!("{}.lock", file_path)).exists());
+        assert!(Lock::acquire(&file_path, 1, Duration::ZERO).is_ok());
+    }
+}
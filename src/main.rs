@@ -1,12 +1,30 @@
-use std::process;
-use task_tracker::run;
+use std::{env, process};
+use task_tracker::{run, utf8_args, Options};
 
 fn main() {
-    if let Err(e) = run() {
-        println!("Application error: {e}");
+    let mut args = match utf8_args(env::args_os()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(1);
+        }
+    };
+    let options = match Options::extract(&mut args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(1);
+        }
+    };
+    if let Err(e) = run(args, &options) {
+        if options.json {
+            eprintln!("{}", e.to_json());
+        } else {
+            eprintln!("Application error: {e}");
+        }
         process::exit(1);
     }
 }
 
 // TODO: things to do 
-// 2. create tests (TDD)
\ No newline at end of file
+// 2. create tests (TDD)
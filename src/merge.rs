@@ -0,0 +1,220 @@
+//! `merge`: combining the task list with a copy of it that was changed separately, e.g. on another computer before a
+//! file sync caught up, into one list.
+//!
+//! A task is the same task on both sides if it has the same ID and was created at the same time. If its two versions
+//! differ, the one updated last is kept (the local one if they were updated at the same time) and the conflict is
+//! reported. Tasks that only share an ID were added separately on each side: the local one keeps the ID and the other
+//! gets a new one.
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use super::Task;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Side {
+    Local,
+    Other
+}
+
+/// A task that differs between the two sides, and the side whose version was kept.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Conflict {
+    pub id: u32,
+    pub kept: Side
+}
+
+#[derive(Debug)]
+pub(crate) struct Merge {
+    /// The local tasks, in their order, followed by those only the other side had.
+    pub tasks: Vec<Task>,
+    /// The tasks only the other side had, by their ID in the merged list.
+    pub added: Vec<u32>,
+    /// Tasks from the other side that got a new ID because a different local task has theirs, as (old, new).
+    pub renumbered: Vec<(u32, u32)>,
+    pub conflicts: Vec<Conflict>
+}
+
+/// When the task was last changed.
+fn last_change(task: &Task) -> DateTime<Utc> {
+    task.updated_at.unwrap_or(task.created_at)
+}
+
+/// Merges the `other` tasks into the `local` ones. Neither list may hold two tasks with the same ID.
+pub(crate) fn merge(local: Vec<Task>, other: Vec<Task>) -> Merge {
+    // New IDs come after those of both sides, so that they can't clash with a task that is yet to be merged
+    let mut next_id = Task::next_id(&local).max(Task::next_id(&other));
+    let positions: HashMap<u32, usize> = local.iter().enumerate().map(|(index, task)| (task.id, index)).collect();
+    let mut renumbered = vec![];
+    for task in &other {
+        if positions.get(&task.id).is_some_and(|&index| local[index].created_at != task.created_at) {
+            renumbered.push((task.id, next_id));
+            next_id += 1;
+        }
+    }
+    let new_ids: HashMap<u32, u32> = renumbered.iter().copied().collect();
+    // References from the other side's tasks are to the other side's IDs
+    let renumber = |task: &mut Task| {
+        let new_id = |id: u32| new_ids.get(&id).copied().unwrap_or(id);
+        task.id = new_id(task.id);
+        task.parent = task.parent.map(new_id);
+        for dependency in &mut task.depends_on {
+            *dependency = new_id(*dependency);
+        }
+    };
+
+    let mut tasks = local;
+    let mut added = vec![];
+    let mut conflicts = vec![];
+    for mut task in other {
+        match positions.get(&task.id) {
+            Some(&index) if tasks[index].created_at == task.created_at => {
+                renumber(&mut task);
+                if tasks[index] == task {
+                    continue;
+                }
+                if last_change(&task) > last_change(&tasks[index]) {
+                    conflicts.push(Conflict { id: task.id, kept: Side::Other });
+                    tasks[index] = task;
+                } else {
+                    conflicts.push(Conflict { id: task.id, kept: Side::Local });
+                }
+            },
+            _ => {
+                renumber(&mut task);
+                // Positions set by `move` on the other side mean nothing here; the task goes at the end
+                task.order = None;
+                added.push(task.id);
+                tasks.push(task);
+            }
+        }
+    }
+    Merge { tasks, added, renumbered, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn task(id: u32, description: &str, created_at: &str) -> Task {
+        let mut task = Task::new(id, description.to_string());
+        task.created_at = at(created_at);
+        task.history.clear();
+        task
+    }
+
+    fn updated(mut task: Task, description: &str, updated_at: &str) -> Task {
+        task.description = description.to_string();
+        task.updated_at = Some(at(updated_at));
+        task
+    }
+
+    fn summary(merge: &Merge) -> Vec<(u32, &str)> {
+        merge.tasks.iter().map(|task| (task.id, task.description.as_str())).collect()
+    }
+
+    #[test]
+    fn identical_tasks_are_kept_once() {
+        let tasks = vec![task(1, "Buy milk", "2025-01-01T09:00:00Z"), task(2, "Walk the dog", "2025-01-01T10:00:00Z")];
+        let merge = merge(tasks.clone(), tasks);
+        assert_eq!(summary(&merge), [(1, "Buy milk"), (2, "Walk the dog")]);
+        assert!(merge.added.is_empty() && merge.renumbered.is_empty() && merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn the_newer_version_of_a_changed_task_is_kept() {
+        let milk = task(1, "Buy milk", "2025-01-01T09:00:00Z");
+        let dog = task(2, "Walk the dog", "2025-01-01T10:00:00Z");
+        let local = vec![updated(milk.clone(), "Buy oat milk", "2025-01-02T09:00:00Z"), updated(dog.clone(), "Walk the dog twice", "2025-01-02T09:00:00Z")];
+        let other = vec![updated(milk, "Buy soy milk", "2025-01-03T09:00:00Z"), updated(dog, "Walk the cat", "2025-01-01T12:00:00Z")];
+        let merge = merge(local, other);
+
+        assert_eq!(summary(&merge), [(1, "Buy soy milk"), (2, "Walk the dog twice")]);
+        assert_eq!(merge.conflicts, [Conflict { id: 1, kept: Side::Other }, Conflict { id: 2, kept: Side::Local }]);
+        assert!(merge.added.is_empty());
+    }
+
+    #[test]
+    fn ties_keep_the_local_version() {
+        let milk = task(1, "Buy milk", "2025-01-01T09:00:00Z");
+        let local = vec![updated(milk.clone(), "Buy oat milk", "2025-01-02T09:00:00Z")];
+        let other = vec![updated(milk.clone(), "Buy soy milk", "2025-01-02T09:00:00Z")];
+        let merge = merge(local, other);
+        assert_eq!(summary(&merge), [(1, "Buy oat milk")]);
+        assert_eq!(merge.conflicts, [Conflict { id: 1, kept: Side::Local }]);
+
+        // Neither side updated it, but they differ anyway (e.g. a file edited by hand)
+        let mut done = milk.clone();
+        done.status = Status::Done;
+        let merge = super::merge(vec![milk], vec![done]);
+        assert_eq!(merge.tasks[0].status, Status::Todo);
+        assert_eq!(merge.conflicts, [Conflict { id: 1, kept: Side::Local }]);
+    }
+
+    #[test]
+    fn tasks_on_one_side_only_are_kept() {
+        let milk = task(1, "Buy milk", "2025-01-01T09:00:00Z");
+        let local = vec![milk.clone(), task(3, "Call mom", "2025-01-01T11:00:00Z")];
+        let other = vec![task(2, "Walk the dog", "2025-01-01T10:00:00Z"), milk];
+        let merge = merge(local, other);
+
+        assert_eq!(summary(&merge), [(1, "Buy milk"), (3, "Call mom"), (2, "Walk the dog")]);
+        assert_eq!(merge.added, [2]);
+        assert!(merge.renumbered.is_empty() && merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn tasks_added_on_both_sides_with_the_same_id_are_both_kept() {
+        let milk = task(1, "Buy milk", "2025-01-01T09:00:00Z");
+        let local = vec![milk.clone(), task(2, "Walk the dog", "2025-01-02T10:00:00Z")];
+        // Task 2 was added on the other side too, with a subtask and a task depending on it
+        let mut subtask = task(3, "Buy a leash", "2025-01-02T11:05:00Z");
+        subtask.parent = Some(2);
+        let mut blocked = task(4, "Go to the park", "2025-01-02T11:10:00Z");
+        blocked.depends_on = vec![1, 2];
+        let other = vec![milk, task(2, "Water the plants", "2025-01-02T11:00:00Z"), subtask, blocked];
+        let merge = merge(local, other);
+
+        // New IDs start after the highest ID on either side (4), so 3 and 4 keep theirs
+        assert_eq!(summary(&merge), [(1, "Buy milk"), (2, "Walk the dog"), (5, "Water the plants"), (3, "Buy a leash"), (4, "Go to the park")]);
+        assert_eq!(merge.renumbered, [(2, 5)]);
+        assert_eq!(merge.added, [5, 3, 4]);
+        assert_eq!(merge.tasks[3].parent, Some(5));
+        assert_eq!(merge.tasks[4].depends_on, [1, 5]);
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn changed_tasks_from_the_other_side_follow_renumbered_ids() {
+        let trip = task(1, "Plan trip", "2025-01-01T09:00:00Z");
+        let local = vec![trip.clone(), task(2, "Walk the dog", "2025-01-02T10:00:00Z")];
+        let mut changed = updated(trip, "Plan the trip", "2025-01-03T09:00:00Z");
+        changed.depends_on = vec![2];
+        let other = vec![changed, task(2, "Book flights", "2025-01-02T11:00:00Z")];
+        let merge = merge(local, other);
+
+        assert_eq!(summary(&merge), [(1, "Plan the trip"), (2, "Walk the dog"), (3, "Book flights")]);
+        assert_eq!(merge.tasks[0].depends_on, [3]);
+        assert_eq!(merge.conflicts, [Conflict { id: 1, kept: Side::Other }]);
+    }
+
+    #[test]
+    fn positions_from_the_other_side_are_dropped() {
+        let mut moved = task(2, "Walk the dog", "2025-01-01T10:00:00Z");
+        moved.order = Some(1);
+        let merge = merge(vec![task(1, "Buy milk", "2025-01-01T09:00:00Z")], vec![moved]);
+        assert_eq!(merge.tasks[1].order, None);
+    }
+
+    #[test]
+    fn either_side_can_be_empty() {
+        let tasks = vec![task(1, "Buy milk", "2025-01-01T09:00:00Z")];
+        assert_eq!(summary(&merge(tasks.clone(), vec![])), [(1, "Buy milk")]);
+        let merge = merge(vec![], tasks);
+        assert_eq!(summary(&merge), [(1, "Buy milk")]);
+        assert_eq!(merge.added, [1]);
+    }
+}
@@ -0,0 +1,136 @@
+//! What commands print about what they did. Every such message goes through [`report`], which writes it the way the
+//! output mode asks for: as a sentence for people, not at all with `--quiet`, or as a porcelain line with
+//! `--porcelain`. The ID of a task `add` created is the exception: `--quiet` and `--json` print it alone, so that a
+//! script can capture it with `id=$(task-tracker -q add ...)`.
+//!
+//! Porcelain lines are a stable interface for scripts: a lowercase verb followed by task IDs (and, for `marked`, the
+//! status), separated by spaces, one line per task, e.g. `added 17` or `marked 3 done`. Messages that don't change
+//! any task (like "The trash is empty.") have no porcelain line.
+use std::io::{self, Write};
+use super::{Options, Status};
+
+/// How commands report what they did.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputMode {
+    /// Sentences, e.g. "Successfully added task (ID: 17)."
+    #[default]
+    Normal,
+    /// Nothing: scripts go by the exit code.
+    Quiet,
+    /// Stable, line-oriented output, e.g. "added 17".
+    Porcelain
+}
+
+/// A message about what a command did, along with its porcelain line(s), if it has any.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Outcome {
+    message: String,
+    porcelain: Vec<String>,
+    /// What `--quiet` and `--json` print instead of the message, if anything.
+    value: Option<String>
+}
+
+impl Outcome {
+    pub fn new(message: String, porcelain: String) -> Self {
+        Outcome { message, porcelain: vec![porcelain], value: None }
+    }
+    /// A message covering several tasks, with a porcelain line for each.
+    pub fn each(message: String, verb: &str, ids: impl IntoIterator<Item = u32>) -> Self {
+        Outcome { message, porcelain: ids.into_iter().map(|id| format!("{} {}", verb, id)).collect(), value: None }
+    }
+    /// A new task; its ID is all `--quiet` and `--json` print.
+    pub fn added(id: u32) -> Self {
+        Outcome { value: Some(id.to_string()), ..Outcome::new(format!("Successfully added task (ID: {}).", id), format!("added {}", id)) }
+    }
+    pub fn updated(id: u32) -> Self {
+        Outcome::new(format!("Successfully updated task (ID: {}).", id), format!("updated {}", id))
+    }
+    /// A task whose status was set, whatever else changed with it.
+    pub fn marked(id: u32, status: Status, message: String) -> Self {
+        Outcome::new(message, format!("marked {} {}", id, status.name()))
+    }
+    /// The next occurrence of a recurring task, added when it was done.
+    pub fn respawned(id: u32) -> Self {
+        Outcome::new(format!("Recurring task respawned (ID: {}).", id), format!("respawned {}", id))
+    }
+    pub fn deleted(id: u32) -> Self {
+        Outcome::new(format!("Successfully deleted task (ID: {}).", id), format!("deleted {}", id))
+    }
+}
+
+/// A message that doesn't change any task, so it has no porcelain line.
+impl From<String> for Outcome {
+    fn from(message: String) -> Self {
+        Outcome { message, porcelain: vec![], value: None }
+    }
+}
+
+/// Prints the outcome of a command, marked as such when nothing was actually persisted.
+pub(crate) fn report(options: &Options, outcome: impl Into<Outcome>) {
+    // Nothing useful can be done if stdout is gone
+    let _ = report_to(options, &outcome.into(), &mut io::stdout());
+}
+
+/// Writes the outcome of a command to `out` in the output mode. Porcelain lines and values are the same in a dry run:
+/// they say what the command would do.
+pub(crate) fn report_to(options: &Options, outcome: &Outcome, out: &mut dyn Write) -> io::Result<()> {
+    match options.output {
+        OutputMode::Quiet => outcome.value.iter().try_for_each(|value| writeln!(out, "{}", value)),
+        OutputMode::Porcelain => outcome.porcelain.iter().try_for_each(|line| writeln!(out, "{}", line)),
+        OutputMode::Normal => match &outcome.value {
+            Some(value) if options.json => writeln!(out, "{}", value),
+            _ if options.dry_run => writeln!(out, "[dry-run] {}", outcome.message),
+            _ => writeln!(out, "{}", outcome.message)
+        }
+    }
+}
+
+/// Escapes a value so that it stays on one line and in one tab-separated field: backslashes, newlines, carriage
+/// returns and tabs become `\\`, `\n`, `\r` and `\t`.
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(options: &Options, outcome: Outcome) -> String {
+        let mut out = vec![];
+        report_to(options, &outcome, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn outcomes_are_written_in_the_output_mode() {
+        let mode = |output| Options { output, ..Options::default() };
+        assert_eq!(written(&mode(OutputMode::Normal), Outcome::added(1)), "Successfully added task (ID: 1).\n");
+        assert_eq!(written(&mode(OutputMode::Quiet), Outcome::added(1)), "1\n");
+        assert_eq!(written(&mode(OutputMode::Quiet), Outcome::deleted(1)), "");
+        assert_eq!(written(&Options { json: true, ..Options::default() }, Outcome::added(7)), "7\n");
+        assert_eq!(written(&Options { json: true, ..Options::default() }, "The trash is empty.".to_string().into()), "The trash is empty.\n");
+        assert_eq!(written(&mode(OutputMode::Porcelain), Outcome::added(1)), "added 1\n");
+        assert_eq!(written(&mode(OutputMode::Porcelain), Outcome::marked(3, Status::InProgress, String::new())), "marked 3 in-progress\n");
+        assert_eq!(written(&mode(OutputMode::Porcelain), Outcome::each(String::new(), "archived", [2, 5])), "archived 2\narchived 5\n");
+        assert_eq!(written(&mode(OutputMode::Porcelain), "The trash is empty.".to_string().into()), "");
+        let dry_run = Options { dry_run: true, ..Options::default() };
+        assert_eq!(written(&dry_run, Outcome::deleted(4)), "[dry-run] Successfully deleted task (ID: 4).\n");
+        assert_eq!(written(&Options { output: OutputMode::Quiet, ..dry_run }, Outcome::added(5)), "5\n");
+    }
+
+    #[test]
+    fn escaped_values_stay_on_one_line() {
+        assert_eq!(escape("a\tb\nc\\d\r"), "a\\tb\\nc\\\\d\\r");
+        assert_eq!(escape("plain"), "plain");
+    }
+}
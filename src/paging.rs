@@ -0,0 +1,181 @@
+//! Windowed paging over long task lists.
+//!
+//! Interactive frontends should only materialize and render the rows around the selection, so the paging math
+//! lives here, independent of any terminal code: given the total count, the window size and a cursor, it yields
+//! the range of rows to show and the (slightly larger) range of rows worth fetching from the store. Plain
+//! `list --limit/--page` output only needs [`page`] and [`showing`].
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pager {
+    total: usize,
+    size: usize,
+    margin: usize,
+    cursor: usize,
+    start: usize
+}
+
+impl Pager {
+    /// Creates a pager over `total` rows showing `size` rows at a time, prefetching `margin` rows on each side.
+    pub fn new(total: usize, size: usize, margin: usize) -> Self {
+        Self { total, size: size.max(1), margin, cursor: 0, start: 0 }
+    }
+    pub fn total(&self) -> usize {
+        self.total
+    }
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    /// Rows currently visible on screen.
+    pub fn visible(&self) -> Range<usize> {
+        self.start..(self.start + self.size).min(self.total)
+    }
+    /// Rows that should be loaded: the visible window plus the margin on each side, clamped to the list.
+    pub fn materialized(&self) -> Range<usize> {
+        let visible = self.visible();
+        visible.start.saturating_sub(self.margin)..(visible.end + self.margin).min(self.total)
+    }
+    /// Whether the rows in `loaded` no longer cover what should be materialized, i.e. more rows must be fetched.
+    pub fn needs_fetch(&self, loaded: &Range<usize>) -> bool {
+        let wanted = self.materialized();
+        wanted.start < loaded.start || wanted.end > loaded.end
+    }
+    /// Moves the cursor to the given row (clamped to the list), scrolling the window just enough to keep it visible.
+    pub fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.total.saturating_sub(1));
+        if self.cursor < self.start {
+            self.start = self.cursor;
+        } else if self.cursor >= self.start + self.size {
+            self.start = self.cursor + 1 - self.size;
+        }
+    }
+    pub fn next(&mut self) {
+        self.move_to(self.cursor + 1);
+    }
+    pub fn prev(&mut self) {
+        self.move_to(self.cursor.saturating_sub(1));
+    }
+    /// Starts over with a new row count, e.g. after the filter changed and the store was queried again.
+    pub fn reset(&mut self, total: usize) {
+        self.total = total;
+        self.cursor = 0;
+        self.start = 0;
+    }
+    /// Position indicator such as "142/50,213" (1-based), or "0/0" for an empty list.
+    pub fn position(&self) -> String {
+        if self.total == 0 {
+            "0/0".to_string()
+        } else {
+            format!("{}/{}", group_thousands(self.cursor + 1), group_thousands(self.total))
+        }
+    }
+}
+
+/// The rows on the given page (counting from 1) of a list cut into pages of `size` rows. A page past the end is empty.
+pub fn page(total: usize, size: usize, page: usize) -> Range<usize> {
+    let start = size.saturating_mul(page.saturating_sub(1)).min(total);
+    start..start.saturating_add(size).min(total)
+}
+
+/// The line saying which rows a page holds, e.g. "showing 21–40 of 137", or "showing none of 137" past the end.
+pub fn showing(rows: &Range<usize>, total: usize) -> String {
+    if rows.is_empty() {
+        format!("showing none of {}", group_thousands(total))
+    } else {
+        format!("showing {}–{} of {}", group_thousands(rows.start + 1), group_thousands(rows.end), group_thousands(total))
+    }
+}
+
+/// Formats a number with commas between groups of three digits.
+fn group_thousands(n: usize) -> String {
+    let digits: Vec<char> = n.to_string().chars().collect();
+    let groups: Vec<String> = digits.rchunks(3).rev().map(|group| group.iter().collect()).collect();
+    groups.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_at_start_of_list() {
+        let pager = Pager::new(50_213, 10, 5);
+
+        assert_eq!(pager.visible(), 0..10);
+        assert_eq!(pager.materialized(), 0..15);
+        assert_eq!(pager.position(), "1/50,213");
+    }
+
+    #[test]
+    fn window_at_end_of_list() {
+        let mut pager = Pager::new(100, 10, 5);
+        pager.move_to(500);
+
+        assert_eq!(pager.cursor(), 99);
+        assert_eq!(pager.visible(), 90..100);
+        assert_eq!(pager.materialized(), 85..100);
+        pager.next();
+        assert_eq!(pager.cursor(), 99);
+    }
+
+    #[test]
+    fn short_and_empty_lists() {
+        let pager = Pager::new(3, 10, 5);
+        assert_eq!(pager.visible(), 0..3);
+        assert_eq!(pager.materialized(), 0..3);
+
+        let mut empty = Pager::new(0, 10, 5);
+        empty.next();
+        assert_eq!(empty.visible(), 0..0);
+        assert_eq!(empty.position(), "0/0");
+    }
+
+    #[test]
+    fn window_advances_with_cursor() {
+        let mut pager = Pager::new(100, 10, 5);
+        for _ in 0..9 {
+            pager.next();
+        }
+        assert_eq!(pager.visible(), 0..10);
+        assert!(!pager.needs_fetch(&(0..15)));
+
+        pager.next();
+        assert_eq!(pager.visible(), 1..11);
+        assert!(pager.needs_fetch(&(0..15)));
+
+        pager.move_to(141 % 100);
+        assert_eq!(pager.visible(), 32..42);
+        for _ in 0..10 {
+            pager.prev();
+        }
+        assert_eq!(pager.cursor(), 31);
+        assert_eq!(pager.visible(), 31..41);
+    }
+
+    #[test]
+    fn filtering_resets_window() {
+        let mut pager = Pager::new(50_213, 10, 5);
+        pager.move_to(141);
+        assert_eq!(pager.position(), "142/50,213");
+
+        pager.reset(12);
+        assert_eq!(pager.cursor(), 0);
+        assert_eq!(pager.visible(), 0..10);
+        assert_eq!(pager.position(), "1/12");
+    }
+
+    #[test]
+    fn pages_of_a_list() {
+        assert_eq!(page(137, 20, 1), 0..20);
+        assert_eq!(page(137, 20, 2), 20..40);
+        assert_eq!(showing(&page(137, 20, 2), 137), "showing 21–40 of 137");
+        // The last page is short, and the ones after it are empty
+        assert_eq!(page(137, 20, 7), 120..137);
+        assert_eq!(page(137, 20, 8), 137..137);
+        assert_eq!(page(137, 20, usize::MAX), 137..137);
+        assert_eq!(showing(&page(137, 20, 8), 137), "showing none of 137");
+        assert_eq!(page(0, 20, 1), 0..0);
+        assert_eq!(page(5, 20, 1), 0..5);
+        assert_eq!(showing(&page(1_500, 500, 3), 1_500), "showing 1,001–1,500 of 1,500");
+    }
+}
@@ -0,0 +1,157 @@
+//! The `report` command: how many tasks were completed and added in each ISO week (Monday to Sunday) of a range of
+//! days, with the change in the number of open tasks and a bar chart of the completions.
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use super::Task;
+
+/// The widest bar in the chart, in characters.
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct Report {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub weeks: Vec<Week>
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct Week {
+    /// Like "2025-W24".
+    pub week: String,
+    /// The Monday the week starts on.
+    pub start: NaiveDate,
+    pub completed: usize,
+    pub added: usize,
+    /// Added minus completed: how much the number of open tasks went up (or, if negative, down).
+    pub net_open: i64
+}
+
+/// Counts the tasks completed and added on each day from `from` to `to` (both included, as dates in `tz`), by the ISO
+/// week they fall in. Every week of the range is listed, including weeks where nothing happened. Only done tasks count
+/// as completed, at their completion time.
+pub(crate) fn weekly<Tz: TimeZone>(tasks: &[Task], from: NaiveDate, to: NaiveDate, tz: &Tz) -> Report {
+    let first_monday = monday(from);
+    let mut weeks = vec![];
+    let mut start = first_monday;
+    while start <= to {
+        let iso = start.iso_week();
+        weeks.push(Week { week: format!("{}-W{:02}", iso.year(), iso.week()), start, completed: 0, added: 0, net_open: 0 });
+        start += Duration::weeks(1);
+    }
+    let week_of = |time: &DateTime<Utc>| {
+        let day = time.with_timezone(tz).date_naive();
+        (from..=to).contains(&day).then(|| ((day - first_monday).num_days() / 7) as usize)
+    };
+    for task in tasks {
+        if let Some(index) = week_of(&task.created_at) {
+            weeks[index].added += 1;
+        }
+        if let Some(index) = task.completion_time().and_then(|completed_at| week_of(&completed_at)) {
+            weeks[index].completed += 1;
+        }
+    }
+    for week in &mut weeks {
+        week.net_open = week.added as i64 - week.completed as i64;
+    }
+    Report { from, to, weeks }
+}
+
+/// The Monday of the ISO week the day is in.
+pub(crate) fn monday(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday().into())
+}
+
+impl Report {
+    /// A table with a row per week, ending with a newline. The bars show the completed tasks, scaled down if a week
+    /// has more than fit.
+    pub fn render(&self) -> String {
+        let most = self.weeks.iter().map(|week| week.completed).max().unwrap_or(0);
+        let mut out = format!("Tasks completed and added from {} to {}\n\n", self.from, self.to);
+        out.push_str("Week      Starting    Completed  Added  Open\n");
+        for week in &self.weeks {
+            let bar = "#".repeat(bar_length(week.completed, most));
+            let line = format!("{:<9} {:<11} {:>9}  {:>5}  {:>+4}  {}", week.week, week.start, week.completed, week.added, week.net_open, bar);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        let completed: usize = self.weeks.iter().map(|week| week.completed).sum();
+        let added: usize = self.weeks.iter().map(|week| week.added).sum();
+        out.push_str(&format!("\n{} completed, {} added, open tasks {:+}\n", completed, added, added as i64 - completed as i64));
+        out
+    }
+}
+
+/// Bars are scaled so that the longest is `BAR_WIDTH`, but a week with any completions always gets at least one mark.
+fn bar_length(count: usize, most: usize) -> usize {
+    if most <= BAR_WIDTH {
+        count
+    } else if count == 0 {
+        0
+    } else {
+        (count * BAR_WIDTH / most).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    fn task(id: u32, created_at: &str, completed_at: Option<&str>) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id));
+        task.created_at = DateTime::parse_from_rfc3339(created_at).unwrap().with_timezone(&Utc);
+        if let Some(completed_at) = completed_at {
+            task.status = Status::Done;
+            task.completed_at = Some(DateTime::parse_from_rfc3339(completed_at).unwrap().with_timezone(&Utc));
+        }
+        task
+    }
+
+    fn day(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn weeks_are_counted_across_their_boundaries() {
+        let tasks = [
+            // Sunday night and Monday morning fall in different weeks
+            task(1, "2025-06-01T23:59:59Z", Some("2025-06-02T00:00:00Z")),
+            task(2, "2025-06-02T09:00:00Z", Some("2025-06-08T23:59:59Z")),
+            task(3, "2025-06-04T09:00:00Z", None),
+            // Nothing in the week of June 9; one task created then but completed later
+            task(4, "2025-06-05T09:00:00Z", Some("2025-06-17T10:00:00Z")),
+            task(5, "2025-06-16T09:00:00Z", None),
+            // Before and after the range
+            task(6, "2025-05-01T09:00:00Z", Some("2025-05-02T09:00:00Z")),
+            task(7, "2025-06-23T09:00:00Z", None),
+        ];
+        let report = weekly(&tasks, day("2025-06-01"), day("2025-06-22"), &Utc);
+        let rows: Vec<(&str, usize, usize, i64)> = report.weeks.iter().map(|week| (week.week.as_str(), week.completed, week.added, week.net_open)).collect();
+
+        assert_eq!(rows, [("2025-W22", 0, 1, 1), ("2025-W23", 2, 3, 1), ("2025-W24", 0, 0, 0), ("2025-W25", 1, 1, 0)]);
+        assert_eq!(report.weeks[0].start, day("2025-05-26"));
+        assert_eq!(report.weeks[2].start, day("2025-06-09"));
+    }
+
+    #[test]
+    fn reopened_tasks_are_not_completed() {
+        let mut reopened = task(1, "2025-06-02T09:00:00Z", Some("2025-06-03T09:00:00Z"));
+        reopened.update_status(Status::Todo);
+        let report = weekly(&[reopened], day("2025-06-02"), day("2025-06-08"), &Utc);
+        assert_eq!((report.weeks[0].completed, report.weeks[0].added), (0, 1));
+    }
+
+    #[test]
+    fn report_renders_a_row_per_week() {
+        let tasks: Vec<Task> = (1..=3).map(|id| task(id, "2025-06-02T09:00:00Z", Some("2025-06-03T09:00:00Z"))).collect();
+        let rendered = weekly(&tasks, day("2025-06-02"), day("2025-06-15"), &Utc).render();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[3], "2025-W23  2025-06-02         3      3    +0  ###");
+        assert_eq!(lines[4], "2025-W24  2025-06-09         0      0    +0");
+        assert_eq!(lines.last(), Some(&"3 completed, 3 added, open tasks +0"));
+        assert_eq!(bar_length(80, 80), BAR_WIDTH);
+        assert_eq!(bar_length(1, 80), 1);
+        assert_eq!(bar_length(0, 80), 0);
+    }
+}
@@ -0,0 +1,562 @@
+//! Where the tasks are kept.
+//!
+//! Commands work on a [`Storage`], which loads the whole list and saves it back. The file backend (JSON, TOML or
+//! YAML, by extension) rewrites the file on every save, unless it keeps a journal; the SQLite backend keeps one row per task and only writes the rows that changed since they were loaded,
+//! which keeps commands fast on large lists.
+//!
+//! With `--journal`, the file backend appends the changes each save makes to `<name>.journal` next to the file, one
+//! line per save, and loading replays them on top of the file. The journal is folded back into the file by `compact`,
+//! or by the first save once it holds `JOURNAL_LIMIT` saves. A journal is replayed whether or not `--journal` is
+//! given, and a save without it writes the file in full and removes the journal, so no change is ever left behind.
+use std::{collections::{HashMap, HashSet}, fs::{self, OpenOptions}, io::{self, Write}, path::Path, str::FromStr};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use super::{create_db, read_db, write_db, JsonStyle, Task, TaskError};
+
+/// How many saves the journal holds before the next one writes the file in full instead.
+pub(crate) const JOURNAL_LIMIT: usize = 1000;
+
+pub(crate) trait Storage {
+    /// Whether the store exists yet; loading creates it.
+    fn exists(&self) -> bool;
+    fn load(&mut self) -> Result<Vec<Task>, TaskError>;
+    fn save(&mut self, tasks: &[Task]) -> Result<(), TaskError>;
+    /// Writes out changes held back in memory; only a [`Buffered`] store holds any.
+    fn flush(&mut self) -> Result<(), TaskError> {
+        Ok(())
+    }
+    /// Folds a journal into the file it belongs to, returning whether there was one.
+    fn compact(&mut self) -> Result<bool, TaskError> {
+        Ok(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// A JSON, TOML or YAML file, depending on its extension.
+    Json,
+    Sqlite
+}
+
+impl Backend {
+    /// Picks the backend from the file extension: .db, .sqlite and .sqlite3 files are SQLite, anything else is a file.
+    pub fn for_path(file_path: &str) -> Self {
+        match Path::new(file_path).extension().and_then(|extension| extension.to_str()) {
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            _ => Backend::Json
+        }
+    }
+    /// The extension of task files created for this backend.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Backend::Json => "json",
+            Backend::Sqlite => "db"
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Backend::Json),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(format!("Invalid backend: {} (expected json or sqlite)", s))
+        }
+    }
+}
+
+/// Opens the store at `file_path`, keeping a journal if it is a file and `journal` is set. Nothing is read or created
+/// until it is loaded or saved.
+pub(crate) fn open(backend: Backend, file_path: &str, style: JsonStyle, journal: bool) -> Box<dyn Storage> {
+    match backend {
+        Backend::Json => Box::new(FileStorage { path: file_path.to_string(), style, journal, saved: None }),
+        Backend::Sqlite => Box::new(SqliteStorage { path: file_path.to_string(), connection: None, saved: HashMap::new() })
+    }
+}
+
+/// Opens a trash. Unlike a task list, it can hold several tasks with the same ID (a deleted ID is given to the next
+/// task added), which the SQLite backend keeps apart by their position instead of their ID.
+pub(crate) fn open_trash(backend: Backend, file_path: &str, style: JsonStyle, journal: bool) -> Box<dyn Storage> {
+    match backend {
+        Backend::Json => open(backend, file_path, style, journal),
+        Backend::Sqlite => Box::new(SqliteTrash { path: file_path.to_string(), connection: None, saved: vec![] })
+    }
+}
+
+/// The journal that goes with a task file: `<name>.journal` next to it.
+pub(crate) fn journal_path(file_path: &str) -> String {
+    Path::new(file_path).with_extension("journal").to_string_lossy().to_string()
+}
+
+/// Keeps the tasks of another store in memory once loaded, and only saves them to it when flushed.
+pub(crate) struct Buffered {
+    inner: Box<dyn Storage>,
+    tasks: Option<Vec<Task>>,
+    changed: bool
+}
+
+impl Buffered {
+    pub fn new(inner: Box<dyn Storage>) -> Self {
+        Buffered { inner, tasks: None, changed: false }
+    }
+}
+
+impl Storage for Buffered {
+    fn exists(&self) -> bool {
+        self.tasks.is_some() || self.inner.exists()
+    }
+    fn load(&mut self) -> Result<Vec<Task>, TaskError> {
+        if self.tasks.is_none() {
+            self.tasks = Some(self.inner.load()?);
+        }
+        Ok(self.tasks.clone().unwrap_or_default())
+    }
+    fn save(&mut self, tasks: &[Task]) -> Result<(), TaskError> {
+        self.tasks = Some(tasks.to_vec());
+        self.changed = true;
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), TaskError> {
+        if let (true, Some(tasks)) = (self.changed, &self.tasks) {
+            self.inner.save(tasks)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+    fn compact(&mut self) -> Result<bool, TaskError> {
+        self.flush()?;
+        self.inner.compact()
+    }
+}
+
+/// A JSON, TOML or YAML file holding the list of tasks, along with the journal of changes made since it was last
+/// written in full, if there is one.
+struct FileStorage {
+    path: String,
+    style: JsonStyle,
+    /// Append changes to the journal rather than rewriting the file.
+    journal: bool,
+    /// What the file and journal hold as of the last load or save, which the next entry records changes from.
+    saved: Option<Snapshot>
+}
+
+struct Snapshot {
+    /// The JSON of each task.
+    tasks: HashMap<u32, String>,
+    order: Vec<u32>,
+    /// Saves in the journal so far.
+    entries: usize,
+    /// Set when the last line of the journal was cut short, so that nothing is appended to it.
+    damaged: bool
+}
+
+impl Snapshot {
+    fn new(path: &str, tasks: &[Task], entries: usize, damaged: bool) -> Result<Self, TaskError> {
+        let mut json = HashMap::new();
+        for task in tasks {
+            json.insert(task.id, serde_json::to_string(task).map_err(|error| TaskError::json(path, error))?);
+        }
+        Ok(Snapshot { tasks: json, order: tasks.iter().map(|task| task.id).collect(), entries, damaged })
+    }
+}
+
+/// A change to the task list, as written to the journal: `{"op": "put", "task": {...}}` and so on. Each line of
+/// the journal holds the changes of one save, as an array.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Op {
+    /// Replaces the task with the same id where it is, or adds it at the end.
+    Put { task: Box<Task> },
+    Remove { id: u32 },
+    /// Puts the tasks in this order, after a `move`; any not listed keep their order after the others.
+    Order { ids: Vec<u32> }
+}
+
+impl Op {
+    fn apply(self, tasks: &mut Vec<Task>) {
+        match self {
+            Op::Put { task } => match tasks.iter_mut().find(|other| other.id == task.id) {
+                Some(existing) => *existing = *task,
+                None => tasks.push(*task)
+            },
+            Op::Remove { id } => tasks.retain(|task| task.id != id),
+            Op::Order { ids } => tasks.sort_by_key(|task| ids.iter().position(|id| *id == task.id).unwrap_or(ids.len()))
+        }
+    }
+}
+
+/// The changes that turn `saved` into `tasks`, removals first.
+fn changes(path: &str, saved: &Snapshot, tasks: &[Task]) -> Result<Vec<Op>, TaskError> {
+    let ids: HashSet<u32> = tasks.iter().map(|task| task.id).collect();
+    let mut ops: Vec<Op> = saved.order.iter().filter(|id| !ids.contains(id)).map(|id| Op::Remove { id: *id }).collect();
+    for task in tasks {
+        let json = serde_json::to_string(task).map_err(|error| TaskError::json(path, error))?;
+        if saved.tasks.get(&task.id) != Some(&json) {
+            ops.push(Op::Put { task: Box::new(task.clone()) });
+        }
+    }
+    // Replaying the puts keeps the tasks that were there in place and adds the new ones at the end
+    let mut expected: Vec<u32> = saved.order.iter().copied().filter(|id| ids.contains(id)).collect();
+    expected.extend(tasks.iter().map(|task| task.id).filter(|id| !saved.tasks.contains_key(id)));
+    let order: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+    if order != expected {
+        ops.push(Op::Order { ids: order });
+    }
+    Ok(ops)
+}
+
+/// Reads the saves in a journal, if there is one, along with whether its last line was cut short (by a command that
+/// was interrupted while writing it). Such a line is left out with a warning; any other invalid line is an error.
+fn read_journal(path: &str) -> Result<(Vec<Vec<Op>>, bool), TaskError> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok((vec![], false)),
+        Err(error) => return Err(TaskError::io(path, error))
+    };
+    let mut saves = vec![];
+    for (index, line) in data.split_inclusive('\n').enumerate() {
+        if !line.ends_with('\n') {
+            eprintln!("Warning: ignoring the incomplete last line of {}; the command that wrote it was probably interrupted.", path);
+            return Ok((saves, true));
+        }
+        match serde_json::from_str(line) {
+            Ok(ops) => saves.push(ops),
+            Err(error) => {
+                let message = format!("line {} of the journal: {}", index + 1, error);
+                return Err(TaskError::Parse { path: path.to_string(), line: index + 1, column: error.column(), message });
+            }
+        }
+    }
+    Ok((saves, false))
+}
+
+impl FileStorage {
+    fn journal_path(&self) -> String {
+        journal_path(&self.path)
+    }
+    /// Writes the tasks to the file in full and removes the journal, which they include.
+    fn write_all(&mut self, tasks: &[Task]) -> Result<(), TaskError> {
+        write_db(&self.path, tasks, self.style)?;
+        let journal = self.journal_path();
+        match fs::remove_file(&journal) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(TaskError::io(&journal, error)),
+            _ => {}
+        }
+        self.saved = if self.journal { Some(Snapshot::new(&self.path, tasks, 0, false)?) } else { None };
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn exists(&self) -> bool {
+        Path::new(&self.path).exists() || Path::new(&self.journal_path()).exists()
+    }
+    fn load(&mut self) -> Result<Vec<Task>, TaskError> {
+        create_db(&self.path)?;
+        let mut tasks = read_db(&self.path)?;
+        let (saves, damaged) = read_journal(&self.journal_path())?;
+        let entries = saves.len();
+        for op in saves.into_iter().flatten() {
+            op.apply(&mut tasks);
+        }
+        self.saved = if self.journal { Some(Snapshot::new(&self.path, &tasks, entries, damaged)?) } else { None };
+        Ok(tasks)
+    }
+    fn save(&mut self, tasks: &[Task]) -> Result<(), TaskError> {
+        let journal = self.journal_path();
+        let Some(saved) = self.saved.as_mut().filter(|saved| !saved.damaged && saved.entries < JOURNAL_LIMIT) else {
+            return self.write_all(tasks);
+        };
+        let ops = changes(&self.path, saved, tasks)?;
+        if ops.is_empty() {
+            return Ok(());
+        }
+        let mut line = serde_json::to_string(&ops).map_err(|error| TaskError::json(&journal, error))?;
+        line.push('\n');
+        // One write per save, so that an interruption can only ever cut the last line short
+        let mut file = OpenOptions::new().append(true).create(true).open(&journal).map_err(|error| TaskError::io(&journal, error))?;
+        file.write_all(line.as_bytes()).map_err(|error| TaskError::io(&journal, error))?;
+        *saved = Snapshot::new(&self.path, tasks, saved.entries + 1, false)?;
+        Ok(())
+    }
+    fn compact(&mut self) -> Result<bool, TaskError> {
+        if !Path::new(&self.journal_path()).exists() {
+            return Ok(false);
+        }
+        let tasks = self.load()?;
+        self.write_all(&tasks)?;
+        Ok(true)
+    }
+}
+
+/// A SQLite database with one row per task, holding the task as JSON along with its position in the list.
+struct SqliteStorage {
+    path: String,
+    connection: Option<Connection>,
+    /// Position and JSON of each task as last loaded or saved, to tell which rows need writing.
+    saved: HashMap<u32, (usize, String)>
+}
+
+impl SqliteStorage {
+    fn connect(path: &str, connection: &mut Option<Connection>) -> Result<(), TaskError> {
+        if connection.is_none() {
+            let opened = Connection::open(path).map_err(|error| sql_error(path, error))?;
+            opened
+                .execute_batch("CREATE TABLE IF NOT EXISTS tasks (id INTEGER PRIMARY KEY, position INTEGER NOT NULL, data TEXT NOT NULL);")
+                .map_err(|error| sql_error(path, error))?;
+            *connection = Some(opened);
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn exists(&self) -> bool {
+        Path::new(&self.path).exists()
+    }
+    fn load(&mut self) -> Result<Vec<Task>, TaskError> {
+        Self::connect(&self.path, &mut self.connection)?;
+        let path = &self.path;
+        let Some(connection) = &self.connection else { unreachable!("connected above") };
+        let mut statement = connection.prepare("SELECT data FROM tasks ORDER BY position").map_err(|error| sql_error(path, error))?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|error| sql_error(path, error))?;
+        let mut tasks = vec![];
+        self.saved.clear();
+        for (position, data) in rows.enumerate() {
+            let data = data.map_err(|error| sql_error(path, error))?;
+            let task: Task = serde_json::from_str(&data).map_err(|error| TaskError::json(path, error))?;
+            self.saved.insert(task.id, (position, data));
+            tasks.push(task);
+        }
+        Ok(tasks)
+    }
+    fn save(&mut self, tasks: &[Task]) -> Result<(), TaskError> {
+        Self::connect(&self.path, &mut self.connection)?;
+        let path = &self.path;
+        let Some(connection) = &mut self.connection else { unreachable!("connected above") };
+        let transaction = connection.transaction().map_err(|error| sql_error(path, error))?;
+        let mut saved = HashMap::new();
+        for (position, task) in tasks.iter().enumerate() {
+            let data = serde_json::to_string(task).map_err(|error| TaskError::json(path, error))?;
+            let row = (position, data);
+            if self.saved.get(&task.id) != Some(&row) {
+                transaction
+                    .execute("INSERT OR REPLACE INTO tasks (id, position, data) VALUES (?1, ?2, ?3)", params![task.id, row.0, row.1])
+                    .map_err(|error| sql_error(path, error))?;
+            }
+            saved.insert(task.id, row);
+        }
+        for id in self.saved.keys().filter(|id| !saved.contains_key(id)) {
+            transaction.execute("DELETE FROM tasks WHERE id = ?1", params![id]).map_err(|error| sql_error(path, error))?;
+        }
+        transaction.commit().map_err(|error| sql_error(path, error))?;
+        self.saved = saved;
+        Ok(())
+    }
+}
+
+/// A SQLite trash with one row per task, keyed by its position in the trash.
+struct SqliteTrash {
+    path: String,
+    connection: Option<Connection>,
+    /// The JSON of each task as last loaded or saved, in order.
+    saved: Vec<String>
+}
+
+impl SqliteTrash {
+    fn connect(path: &str, connection: &mut Option<Connection>) -> Result<(), TaskError> {
+        if connection.is_none() {
+            let mut opened = Connection::open(path).map_err(|error| sql_error(path, error))?;
+            let transaction = opened.transaction().map_err(|error| sql_error(path, error))?;
+            transaction
+                .execute_batch("CREATE TABLE IF NOT EXISTS trash (position INTEGER PRIMARY KEY, data TEXT NOT NULL);")
+                .map_err(|error| sql_error(path, error))?;
+            // Trashes used to be kept in a `tasks` table keyed by ID, like the task list
+            let legacy: bool = transaction
+                .query_row("SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'tasks'", [], |row| row.get(0))
+                .map_err(|error| sql_error(path, error))?;
+            if legacy {
+                transaction
+                    .execute_batch("INSERT INTO trash (position, data) SELECT position, data FROM tasks ORDER BY position; DROP TABLE tasks;")
+                    .map_err(|error| sql_error(path, error))?;
+            }
+            transaction.commit().map_err(|error| sql_error(path, error))?;
+            *connection = Some(opened);
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteTrash {
+    fn exists(&self) -> bool {
+        Path::new(&self.path).exists()
+    }
+    fn load(&mut self) -> Result<Vec<Task>, TaskError> {
+        Self::connect(&self.path, &mut self.connection)?;
+        let path = &self.path;
+        let Some(connection) = &self.connection else { unreachable!("connected above") };
+        let mut statement = connection.prepare("SELECT data FROM trash ORDER BY position").map_err(|error| sql_error(path, error))?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|error| sql_error(path, error))?;
+        let mut tasks = vec![];
+        self.saved.clear();
+        for data in rows {
+            let data = data.map_err(|error| sql_error(path, error))?;
+            tasks.push(serde_json::from_str(&data).map_err(|error| TaskError::json(path, error))?);
+            self.saved.push(data);
+        }
+        Ok(tasks)
+    }
+    fn save(&mut self, tasks: &[Task]) -> Result<(), TaskError> {
+        Self::connect(&self.path, &mut self.connection)?;
+        let path = &self.path;
+        let Some(connection) = &mut self.connection else { unreachable!("connected above") };
+        let transaction = connection.transaction().map_err(|error| sql_error(path, error))?;
+        let mut saved = vec![];
+        for (position, task) in tasks.iter().enumerate() {
+            let data = serde_json::to_string(task).map_err(|error| TaskError::json(path, error))?;
+            if self.saved.get(position) != Some(&data) {
+                transaction
+                    .execute("INSERT OR REPLACE INTO trash (position, data) VALUES (?1, ?2)", params![position, data])
+                    .map_err(|error| sql_error(path, error))?;
+            }
+            saved.push(data);
+        }
+        transaction.execute("DELETE FROM trash WHERE position >= ?1", params![tasks.len()]).map_err(|error| sql_error(path, error))?;
+        transaction.commit().map_err(|error| sql_error(path, error))?;
+        self.saved = saved;
+        Ok(())
+    }
+}
+
+fn sql_error(path: &str, error: rusqlite::Error) -> TaskError {
+    TaskError::io(path, io::Error::other(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("task-tracker-journal-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(path.with_extension("journal"));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn tasks(count: u32) -> Vec<Task> {
+        (1..=count).map(|id| Task::new(id, format!("Task number {}", id))).collect()
+    }
+
+    fn journal_len(path: &str) -> u64 {
+        fs::metadata(Path::new(path).with_extension("journal")).map(|metadata| metadata.len()).unwrap_or(0)
+    }
+
+    /// The bytes written by changing one task, and whether the file itself was left alone.
+    fn change_one(count: u32) -> (u64, bool) {
+        let path = temp_file(&format!("size-{}", count));
+        let mut storage = open(Backend::Json, &path, JsonStyle::Pretty, true);
+        storage.save(&tasks(count)).unwrap();
+        let base = fs::read(&path).unwrap();
+        let mut loaded = storage.load().unwrap();
+        loaded[0].description = "Changed".to_string();
+        storage.save(&loaded).unwrap();
+        let written = journal_len(&path);
+
+        assert_eq!(open(Backend::Json, &path, JsonStyle::Pretty, false).load().unwrap()[0].description, "Changed");
+        (written, fs::read(&path).unwrap() == base)
+    }
+
+    #[test]
+    fn saves_append_only_what_changed() {
+        let (small, small_untouched) = change_one(10);
+        let (large, large_untouched) = change_one(3000);
+        assert!(small_untouched && large_untouched);
+        // A few bytes more for the longer ids, but nothing like the 300 times bigger file
+        assert!(large < small + 20, "{} bytes for 10 tasks, {} for 3000", small, large);
+    }
+
+    #[test]
+    fn journals_replay_removals_and_moves() {
+        let path = temp_file("replay");
+        let mut storage = open(Backend::Json, &path, JsonStyle::Pretty, true);
+        storage.save(&tasks(3)).unwrap();
+        let mut changed = storage.load().unwrap();
+        changed.remove(1);
+        changed.swap(0, 1);
+        changed.push(Task::new(4, "Added".to_string()));
+        storage.save(&changed).unwrap();
+        storage.save(&changed).unwrap();
+
+        let ids = |tasks: Vec<Task>| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(ids(open(Backend::Json, &path, JsonStyle::Pretty, false).load().unwrap()), [3, 1, 4]);
+        // Saving nothing new adds nothing
+        assert_eq!(fs::read_to_string(Path::new(&path).with_extension("journal")).unwrap().lines().count(), 1);
+
+        assert!(storage.compact().unwrap());
+        assert!(!Path::new(&path).with_extension("journal").exists());
+        assert!(!storage.compact().unwrap());
+        assert_eq!(ids(read_db(&path).unwrap()), [3, 1, 4]);
+    }
+
+    #[test]
+    fn a_cut_off_last_line_is_ignored() {
+        let path = temp_file("truncated");
+        let journal = Path::new(&path).with_extension("journal");
+        let mut storage = open(Backend::Json, &path, JsonStyle::Pretty, true);
+        storage.save(&tasks(2)).unwrap();
+        let mut loaded = storage.load().unwrap();
+        loaded[0].description = "Kept".to_string();
+        storage.save(&loaded).unwrap();
+        loaded[1].description = "Lost".to_string();
+        storage.save(&loaded).unwrap();
+        let data = fs::read_to_string(&journal).unwrap();
+        fs::write(&journal, &data[..data.len() - 10]).unwrap();
+
+        let mut storage = open(Backend::Json, &path, JsonStyle::Pretty, true);
+        let loaded = storage.load().unwrap();
+        assert_eq!((loaded[0].description.as_str(), loaded[1].description.as_str()), ("Kept", "Task number 2"));
+        // Nothing is appended after the broken line: the next save writes everything out
+        storage.save(&loaded).unwrap();
+        assert!(!journal.exists());
+
+        fs::write(&journal, "not json\n[]\n").unwrap();
+        assert!(matches!(storage.load(), Err(TaskError::Parse { line: 1, .. })));
+    }
+
+    #[test]
+    fn sqlite_trashes_from_before_the_trash_table_are_moved_into_it() {
+        let path = temp_file("legacy-trash").replace(".json", ".db");
+        let mut legacy = open(Backend::Sqlite, &path, JsonStyle::Pretty, false);
+        legacy.save(&tasks(2)).unwrap();
+        drop(legacy);
+
+        let mut trash = open_trash(Backend::Sqlite, &path, JsonStyle::Pretty, false);
+        let mut trashed = trash.load().unwrap();
+        assert_eq!(trashed.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 2]);
+        trashed.push(Task::new(1, "Deleted again".to_string()));
+        trash.save(&trashed).unwrap();
+        trashed.remove(0);
+        trash.save(&trashed).unwrap();
+        let reloaded = open_trash(Backend::Sqlite, &path, JsonStyle::Pretty, false).load().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reloaded.iter().map(|task| task.description.as_str()).collect::<Vec<_>>(), ["Task number 2", "Deleted again"]);
+    }
+
+    #[test]
+    fn a_full_journal_is_folded_into_the_file() {
+        let path = temp_file("limit");
+        let mut storage = open(Backend::Json, &path, JsonStyle::Pretty, true);
+        let mut tasks = tasks(1);
+        storage.save(&tasks).unwrap();
+        for count in 0..JOURNAL_LIMIT {
+            tasks[0].description = format!("Version {}", count);
+            storage.save(&tasks).unwrap();
+        }
+        assert!(journal_len(&path) > 0);
+        tasks[0].description = "Last".to_string();
+        storage.save(&tasks).unwrap();
+        assert_eq!(journal_len(&path), 0);
+        assert_eq!(read_db(&path).unwrap()[0].description, "Last");
+    }
+}
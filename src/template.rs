@@ -0,0 +1,128 @@
+//! `list --template`: one line per task, made from a format string whose placeholders (like `{id}` or `{desc}`) are
+//! filled in from the task. `{{` and `}}` stand for literal braces.
+use super::{Task, TimeFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Id,
+    Status,
+    Description,
+    Created,
+    Updated,
+    Due,
+    Priority,
+    Tags
+}
+
+/// The placeholders a template can use, by name.
+const FIELDS: [(&str, Field); 8] = [
+    ("id", Field::Id),
+    ("status", Field::Status),
+    ("desc", Field::Description),
+    ("created", Field::Created),
+    ("updated", Field::Updated),
+    ("due", Field::Due),
+    ("priority", Field::Priority),
+    ("tags", Field::Tags)
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(Field)
+}
+
+/// A parsed format string, checked when the command line is read so that a typo fails before anything is printed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template(Vec<Piece>);
+
+impl Template {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unclosed placeholder in template: {{{}", name))
+                        }
+                    }
+                    let Some((_, field)) = FIELDS.iter().find(|(known, _)| *known == name) else {
+                        let known: Vec<String> = FIELDS.iter().map(|(known, _)| format!("{{{}}}", known)).collect();
+                        return Err(format!("Unknown placeholder in template: {{{}}} (expected one of: {})", name, known.join(", ")));
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(*field));
+                },
+                '}' => return Err("Unmatched '}' in template; use '}}' for a literal brace".to_string()),
+                c => text.push(c)
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template(pieces))
+    }
+
+    /// The line for one task, without a newline. Times are shown exactly; fields the task doesn't have are left empty.
+    pub fn render(&self, task: &Task, times: TimeFormat) -> String {
+        let mut line = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Field(field) => line.push_str(&match field {
+                    Field::Id => task.id.to_string(),
+                    Field::Status => task.status.to_string(),
+                    Field::Description => task.description.clone(),
+                    Field::Created => times.exact(&task.created_at),
+                    Field::Updated => task.updated_at.map(|updated_at| times.exact(&updated_at)).unwrap_or_default(),
+                    Field::Due => task.due_at.map(|due_at| times.exact(&due_at)).unwrap_or_default(),
+                    Field::Priority => task.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+                    Field::Tags => task.tags.join(",")
+                })
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{timestamp, Priority, Status};
+
+    #[test]
+    fn placeholders_are_filled_in_from_the_task() {
+        let mut task = Task::new(7, "Write report".to_string());
+        task.created_at = timestamp::parse("2025-03-01T09:00:00Z").unwrap();
+        task.status = Status::InProgress;
+        task.priority = Some(Priority::High);
+        let template = Template::parse("#{id}\t[{status}] {desc} ({priority}) {{created {created}}} due:{due}").unwrap();
+
+        assert_eq!(template.render(&task, TimeFormat::Iso), "#7\t[in progress] Write report (high) {created 2025-03-01T09:00:00Z} due:");
+        assert_eq!(Template::parse("no placeholders").unwrap().render(&task, TimeFormat::Iso), "no placeholders");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_refused() {
+        let error = Template::parse("{id} {description}").unwrap_err();
+        assert!(error.starts_with("Unknown placeholder in template: {description} (expected one of: {id}, {status}, {desc}"), "{}", error);
+        assert!(Template::parse("{id").unwrap_err().starts_with("Unclosed placeholder"));
+        assert!(Template::parse("id}").unwrap_err().starts_with("Unmatched '}'"));
+    }
+}
@@ -0,0 +1,123 @@
+//! Reading tasks written in the todo.txt format (<https://github.com/todotxt/todo.txt>).
+//!
+//! A line is `[x ][(A) ][completion date ][creation date ]description`, where the description may hold `+project`
+//! and `@context` tags and `key:value` extensions. Tags are dropped, since tasks have nothing to keep them in; of the
+//! extensions, `due:` becomes the due date and `pri:` (where done tasks keep their priority) the priority, and any
+//! others are left in the description.
+use chrono::NaiveDate;
+use super::Priority;
+
+/// One task read from a todo.txt line.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Entry {
+    pub done: bool,
+    pub priority: Option<Priority>,
+    pub completed_on: Option<NaiveDate>,
+    pub created_on: Option<NaiveDate>,
+    pub due_on: Option<NaiveDate>,
+    pub description: String
+}
+
+/// Parses a line, giving `None` for a blank one.
+pub(crate) fn parse_line(line: &str) -> Result<Option<Entry>, String> {
+    let mut words = line.split_whitespace().peekable();
+    if words.peek().is_none() {
+        return Ok(None);
+    }
+    let mut entry = Entry { done: words.next_if_eq(&"x").is_some(), ..Entry::default() };
+    if let Some(word) = words.next_if(|word| priority(word).is_some()) {
+        entry.priority = priority(word);
+    }
+    if entry.done {
+        if let Some(word) = words.next_if(|word| looks_like_date(word)) {
+            entry.completed_on = Some(date(word)?);
+        }
+    }
+    if let Some(word) = words.next_if(|word| looks_like_date(word)) {
+        entry.created_on = Some(date(word)?);
+    }
+    let mut description = vec![];
+    for word in words {
+        match word.split_once(':') {
+            _ if word.len() > 1 && (word.starts_with('+') || word.starts_with('@')) => {},
+            Some(("due", value)) => entry.due_on = Some(date(value)?),
+            Some(("pri", value)) => entry.priority = Some(priority(&format!("({})", value)).ok_or(format!("Invalid priority {:?}", value))?),
+            _ => description.push(word)
+        }
+    }
+    if description.is_empty() {
+        return Err("No description".to_string());
+    }
+    entry.description = description.join(" ");
+    Ok(Some(entry))
+}
+
+/// Reads a priority such as `(A)`. A is high, B medium, and the rest of the alphabet low.
+fn priority(word: &str) -> Option<Priority> {
+    match word.as_bytes() {
+        [b'(', b'A', b')'] => Some(Priority::High),
+        [b'(', b'B', b')'] => Some(Priority::Medium),
+        [b'(', b'C'..=b'Z', b')'] => Some(Priority::Low),
+        _ => None
+    }
+}
+
+/// Whether the word is shaped like a YYYY-MM-DD date, valid or not.
+fn looks_like_date(word: &str) -> bool {
+    word.len() == 10 && word.bytes().enumerate().all(|(index, byte)| if index == 4 || index == 7 { byte == b'-' } else { byte.is_ascii_digit() })
+}
+
+fn date(word: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").map_err(|_| format!("Invalid date {:?}", word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> Option<NaiveDate> {
+        Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap())
+    }
+
+    fn entry(description: &str) -> Entry {
+        Entry { description: description.to_string(), ..Entry::default() }
+    }
+
+    #[test]
+    fn reads_real_world_lines() {
+        let lines = [
+            ("Call Mom", entry("Call Mom")),
+            ("(A) Thank Mom for the meatballs @phone", Entry { priority: Some(Priority::High), ..entry("Thank Mom for the meatballs") }),
+            ("(B) Schedule Goodwill pickup +GarageSale @phone", Entry { priority: Some(Priority::Medium), ..entry("Schedule Goodwill pickup") }),
+            ("(D) 2011-03-02 Post signs around the neighborhood +GarageSale", Entry { priority: Some(Priority::Low), created_on: day("2011-03-02"), ..entry("Post signs around the neighborhood") }),
+            ("2011-03-02 Document +TodoTxt task format", Entry { created_on: day("2011-03-02"), ..entry("Document task format") }),
+            ("x 2011-03-03 Call Mom", Entry { done: true, completed_on: day("2011-03-03"), ..entry("Call Mom") }),
+            (
+                "x 2011-03-02 2011-03-01 Review Tim's pull request +TodoTxtTouch @github",
+                Entry { done: true, completed_on: day("2011-03-02"), created_on: day("2011-03-01"), ..entry("Review Tim's pull request") }
+            ),
+            ("x 2020-01-05 2020-01-01 Pay rent pri:A", Entry { done: true, priority: Some(Priority::High), completed_on: day("2020-01-05"), created_on: day("2020-01-01"), ..entry("Pay rent") }),
+            ("Renew passport due:2024-08-01 t:2024-07-01", Entry { due_on: day("2024-08-01"), ..entry("Renew passport t:2024-07-01") }),
+            ("  Water plants\r", entry("Water plants")),
+            // None of these are what they look like at first sight
+            ("xylophone lessons", entry("xylophone lessons")),
+            ("X-ray appointment", entry("X-ray appointment")),
+            ("(a) lowercase is not a priority", entry("(a) lowercase is not a priority")),
+            ("Really (A) priority only comes first", entry("Really (A) priority only comes first")),
+            ("Email someone@example.com about + and @ signs", entry("Email someone@example.com about + and @ signs")),
+            ("Read https://example.com/docs", entry("Read https://example.com/docs")),
+        ];
+        for (line, expected) in lines {
+            assert_eq!(parse_line(line), Ok(Some(expected)), "{}", line);
+        }
+    }
+
+    #[test]
+    fn blank_and_malformed_lines() {
+        assert_eq!(parse_line(""), Ok(None));
+        assert_eq!(parse_line("   \t"), Ok(None));
+        for malformed in ["x", "(A) 2011-03-02", "+GarageSale @phone", "2011-13-45 Bad date", "x 2011-02-30 Bad completion date", "Pay bills due:tomorrow", "Pay bills pri:high"] {
+            assert!(parse_line(malformed).is_err(), "{}", malformed);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+// INTEGRATION TESTS
+// Each test drives the compiled binary against its own temporary directory, passing the task file with --file
+// (and running from that directory, so nothing can end up in the working tree).
+use std::{env, fs, io::Write, path::PathBuf, process::{Command, Output, Stdio}};
+
+/// A scratch directory that is removed when the test is over.
+struct Scenario {
+    dir: PathBuf
+}
+
+impl Scenario {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("task-tracker-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scenario { dir }
+    }
+    fn file(&self) -> PathBuf {
+        self.dir.join("tasks.json")
+    }
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_task-tracker"))
+            .args(args)
+            .arg("--file")
+            .arg(self.file())
+            .current_dir(&self.dir)
+            .output()
+            .unwrap()
+    }
+    /// Runs a command without `--file`, so the task file is picked by the options given. The scratch directory stands
+    /// in for the home directory as well, so that the default data directory is inside it.
+    fn run_here(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_task-tracker"))
+            .args(args)
+            .current_dir(&self.dir)
+            .env("HOME", &self.dir)
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env_remove("TASKS_FILE")
+            .output()
+            .unwrap()
+    }
+    /// The task file `run_here` picks with the given options, as printed by `path`.
+    fn path_here(&self, args: &[&str]) -> (PathBuf, String) {
+        let output = stdout(&self.run_here(&[args, &["path"]].concat()));
+        let (path, source) = output.trim_end().rsplit_once(" (").unwrap();
+        (PathBuf::from(path), source.trim_end_matches(')').to_string())
+    }
+    /// Runs a command with `input` piped to its stdin.
+    fn run_with_stdin(&self, args: &[&str], input: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_task-tracker"))
+            .args(args)
+            .arg("--file")
+            .arg(self.file())
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+    /// Runs a command that must succeed without writing to stderr, returning its stdout.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(stderr(&output), "", "{:?}", args);
+        stdout(&output)
+    }
+    fn descriptions(&self) -> Vec<String> {
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(self.file()).unwrap()).unwrap();
+        json["tasks"].as_array().unwrap().iter().map(|task| task["description"].as_str().unwrap().to_string()).collect()
+    }
+}
+
+impl Drop for Scenario {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Stdout with the timestamps replaced, since they depend on when (and in which time zone) the test runs.
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| match line.split_once(": ") {
+            Some((label @ ("Created at" | "Last Update" | "Completed at"), time)) if time != "-" => format!("{}: <time>\n", label),
+            _ => format!("{}\n", line)
+        })
+        .collect()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// The summary line at the end of `list`. 
+fn footer(summary: &str) -> String {
+    format!("------------\n{}\n", summary)
+}
+
+fn task(id: u32, status: &str, description: &str, updated: bool) -> String {
+    let last_update = if updated { "<time>" } else { "-" };
+    format!("------------\nID: {} [{}]\nTask: {}\nCreated at: <time>\nLast Update: {}\n", id, status, description, last_update)
+}
+
+#[test]
+fn task_lifecycle() {
+    let scenario = Scenario::new("lifecycle");
+
+    assert_eq!(scenario.ok(&["add", "Buy milk"]), "Successfully added task (ID: 1).\n");
+    assert_eq!(scenario.ok(&["add", "Walk the dog"]), "Successfully added task (ID: 2).\n");
+    assert_eq!(scenario.ok(&["add", "Call mom"]), "Successfully added task (ID: 3).\n");
+    let all = [task(1, "todo", "Buy milk", false), task(2, "todo", "Walk the dog", false), task(3, "todo", "Call mom", false)];
+    assert_eq!(scenario.ok(&["list"]), all.concat() + &footer("3 todo, 0 in progress, 0 done (3 total)"));
+
+    assert_eq!(scenario.ok(&["mark-done", "2"]), "Successfully updated task (ID: 2).\n");
+    let done = scenario.ok(&["list", "done"]);
+    assert_eq!(done, format!("{}Completed at: <time>\n{}", task(2, "done", "Walk the dog", true), footer("0 todo, 0 in progress, 1 done (1 listed, 3 total)")));
+    assert_eq!(scenario.ok(&["list", "in-progress"]), "No tasks found with the status in progress\n");
+    assert_eq!(scenario.ok(&["list", "todo", "--count"]), "2\n");
+
+    assert_eq!(scenario.ok(&["update", "1", "Buy oat milk"]), "Successfully updated task (ID: 1).\n");
+    assert_eq!(scenario.ok(&["delete", "3"]), "Successfully deleted task (ID: 3).\n");
+    let list = scenario.ok(&["list", "all"]);
+    let summary = footer("1 todo, 0 in progress, 1 done (2 total)");
+    assert_eq!(list, format!("{}{}Completed at: <time>\n{}", task(1, "todo", "Buy oat milk", true), task(2, "done", "Walk the dog", true), summary));
+    // Done tasks are left out unless asked for
+    let open = scenario.ok(&["list"]);
+    assert_eq!(open, task(1, "todo", "Buy oat milk", true) + &footer("1 todo, 0 in progress, 0 done (1 listed, 2 total)"));
+
+    assert_eq!(scenario.descriptions(), ["Buy oat milk", "Walk the dog"]);
+}
+
+#[test]
+fn bad_command_fails() {
+    let scenario = Scenario::new("bad-command");
+    let output = scenario.run(&["frobnicate"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "Application error: Unknown command: frobnicate (run `task-tracker help` to see the commands)\n");
+    assert!(!scenario.file().exists());
+}
+
+#[test]
+fn missing_id_is_reported_on_stderr() {
+    let scenario = Scenario::new("missing-id");
+    scenario.ok(&["add", "Buy milk"]);
+    let output = scenario.run(&["update", "7", "Buy bread"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "Error: ID not found.\n");
+    assert_eq!(scenario.descriptions(), ["Buy milk"]);
+}
+
+#[test]
+fn corrupt_file_needs_repair() {
+    let scenario = Scenario::new("corrupt");
+    fs::write(scenario.file(), "[{\"id\": 1,").unwrap();
+    let output = scenario.run(&["add", "Buy milk"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("is not a valid task database (line 1, column 10)"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("task-tracker repair"), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(scenario.file()).unwrap(), "[{\"id\": 1,");
+
+    assert!(scenario.ok(&["repair"]).starts_with("Moved "));
+    let backups: Vec<String> = fs::read_dir(&scenario.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("tasks.json.corrupt-"))
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(fs::read_to_string(scenario.dir.join(&backups[0])).unwrap(), "[{\"id\": 1,");
+    assert_eq!(scenario.ok(&["add", "Buy milk"]), "Successfully added task (ID: 1).\n");
+    assert_eq!(scenario.descriptions(), ["Buy milk"]);
+}
+
+#[test]
+fn errors_stay_off_stdout() {
+    let scenario = Scenario::new("stderr");
+    scenario.ok(&["add", "Buy milk"]);
+    let output = scenario.run(&["mark-done", "1,8,9"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Successfully updated task (ID: 1).\n");
+    assert_eq!(stderr(&output), "Error: ID not found: 8, 9.\n");
+
+    let output = scenario.run(&["delete"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).starts_with("Application error: "));
+}
+
+#[test]
+fn config_file_sets_defaults() {
+    let scenario = Scenario::new("config");
+    scenario.ok(&["add", "Buy milk"]);
+    scenario.ok(&["add", "Walk the dog"]);
+    scenario.ok(&["mark-done", "2"]);
+    fs::write(scenario.dir.join("task-tracker.toml"), "default_list_filter = \"todo\"\n").unwrap();
+
+    assert_eq!(scenario.ok(&["list"]), task(1, "todo", "Buy milk", false) + &footer("1 todo, 0 in progress, 0 done (1 listed, 2 total)"));
+    assert!(scenario.ok(&["list", "done"]).contains("Walk the dog"));
+
+    fs::write(scenario.dir.join("task-tracker.toml"), "default_list_filter = todo").unwrap();
+    let output = scenario.run(&["list"]);
+    assert!(output.status.success());
+    assert!(stderr(&output).starts_with("Warning: ignoring the config file "), "{}", stderr(&output));
+    // Done tasks are hidden by default, so "Walk the dog" can't show that the filter was ignored
+    assert!(stdout(&output).contains("Buy milk"));
+
+    let output = scenario.run(&["list", "--strict"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Invalid config file "), "{}", stderr(&output));
+}
+
+#[test]
+fn config_file_can_bring_back_done_tasks() {
+    let scenario = Scenario::new("config-all");
+    scenario.ok(&["add", "Buy milk"]);
+    scenario.ok(&["add", "Walk the dog"]);
+    scenario.ok(&["mark-done", "2"]);
+    assert!(!scenario.ok(&["list"]).contains("Walk the dog"));
+
+    fs::write(scenario.dir.join("task-tracker.toml"), "default_list_filter = \"all\"\n").unwrap();
+    let list = scenario.ok(&["list"]);
+    assert!(list.contains("Buy milk") && list.contains("Walk the dog"), "{}", list);
+    assert!(!scenario.ok(&["list", "todo"]).contains("Walk the dog"));
+}
+
+#[test]
+fn add_from_stdin() {
+    let scenario = Scenario::new("stdin");
+    scenario.ok(&["add", "Buy milk"]);
+    let input: String = (1..=2000).map(|n| format!("# batch {}\r\nTask {}\r\n\r\n", n, n)).collect();
+
+    let output = scenario.run_with_stdin(&["add", "--stdin"], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "Successfully added 2000 tasks (IDs: 2-2001).\n");
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(scenario.file()).unwrap()).unwrap();
+    let ids: Vec<u64> = json["tasks"].as_array().unwrap().iter().map(|task| task["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, (1..=2001).collect::<Vec<u64>>());
+    assert_eq!(scenario.descriptions()[2000], "Task 2000");
+
+    // A line that is too long stops the whole batch
+    let output = scenario.run_with_stdin(&["add", "--stdin"], &format!("Call mom\n{}\n", "x".repeat(10_001)));
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Line 2: "), "{}", stderr(&output));
+    assert_eq!(scenario.descriptions().len(), 2001);
+}
+
+#[test]
+fn projects_have_their_own_files() {
+    let scenario = Scenario::new("projects");
+    assert_eq!(stdout(&scenario.run_here(&["projects"])), "No projects found.\n");
+    for args in [&["--project", "work", "add", "Write report"][..], &["--project", "home", "add", "Water plants"], &["add", "Unfiled"]] {
+        assert!(scenario.run_here(args).status.success(), "{:?}", args);
+    }
+    assert!(scenario.run_here(&["--project", "home", "mark-done", "1"]).status.success());
+
+    let (work_file, _) = scenario.path_here(&["--project", "work"]);
+    assert!(work_file.ends_with("task-tracker/tasks.work.json") && work_file.exists(), "{:?}", work_file);
+    assert!(scenario.path_here(&["--project", "home"]).0.exists());
+    assert_eq!(stdout(&scenario.run_here(&["projects"])), "home\nwork\n");
+    let work = stdout(&scenario.run_here(&["--project", "work", "list", "all"]));
+    assert!(work.contains("Write report") && !work.contains("Water plants") && !work.contains("Unfiled"), "{}", work);
+    assert!(stdout(&scenario.run_here(&["--project", "work", "list", "done"])).contains("No tasks"));
+    let unfiled = stdout(&scenario.run_here(&["list", "all"]));
+    assert!(unfiled.contains("Unfiled") && !unfiled.contains("Write report"), "{}", unfiled);
+}
+
+#[test]
+fn task_files_go_in_the_data_directory_unless_there_is_one_here() {
+    let scenario = Scenario::new("path");
+    let (default, source) = scenario.path_here(&[]);
+    assert_eq!(source, "the default, in the data directory");
+    assert!(default.starts_with(&scenario.dir) && default.ends_with("task-tracker/tasks.json"), "{:?}", default);
+    assert!(default.parent().unwrap().is_dir());
+
+    fs::write(scenario.dir.join("tasks.json"), "[]").unwrap();
+    let (here, source) = scenario.path_here(&[]);
+    assert_eq!((here, source.as_str()), (scenario.dir.canonicalize().unwrap().join("tasks.json"), "found in the working directory"));
+    assert_eq!(scenario.path_here(&["--file", "other.json"]).1, "given with --file");
+}
+
+#[test]
+fn deleted_tasks_can_be_restored_until_the_trash_is_emptied() {
+    let scenario = Scenario::new("trash");
+    for description in ["Buy milk", "Walk the dog", "Call mom"] {
+        scenario.ok(&["add", description]);
+    }
+
+    assert_eq!(scenario.ok(&["delete", "2,3"]), "Successfully deleted task (ID: 2).\nSuccessfully deleted task (ID: 3).\n");
+    assert_eq!(scenario.descriptions(), ["Buy milk"]);
+    assert!(!scenario.ok(&["list", "all"]).contains("Walk the dog"));
+    let trash = scenario.ok(&["list", "--trash"]);
+    assert!(trash.contains("Walk the dog") && trash.contains("Call mom"), "{}", trash);
+
+    assert_eq!(scenario.ok(&["restore", "2"]), "Successfully restored task (ID: 2).\n");
+    assert_eq!(scenario.descriptions(), ["Buy milk", "Walk the dog"]);
+
+    assert_eq!(scenario.ok(&["empty-trash"]), "Permanently deleted 1 task(s) from the trash.\n");
+    assert_eq!(scenario.ok(&["empty-trash"]), "The trash is empty.\n");
+    let output = scenario.run(&["restore", "3"]);
+    assert_eq!(stderr(&output), "Error: ID not found in the trash.\n");
+    assert_eq!(scenario.descriptions(), ["Buy milk", "Walk the dog"]);
+}
+
+#[test]
+fn count_prints_a_bare_number() {
+    let scenario = Scenario::new("count");
+    assert_eq!(scenario.run(&["count"]).stdout, b"0\n");
+    scenario.ok(&["add", "Write report", "--tag", "work"]);
+    scenario.ok(&["add", "Review PR", "--tag", "Work", "--status", "in-progress"]);
+    scenario.ok(&["add", "Buy milk", "--tag", "home"]);
+    scenario.ok(&["mark-done", "3"]);
+
+    for (args, expected) in [
+        (&["count"][..], "2\n"),
+        (&["count", "todo"], "1\n"),
+        (&["count", "all"], "3\n"),
+        (&["count", "--tag", "work", "--status", "in-progress"], "1\n"),
+        (&["count", "--tag", "#WORK"], "2\n"),
+        (&["count", "--tag", "garden"], "0\n"),
+        (&["count", "--json", "done"], "1\n"),
+    ] {
+        let output = scenario.run(args);
+        assert!(output.status.success(), "{:?}", args);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected, "{:?}", args);
+        assert!(output.stderr.is_empty(), "{:?}", args);
+    }
+    let output = scenario.run(&["count", "--status", "blocked"]);
+    assert!(!output.status.success() && output.stdout.is_empty());
+}
+
+#[test]
+fn porcelain_output_is_one_stable_line_per_task() {
+    let scenario = Scenario::new("porcelain");
+    for (args, expected) in [
+        (&["add", "Buy milk"][..], "added 1\n"),
+        (&["add", "Fix C:\\temp"], "added 2\n"),
+        (&["mark-in-progress", "1"], "marked 1 in-progress\n"),
+        (&["mark-done", "1,2"], "marked 1 done\nmarked 2 done\n"),
+        (&["toggle", "2"], "marked 2 todo\n"),
+        (&["priority", "2", "high"], "updated 2\n"),
+        (&["note", "2", "Ask IT"], "updated 2\n"),
+        (&["block", "2", "--on", "1"], "blocked 2 1\n"),
+        (&["unblock", "2", "--on", "1"], "unblocked 2 1\n"),
+        (&["copy", "2", "Water plants"], "copied 2 3\n"),
+        (&["move", "3", "1"], "moved 3 1\n"),
+        (&["start", "2"], "started 2\n"),
+        (&["stop", "2"], "stopped 2\n"),
+        (&["set-repeat", "3", "daily"], "updated 3\n"),
+        (&["mark-done", "3"], "marked 3 done\nrespawned 4\n"),
+        (&["list", "all"], "3\tdone\tWater plants\n1\tdone\tBuy milk\n2\ttodo\tFix C:\\\\temp\n4\ttodo\tWater plants\n"),
+        (&["delete", "4"], "deleted 4\n"),
+        (&["restore", "4"], "restored 4 4\n"),
+        (&["archive"], "archived 3\narchived 1\n"),
+        (&["list"], "2\ttodo\tFix C:\\\\temp\n4\ttodo\tWater plants\n"),
+        (&["list", "done"], ""),
+        (&["count"], "2\n"),
+        (&["show", "9"], ""),
+    ] {
+        let output = scenario.run(&[&["--porcelain"], args].concat());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{:?}", args);
+    }
+    // Quiet leaves only the exit code, and the ID of a new task
+    let output = scenario.run(&["-q", "mark-done", "2"]);
+    assert!(output.status.success() && output.stdout.is_empty());
+    assert_eq!(scenario.ok(&["-q", "add", "Call mom"]), "5\n");
+    assert_eq!(scenario.ok(&["--json", "--dry-run", "add", "Call dad"]), "6\n");
+    assert_eq!(scenario.ok(&["--json", "add", "Call dad"]), "6\n");
+    assert!(!scenario.run(&["-q", "--porcelain", "list"]).status.success());
+}